@@ -0,0 +1,500 @@
+//! The command line of the binary.
+//!
+//! The binary plays games, verifies their transcripts and manages the keys of the players:
+//!
+//! - `play` plays a tournament between players given by their mnemonic or keystore file, or with
+//!   fresh keys, and saves its transcript. It is the default when no command is given.
+//! - `verify <transcript>` replays a saved transcript and recomputes the standings.
+//! - `keygen` generates a new identity, with its mnemonic and SS58 address.
+//! - `simulate` plays many seeded tournaments between betting strategies on parallel threads,
+//!   and reports how each of them did (see `poker::simulation`).
+//! - `inspect <address>` shows the public key of an address, and the key it plays a game with.
+//! - `host <address>` and `join <address>` play a game over the network, each player in their
+//!   own process (see `poker::net`), and save its transcript.
+//!
+//! Every command exits with `EXIT_OK` when it completes and nobody cheated, and `EXIT_CHEATING`
+//! when a player was caught cheating in a game it played or verified. A transcript that does not
+//! verify, a bad key or a file that can't be read exit with `EXIT_ERROR`, and a bad command line
+//! with `EXIT_USAGE`.
+
+use std::path::PathBuf;
+
+use crate::poker::{Cheat, Dealing, KeySource, StrategyKind, Variant};
+
+/// The command completed and nobody cheated
+pub const EXIT_OK: i32 = 0;
+/// The command could not complete: a transcript does not verify, a key or a file is bad
+pub const EXIT_ERROR: i32 = 1;
+/// The command line is not valid
+pub const EXIT_USAGE: i32 = 2;
+/// A player was caught cheating
+pub const EXIT_CHEATING: i32 = 3;
+
+/// The file the transcript of a game is saved to, unless another is given
+pub const TRANSCRIPT_FILE: &str = "game.transcript";
+
+/// How to use the binary
+pub const USAGE: &str = "\
+usage: poker <command> [options]
+
+commands:
+  play                   play a tournament and save its transcript (the default)
+  verify <transcript>    replay a saved transcript and recompute the standings
+  keygen                 generate a new identity
+  simulate               play many tournaments between betting strategies and report how
+                         each of them did
+  inspect <address>      show the public keys of an SS58 address
+  host <address>         host a game over the network on an address, like 127.0.0.1:7878,
+                         and play it with the players who join it
+  join <address>         join the game hosted on an address, and play it
+
+options of play and simulate:
+  --variant <name>       five-card, holdem or draw (five-card)
+  --dealing <name>       vrf or deck (vrf)
+  --players <count>      the number of players, 2 to 10 (2, or one per identity given)
+  --stack <points>       the points every player starts with (100)
+  --rng-seed <seed>      the seed of the random number generator of the first player, the
+                         next players counting up from it (0)
+  --cheat <seat>:<name>  have the player in a seat cheat: bad-reveal, invalid-vrf-proof,
+                         double-commitment, equivocation, bad-shuffle, bad-decryption,
+                         stall-commit, stall-reveal or stall-showdown. In simulate, the seat
+                         is the index of a strategy in every matchup, whose player cheats
+                         whatever seat it takes
+
+options of play:
+  --seed <mnemonic>      the mnemonic of the next player, in seat order
+  --keystore <file>      the keystore file of the next player, in seat order
+  --game-id <id>         the id of the game (0)
+  --transcript <file>    where to save the transcript (game.transcript)
+
+options of simulate:
+  --matchup <strategies> the strategies of the players, separated by commas, from linear,
+                         constant:<amount>, kelly, kelly:<fraction> and bluff:<probability>.
+                         Can be given more than once (linear and kelly, taking turns for
+                         every player)
+  --games <count>        the number of tournaments of every matchup (100)
+  --max-hands <count>    stop every tournament after some hands
+  --threads <count>      the number of threads to play on (one per core)
+  --format <name>        table or csv (table)
+
+options of host and join, which every player of the game gives the same but for their key:
+  --variant <name>       five-card or holdem (five-card)
+  --stack <points>       the points every player starts with (100)
+  --seed <mnemonic>      the mnemonic of our player (fresh keys)
+  --keystore <file>      the keystore file of our player
+  --rng-seed <seed>      the seed of the random number generator of our player (random)
+  --game-id <id>         the id of the game (0)
+  --transcript <file>    where to save the transcript (game.transcript)
+
+options of host:
+  --players <count>      the number of players, the host included, 2 to 10 (2)
+
+options of keygen:
+  --out <file>           save the identity to a keystore file
+
+options of inspect:
+  --game-id <id>         the game to derive the key of (0)
+
+exit codes: 0 when the command completes and nobody cheated, 1 on errors, 2 on a bad command
+line, and 3 when a player was caught cheating";
+
+/// The table of the games played by `play` and `simulate`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableOptions {
+    pub variant: Variant,
+    pub dealing: Dealing,
+    /// The number of players, if given
+    pub players: Option<usize>,
+    /// The points every player starts with
+    pub stack: u32,
+    /// The seed of the random number generator of the first player, the next ones counting up
+    pub rng_seed: u64,
+    /// The players who cheat, by seat, or by strategy of the matchups in a simulation
+    pub cheats: Vec<(usize, Cheat)>,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        TableOptions {
+            variant: Variant::default(),
+            dealing: Dealing::default(),
+            players: None,
+            stack: 100,
+            rng_seed: 0,
+            cheats: Vec::new(),
+        }
+    }
+}
+
+impl TableOptions {
+    /// Set the option of a flag, if it is one of the table. Returns whether it is.
+    fn set(&mut self, flag: &str, value: &str) -> Result<bool, String> {
+        match flag {
+            "variant" => {
+                self.variant =
+                    Variant::from_name(value).map_err(|_| format!("unknown variant {}", value))?
+            }
+            "dealing" => {
+                self.dealing = Dealing::from_name(value)
+                    .map_err(|_| format!("unknown dealing mode {}", value))?
+            }
+            "players" => self.players = Some(number(flag, value)?),
+            "stack" => self.stack = number(flag, value)?,
+            "rng-seed" => self.rng_seed = number(flag, value)?,
+            "cheat" => {
+                let (seat, name) = value
+                    .split_once(':')
+                    .ok_or(format!("--cheat takes <seat>:<name>, not {}", value))?;
+                let cheat =
+                    Cheat::from_name(name).map_err(|_| format!("unknown cheat {}", name))?;
+                self.cheats.push((number(flag, seat)?, cheat));
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// What `play` plays
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlayOptions {
+    pub table: TableOptions,
+    /// Where the identities of the first players come from, in seat order. The other players get
+    /// fresh keys.
+    pub sources: Vec<KeySource>,
+    pub game_id: u64,
+    /// Where to save the transcript
+    pub transcript: PathBuf,
+}
+
+impl Default for PlayOptions {
+    fn default() -> Self {
+        PlayOptions {
+            table: TableOptions::default(),
+            sources: Vec::new(),
+            game_id: 0,
+            transcript: PathBuf::from(TRANSCRIPT_FILE),
+        }
+    }
+}
+
+/// What `simulate` simulates
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulateOptions {
+    /// The table of every game. Its seed is the seed of the simulation.
+    pub table: TableOptions,
+    /// The strategies of every matchup
+    pub matchups: Vec<Vec<StrategyKind>>,
+    pub games: u32,
+    pub max_hands: Option<u32>,
+    pub threads: Option<usize>,
+    /// Report as CSV instead of a table
+    pub csv: bool,
+}
+
+impl Default for SimulateOptions {
+    fn default() -> Self {
+        SimulateOptions {
+            table: TableOptions::default(),
+            matchups: Vec::new(),
+            games: 100,
+            max_hands: None,
+            threads: None,
+            csv: false,
+        }
+    }
+}
+
+/// What `host` and `join` play over the network
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetOptions {
+    /// The address the host listens on, and the clients connect to
+    pub address: String,
+    pub variant: Variant,
+    /// The points every player starts with
+    pub stack: u32,
+    /// Where the identity of our player comes from, fresh keys if not given
+    pub source: Option<KeySource>,
+    /// The seed of the random number generator of our player, a random one if not given
+    pub rng_seed: Option<u64>,
+    pub game_id: u64,
+    /// Where to save the transcript
+    pub transcript: PathBuf,
+    /// The number of players, the host included. Only the host gives it.
+    pub players: usize,
+}
+
+impl NetOptions {
+    fn new(address: &str) -> Self {
+        NetOptions {
+            address: address.to_string(),
+            variant: Variant::default(),
+            stack: 100,
+            source: None,
+            rng_seed: None,
+            game_id: 0,
+            transcript: PathBuf::from(TRANSCRIPT_FILE),
+            players: 2,
+        }
+    }
+}
+
+/// A command of the binary
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Play(PlayOptions),
+    Host(NetOptions),
+    Join(NetOptions),
+    Verify { transcript: PathBuf },
+    Keygen { out: Option<PathBuf> },
+    Simulate(SimulateOptions),
+    Inspect { address: String, game_id: u64 },
+    Help,
+}
+
+/// Parse a number given to a flag
+fn number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("--{} takes a number, not {}", flag, value))
+}
+
+/// The flags of a command, without their dashes, each with its value
+type Flags<'a> = Vec<(&'a str, &'a str)>;
+
+/// Split the arguments of a command into its positional arguments and its flags. Errors if a flag
+/// has no value.
+fn split(args: &[String]) -> Result<(Vec<&str>, Flags<'_>), String> {
+    let mut positional = Vec::new();
+    let mut flags = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--") {
+            Some(flag) => {
+                let value = args.next().ok_or(format!("--{} needs a value", flag))?;
+                flags.push((flag, value.as_str()));
+            }
+            None => positional.push(arg.as_str()),
+        }
+    }
+    Ok((positional, flags))
+}
+
+/// Parse the command line, without the name of the binary. Errors with what is wrong with it.
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    let (command, args) = match args.split_first() {
+        Some((command, args)) => (command.as_str(), args),
+        None => return Ok(Command::Play(PlayOptions::default())),
+    };
+    if matches!(command, "help" | "-h" | "--help") {
+        return Ok(Command::Help);
+    }
+    let (positional, flags) = split(args)?;
+    let expected = match command {
+        "verify" | "inspect" | "host" | "join" => 1,
+        _ => 0,
+    };
+    if positional.len() != expected {
+        return Err(format!(
+            "{} takes {} argument(s), not {}",
+            command,
+            expected,
+            positional.len()
+        ));
+    }
+    let unknown = |flag: &str| Err(format!("{} has no option --{}", command, flag));
+
+    match command {
+        "play" => {
+            let mut options = PlayOptions::default();
+            for (flag, value) in flags {
+                match flag {
+                    "seed" => options.sources.push(KeySource::Mnemonic(value.to_string())),
+                    "keystore" => options.sources.push(KeySource::Keystore(value.into())),
+                    "game-id" => options.game_id = number(flag, value)?,
+                    "transcript" => options.transcript = value.into(),
+                    _ if options.table.set(flag, value)? => {}
+                    _ => return unknown(flag),
+                }
+            }
+            Ok(Command::Play(options))
+        }
+        "verify" => match flags.first() {
+            Some((flag, _)) => unknown(flag),
+            None => Ok(Command::Verify {
+                transcript: positional[0].into(),
+            }),
+        },
+        "keygen" => {
+            let mut out = None;
+            for (flag, value) in flags {
+                match flag {
+                    "out" => out = Some(value.into()),
+                    _ => return unknown(flag),
+                }
+            }
+            Ok(Command::Keygen { out })
+        }
+        "simulate" => {
+            let mut options = SimulateOptions::default();
+            for (flag, value) in flags {
+                match flag {
+                    "matchup" => options.matchups.push(
+                        value
+                            .split(',')
+                            .map(|name| {
+                                StrategyKind::from_name(name)
+                                    .map_err(|_| format!("unknown strategy {}", name))
+                            })
+                            .collect::<Result<_, _>>()?,
+                    ),
+                    "games" => options.games = number(flag, value)?,
+                    "max-hands" => options.max_hands = Some(number(flag, value)?),
+                    "threads" => options.threads = Some(number(flag, value)?),
+                    "format" => {
+                        options.csv = match value {
+                            "table" => false,
+                            "csv" => true,
+                            _ => return Err(format!("unknown format {}", value)),
+                        }
+                    }
+                    _ if options.table.set(flag, value)? => {}
+                    _ => return unknown(flag),
+                }
+            }
+            Ok(Command::Simulate(options))
+        }
+        "inspect" => {
+            let mut game_id = 0;
+            for (flag, value) in flags {
+                match flag {
+                    "game-id" => game_id = number(flag, value)?,
+                    _ => return unknown(flag),
+                }
+            }
+            Ok(Command::Inspect {
+                address: positional[0].to_string(),
+                game_id,
+            })
+        }
+        "host" | "join" => {
+            let mut options = NetOptions::new(positional[0]);
+            for (flag, value) in flags {
+                match flag {
+                    "variant" => {
+                        options.variant = Variant::from_name(value)
+                            .map_err(|_| format!("unknown variant {}", value))?
+                    }
+                    "stack" => options.stack = number(flag, value)?,
+                    "seed" => options.source = Some(KeySource::Mnemonic(value.to_string())),
+                    "keystore" => options.source = Some(KeySource::Keystore(value.into())),
+                    "rng-seed" => options.rng_seed = Some(number(flag, value)?),
+                    "game-id" => options.game_id = number(flag, value)?,
+                    "transcript" => options.transcript = value.into(),
+                    "players" if command == "host" => options.players = number(flag, value)?,
+                    _ => return unknown(flag),
+                }
+            }
+            match command {
+                "host" => Ok(Command::Host(options)),
+                _ => Ok(Command::Join(options)),
+            }
+        }
+        _ => Err(format!("unknown command {}", command)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poker::Phase;
+
+    fn parse_line(line: &str) -> Result<Command, String> {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        parse(&args)
+    }
+
+    #[test]
+    fn play_is_the_default() {
+        assert_eq!(parse_line(""), Ok(Command::Play(PlayOptions::default())));
+        assert_eq!(
+            parse_line("play"),
+            Ok(Command::Play(PlayOptions::default()))
+        );
+        assert_eq!(parse_line("--help"), Ok(Command::Help));
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        let command = parse_line(
+            "play --variant holdem --players 4 --keystore a.key --stack 50 --cheat 2:stall-reveal",
+        );
+        let expected = PlayOptions {
+            table: TableOptions {
+                variant: Variant::Holdem,
+                players: Some(4),
+                stack: 50,
+                cheats: vec![(2, Cheat::Stall(Phase::Reveal))],
+                ..TableOptions::default()
+            },
+            sources: vec![KeySource::Keystore("a.key".into())],
+            ..PlayOptions::default()
+        };
+        assert_eq!(command, Ok(Command::Play(expected)));
+        assert_eq!(
+            parse_line("simulate --games 20 --matchup linear,kelly:0.5 --format csv"),
+            Ok(Command::Simulate(SimulateOptions {
+                matchups: vec![vec![StrategyKind::Linear, StrategyKind::Kelly(0.5)]],
+                games: 20,
+                csv: true,
+                ..SimulateOptions::default()
+            }))
+        );
+        assert_eq!(
+            parse_line("host 127.0.0.1:7878 --players 3 --variant holdem --rng-seed 5"),
+            Ok(Command::Host(NetOptions {
+                variant: Variant::Holdem,
+                rng_seed: Some(5),
+                players: 3,
+                ..NetOptions::new("127.0.0.1:7878")
+            }))
+        );
+        assert_eq!(
+            parse_line("join 127.0.0.1:7878 --keystore b.key"),
+            Ok(Command::Join(NetOptions {
+                source: Some(KeySource::Keystore("b.key".into())),
+                ..NetOptions::new("127.0.0.1:7878")
+            }))
+        );
+        assert_eq!(
+            parse_line("inspect 5GEkFD1WxzmfasT7yMUERDprkEueFEDrSojE3ajwxXvfYYaF --game-id 3"),
+            Ok(Command::Inspect {
+                address: "5GEkFD1WxzmfasT7yMUERDprkEueFEDrSojE3ajwxXvfYYaF".to_string(),
+                game_id: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn bad_command_lines_are_refused() {
+        for line in [
+            "deal",
+            "verify",
+            "verify a.transcript b.transcript",
+            "play --players",
+            "play --players two",
+            "play --variant stud",
+            "play --cheat 1",
+            "play --cheat 1:peek",
+            "simulate --seed word",
+            "simulate --matchup linear,martingale",
+            "simulate --format json",
+            "keygen --game-id 1",
+            "host",
+            "join 127.0.0.1:7878 --players 3",
+            "host 127.0.0.1:7878 --cheat 1:bad-reveal",
+        ] {
+            assert!(parse_line(line).is_err(), "{}", line);
+        }
+    }
+}
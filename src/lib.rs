@@ -0,0 +1,8 @@
+//! The verifiable poker engine and the hashing exercises it is built on, as a library, so the
+//! binary, tests, bots or a UI can all drive the same games.
+
+// The engine reports the errors that carry no detail as `()`, like the exercises it builds on
+#![allow(clippy::result_unit_err)]
+
+pub mod p1_hashing;
+pub mod poker;
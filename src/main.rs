@@ -1,150 +1,367 @@
-mod p1_hashing;
+mod cli;
 mod p2_addresses;
 
-use std::cmp::min;
+use std::net::TcpListener;
+use std::path::Path;
 
-use byteorder::{LittleEndian, ReadBytesExt};
-use hex::ToHex;
-use sp_core::ecdsa::{Pair as ECDSAPair, Public as ECDSAPublic};
-use sp_core::ed25519::{Pair as Ed25519Pair, Public as Ed25519Public, Signature as Ed25519Signature};
-use sp_core::sr25519::{Pair as Sr25519Pair, Public as Sr25519Public};
-use sp_core::{
-    crypto::{Derive, DeriveJunction, Ss58Codec},
-    Pair, Public,
+use cli::{
+    Command, NetOptions, PlayOptions, SimulateOptions, TableOptions, EXIT_CHEATING, EXIT_ERROR,
+    EXIT_OK, EXIT_USAGE,
 };
-use rand::{Rng, rngs::SmallRng, SeedableRng, rngs::OsRng};
-use schnorrkel::{Keypair,Signature};
-use sp_runtime::print;
-use sp_runtime::traits::Verify;
-use crate::p1_hashing::hash_with_blake;
+use pba_cryptography_group_6_activity_3::poker;
+use poker::keys::{derive_game_public, ss58_address};
+use poker::tournament::transcript_standings;
+use poker::{
+    best_hand, verify_tournament, verify_transcript, Game, Identity, Peer, Player, Round, Schedule,
+    Simulation, Standing, StrategyKind, Tournament, Transcript,
+};
+use schnorrkel::PublicKey;
 
+/// The deposit every player puts aside when they sit down, lost if they miss a deadline
+const DEPOSIT: u32 = 10;
 
-pub fn generate_ed25519_pair(bip39_str: &str) -> Ed25519Pair {
-    Ed25519Pair::from_phrase(bip39_str, None).unwrap().0
+fn print_round(round: &Round) {
+    println!(
+        "Button: player {}, blinds {}/{}, ante {}",
+        round.button, round.blinds.small, round.blinds.big, round.blinds.ante
+    );
+    for (seat, action) in &round.actions {
+        println!("Player {} {}", seat, action);
+    }
+    for (seat, _, slots) in &round.discards {
+        println!("Player {} discards {} cards", seat, slots.len());
+    }
+    if !round.community.is_empty() {
+        let board: Vec<String> = round
+            .community
+            .iter()
+            .map(|card| card.to_string())
+            .collect();
+        println!("Board: {}", board.join(" "));
+    }
+    for (i, seat) in round.seats.iter().enumerate() {
+        match &round.hands[i] {
+            Some(hand) => {
+                let cards: Vec<String> = hand.iter().map(|card| card.to_string()).collect();
+                println!(
+                    "Player {}: HAND {} ({}) BET {}",
+                    seat,
+                    cards.join(" "),
+                    best_hand(&[hand.as_slice(), &round.community].concat()),
+                    round.contributions[i]
+                )
+            }
+            None => println!("Player {}: HAND ?? BET {}", seat, round.contributions[i]),
+        }
+    }
+    // the main pot comes first, then the side pots of the all-ins
+    for (i, pot) in round.pots.iter().enumerate() {
+        let name = match i {
+            0 => "Pot",
+            _ => "Side pot",
+        };
+        println!("{}: {} won by {:?}", name, pot.amount, pot.winners);
+    }
+    if let Some(missed) = round.missed {
+        println!("DEADLINE MISSED: {}", missed);
+    }
+    println!("/////////");
+    println!("          ");
 }
-fn main() {
-    
-    let mut points_0 =100;
-    let mut points_1 =100;
-    
-    let mut rng_0 = SmallRng::seed_from_u64(0);
-    let mut rng_1 = SmallRng::seed_from_u64(1);
 
-    while points_0 > 0 && points_1 > 0 {
-    println!("CURRENT POINTS : Player 0: {} Player 1: {}", points_0, points_1);
+fn print_standings(standings: &[Standing]) {
+    for (place, standing) in standings.iter().enumerate() {
+        match standing.out_in {
+            Some(hand) => println!(
+                "{}. PLAYER {} OUT IN HAND {}",
+                place + 1,
+                standing.seat,
+                hand
+            ),
+            None => println!("{}. PLAYER {} WINS", place + 1, standing.seat),
+        }
+    }
+}
 
-        const SEED1: &str = "lunar antique thank school space garden soda cigar glove despair master pumpkin";
-    const SEED2: &str = "parade method erupt shock bacon wait follow limb onion laugh exhibit whip";
+/// Start a tournament at a table between some players, and as many players with fresh keys as
+/// the table has seats left. Errors if there are too many players, or a cheater has no seat.
+fn new_tournament(
+    table: &TableOptions,
+    mut identities: Vec<Identity>,
+    game_id: u64,
+) -> Result<Tournament, String> {
+    let seats = table.players.unwrap_or(identities.len().max(2));
+    if identities.len() > seats {
+        return Err(format!("{} players for {} seats", identities.len(), seats));
+    }
+    identities.extend(std::iter::repeat_with(Identity::generate).take(seats - identities.len()));
+    let mut players: Vec<Player> = identities
+        .into_iter()
+        .enumerate()
+        .map(|(seat, identity)| {
+            Player::from_identity(identity, table.stack, table.rng_seed + seat as u64)
+        })
+        .collect();
+    for (seat, cheat) in &table.cheats {
+        let player = players
+            .get_mut(*seat)
+            .ok_or(format!("there is no player in seat {}", seat))?;
+        player.cheat = Some(*cheat);
+    }
+    let game = Game::new(game_id, players)
+        .map_err(|_| "2 to 10 players can play".to_string())?
+        .with_variant(table.variant)
+        .with_dealing(table.dealing)
+        .with_deposit(DEPOSIT);
+    Ok(Tournament::new(game, Schedule::standard()))
+}
 
-    let player_0 = generate_ed25519_pair(SEED1);
-    let player_1 = generate_ed25519_pair(SEED2);
+/// Play a tournament hand by hand, save its transcript and verify it
+fn play(options: &PlayOptions) -> Result<i32, String> {
+    let identities = options
+        .sources
+        .iter()
+        .map(|source| {
+            Identity::from_source(source).map_err(|_| format!("invalid key source {:?}", source))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let table = &options.table;
+    let mut tournament = new_tournament(table, identities, options.game_id)?;
+    for (seat, player) in tournament.game.players.iter().enumerate() {
+        println!("PLAYER {}: {}", seat, player.address());
+    }
 
-    // let player_0: Keypair = Keypair::generate_with(OsRng);
-    // let player_1: Keypair = Keypair::generate_with(OsRng);
-    // player_0.secret;
+    while !tournament.is_over() {
+        let points: Vec<u32> = tournament
+            .game
+            .players
+            .iter()
+            .map(|player| player.points)
+            .collect();
+        println!("CURRENT POINTS : {:?}", points);
 
-    
-    let randomness_0: [u8; 4] = rng_0.gen();
-    let randomness_1: [u8; 4] = rng_1.gen();
+        match tournament.play_hand() {
+            Ok(round) => print_round(&round),
+            Err(evidence) => println!("CHEATING DETECTED: {}", evidence),
+        }
+    }
+    print_standings(&tournament.standings());
+    let game = &tournament.game;
 
-    // dbg!(randomness_0);
-    // dbg!(randomness_1);
+    // anyone can check the game afterwards from the saved transcript
+    game.transcript
+        .save(&options.transcript)
+        .map_err(|e| format!("could not save the transcript: {}", e))?;
+    println!("TRANSCRIPT SAVED TO {}", options.transcript.display());
+    let standings = verify_tournament(&game.transcript)
+        .map_err(|e| format!("the transcript does not verify: {}", e))?;
+    println!("TRANSCRIPT VERIFIED, WINNER PLAYER {}", standings[0].seat);
+    match game.evidence.is_empty() {
+        true => Ok(EXIT_OK),
+        false => Ok(EXIT_CHEATING),
+    }
+}
 
-    // The random values have been shared now
-    let commited_hash_0=hash_with_blake(&randomness_0);
-    let commited_hash_1=hash_with_blake(&randomness_1);
+/// Host a game over the network and wait for the other players to join it, or join one, then play
+/// it round by round with the other peers, save its transcript and verify it
+fn play_over_network(options: &NetOptions, host: bool) -> Result<i32, String> {
+    let identity = match &options.source {
+        Some(source) => {
+            Identity::from_source(source).map_err(|_| format!("invalid key source {:?}", source))?
+        }
+        None => Identity::generate(),
+    };
+    let rng_seed = options.rng_seed.unwrap_or_else(rand::random);
+    let player = Player::from_identity(identity, options.stack, rng_seed);
+    println!("PLAYER: {}", player.address());
+    let (game_id, variant, stack) = (options.game_id, options.variant, options.stack);
+    let peer = match host {
+        true => {
+            let listener = TcpListener::bind(&options.address)
+                .map_err(|e| format!("could not listen on {}: {}", options.address, e))?;
+            let address = listener
+                .local_addr()
+                .map_err(|e| format!("could not listen on {}: {}", options.address, e))?;
+            println!("HOSTING GAME {} ON {}", game_id, address);
+            let clients = options.players.saturating_sub(1);
+            Peer::host(&listener, player, game_id, variant, stack, clients)
+        }
+        false => Peer::join(&options.address, player, game_id, variant, stack),
+    };
+    let mut peer = peer.map_err(|e| format!("could not start the game: {}", e))?;
+    println!("SEAT {} OF {}", peer.seat, peer.transcript.players.len());
 
-    // now the random are shared
-    let revealed_0 = randomness_0;
-    let revealed_1 = randomness_1;
+    while !peer.is_over() {
+        println!("CURRENT POINTS : {:?}", peer.points());
+        match peer.play_round() {
+            Ok(Ok(round)) => print_round(&round),
+            Ok(Err(evidence)) => println!("CHEATING DETECTED: {}", evidence),
+            Err(e) => return Err(format!("the game stopped: {}", e)),
+        }
+    }
+    println!("FINAL POINTS: {:?}", peer.points());
 
-    // now the random are verified from the commitments
-    if hash_with_blake(&revealed_0) != commited_hash_0 {
-       panic!("hes cheating");
+    // every peer keeps the same transcript, which anyone can check afterwards
+    peer.transcript
+        .save(&options.transcript)
+        .map_err(|e| format!("could not save the transcript: {}", e))?;
+    println!("TRANSCRIPT SAVED TO {}", options.transcript.display());
+    verify_transcript(&peer.transcript)
+        .map_err(|e| format!("the transcript does not verify: {}", e))?;
+    println!("TRANSCRIPT VERIFIED, WINNER PLAYER {}", peer.seats_in()[0]);
+    match peer.evidence.is_empty() {
+        true => Ok(EXIT_OK),
+        false => Ok(EXIT_CHEATING),
+    }
+}
 
+/// Replay a saved transcript, and show the cheaters it caught and the standings
+fn verify(path: &Path) -> Result<i32, String> {
+    let transcript = Transcript::load(path)
+        .map_err(|_| format!("could not read a transcript from {}", path.display()))?;
+    let verified = verify_transcript(&transcript)
+        .map_err(|e| format!("the transcript does not verify: {}", e))?;
+    println!(
+        "TRANSCRIPT VERIFIED: GAME {}, {} {}, {} PLAYERS",
+        transcript.game_id,
+        transcript.variant.name(),
+        transcript.dealing.name(),
+        transcript.players.len()
+    );
+    for evidence in &verified.evidence {
+        println!("CHEATING DETECTED: {}", evidence);
     }
-    // now the random are verified from the commitments
-    if hash_with_blake(&revealed_1) != commited_hash_1 {
-       panic!("hes cheating");
+    for missed in &verified.missed {
+        println!("DEADLINE MISSED: {}", missed);
     }
+    println!("FINAL POINTS: {:?}", verified.points);
+    print_standings(&transcript_standings(&transcript));
+    match verified.evidence.is_empty() {
+        true => Ok(EXIT_OK),
+        false => Ok(EXIT_CHEATING),
+    }
+}
 
-    // we will generate a comon ramdon suming the two revealed
-    let value_0 = u32::from_le_bytes(revealed_0);
-    let value_1 = u32::from_le_bytes(revealed_1);
-    let sum = value_0 as u64 + value_1 as u64;
-    let common_random = sum.to_le_bytes();
-
-    // now we will use the VRF from each player to evaluate in the common random
-    let proof_0 = player_0.sign(&common_random);
-    let proof_1 = player_1.sign(&common_random);
-
-   
-    // From their putpuit they compute and calculate the 
-    let vrf_output_0 = (hash_with_blake(&proof_0.0)[0] % 52 ) % 13;
-    let vrf_output_1 = (hash_with_blake(&proof_1.0)[0] % 52 ) % 13;
-
-    println!("CARDS Player 0: {} Player 1: {}", vrf_output_0, vrf_output_1);
-
-    // THEY CALCULATE THEIR BET,
-    // bet is linear function of their card (from 2, 3, to A), 
-    // bet is from 1 to their total points
-    let bet_0 = ((vrf_output_0 as f32) / 12.0 * points_0 as f32 - 1.0) as u32 +1;
-    let bet_1 = ((vrf_output_1 as f32) / 12.0 * points_1 as f32 -1.0 ) as u32 +1 ;
-
-    println!("BETS Player 0: {} Player 1: {}", bet_0, bet_1);
-
-    let common_bet = min(bet_0, bet_1);
-    println!("Common Bet: {}", common_bet);
+/// Generate a new identity from a fresh mnemonic, and save it to a keystore file if asked to
+fn keygen(out: Option<&Path>) -> Result<i32, String> {
+    let (_, phrase) = p2_addresses::generate_sr25519_phrase();
+    let identity = Identity::from_mnemonic(&phrase).map_err(|_| "invalid mnemonic".to_string())?;
+    println!("MNEMONIC: {}", phrase);
+    println!(
+        "PUBLIC KEY: 0x{}",
+        hex::encode(identity.public().to_bytes())
+    );
+    println!("ADDRESS: {}", identity.address());
+    if let Some(path) = out {
+        identity
+            .save(path)
+            .map_err(|e| format!("could not save the keystore: {}", e))?;
+        println!("KEYSTORE SAVED TO {}", path.display());
+    }
+    Ok(EXIT_OK)
+}
 
-    // they now exchange the signatures, exchange them and verify their output and they verify\
-    if !proof_0.verify(&common_random[..], &player_0.public()) {
-        
-        panic!("hes cheating");
+/// Play many tournaments between betting strategies on parallel threads, and report how each of
+/// them did
+fn simulate(options: &SimulateOptions) -> Result<i32, String> {
+    let table = &options.table;
+    let matchups = match options.matchups.is_empty() {
+        true => {
+            let strategies = [StrategyKind::Linear, StrategyKind::Kelly(1.0)];
+            let players = table.players.unwrap_or(2);
+            vec![strategies.iter().copied().cycle().take(players).collect()]
+        }
+        false => options.matchups.clone(),
     };
-    if !proof_1.verify(&common_random[..], &player_1.public()){
-       
-        panic!("hes cheating");
+    if let Some(players) = table.players {
+        if matchups.iter().any(|matchup| matchup.len() != players) {
+            return Err(format!("every matchup needs {} strategies", players));
+        }
     }
-    
-    // now they pay.
-    match vrf_output_0 > vrf_output_1 {
-        true => {
-            // 0 WON
-            if common_bet == points_1 {
-                println!("PLAYER 0 WINS");
-            }
-            points_0 += bet_1;
-            points_1 -= bet_1;
-        },
-        false => {
-            if common_bet == points_0 {
-                println!("PLAYER 1 WINS");
-            }
-            points_0 -= bet_0;
-            points_1 += bet_0;
+    let mut simulation = Simulation::new(matchups, options.games)
+        .map_err(|_| "every matchup needs 2 to 10 strategies".to_string())?
+        .with_variant(table.variant)
+        .with_dealing(table.dealing)
+        .with_stack(table.stack)
+        .with_seed(table.rng_seed);
+    if let Some(threads) = options.threads {
+        simulation = simulation.with_threads(threads);
+    }
+    if let Some(max_hands) = options.max_hands {
+        simulation = simulation.with_max_hands(max_hands);
+    }
+    for (strategy, cheat) in &table.cheats {
+        if simulation
+            .matchups
+            .iter()
+            .any(|matchup| *strategy >= matchup.len())
+        {
+            return Err(format!(
+                "there is no strategy {} in every matchup",
+                strategy
+            ));
         }
+        simulation = simulation.with_cheat(*strategy, *cheat);
     }
 
-    println!("/////////");
-    println!("          ");
-    
-}
+    let report = simulation.run();
+    match options.csv {
+        true => print!("{}", report.to_csv()),
+        false => print!("{}", report),
+    }
+    match report.matchups.iter().all(|matchup| matchup.cheated == 0) {
+        true => Ok(EXIT_OK),
+        false => Ok(EXIT_CHEATING),
+    }
 }
-/*
-Players start with 100 points each
-VRF scheme
-commit reveal
-from rand number get number from 1 to 52 (card)
-from 1 to 52 find number from 1 to 13 (mod) (car carcd)
-from this fid bet (linear function)
-
-
-strcut
-Player
-- points
-- current card
-- random number
 
+/// Show the public key of an address, and the key its owner plays a game with
+fn inspect(address: &str, game_id: u64) -> Result<i32, String> {
+    let public = p2_addresses::sr25519_public_from_address(address)
+        .map_err(|_| format!("invalid address {}", address))?;
+    let master = PublicKey::from_bytes(public.as_ref())
+        .map_err(|_| format!("invalid public key in {}", address))?;
+    let game = derive_game_public(&master, game_id);
+    println!("ADDRESS: {}", ss58_address(&master));
+    println!("PUBLIC KEY: 0x{}", hex::encode(master.to_bytes()));
+    println!(
+        "GAME {} PUBLIC KEY: 0x{}",
+        game_id,
+        hex::encode(game.to_bytes())
+    );
+    println!("GAME {} ADDRESS: {}", game_id, ss58_address(&game));
+    Ok(EXIT_OK)
+}
 
-*/
+/// Run a command, returning the code to exit with
+fn run(command: Command) -> i32 {
+    let result = match command {
+        Command::Play(options) => play(&options),
+        Command::Host(options) => play_over_network(&options, true),
+        Command::Join(options) => play_over_network(&options, false),
+        Command::Verify { transcript } => verify(&transcript),
+        Command::Keygen { out } => keygen(out.as_deref()),
+        Command::Simulate(options) => simulate(&options),
+        Command::Inspect { address, game_id } => inspect(&address, game_id),
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            Ok(EXIT_OK)
+        }
+    };
+    result.unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        EXIT_ERROR
+    })
+}
 
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let code = match cli::parse(&args) {
+        Ok(command) => run(command),
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            EXIT_USAGE
+        }
+    };
+    std::process::exit(code);
+}
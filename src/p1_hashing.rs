@@ -89,7 +89,7 @@ pub fn verify_data_integrity(data: Vec<u8>) -> Result<Vec<u8>, ()> {
 use rand::{rngs::SmallRng, seq::IteratorRandom, Rng, SeedableRng};
 use std::{cell::RefCell, collections::HashMap};
 use strum::{EnumIter, IntoEnumIterator};
-type HashValue = [u8; HASH_SIZE];
+pub type HashValue = [u8; HASH_SIZE];
 
 /// Now that we are comfortable using hashes, let's implement a classic commit-reveal scheme using a
 /// public message board. This message board implements some functionality to allow people to communicate.
@@ -90,6 +90,17 @@ pub fn generate_with_password() -> Sr25519Pair {
         .0
 }
 
+/// Generate a fresh sr25519 keypair, with the bip39 phrase it comes from
+pub fn generate_sr25519_phrase() -> (Sr25519Pair, String) {
+    let (pair, phrase, _) = Sr25519Pair::generate_with_phrase(None);
+    (pair, phrase)
+}
+
+/// The sr25519 public key of an address in SS58 format. Errors if the address is invalid.
+pub fn sr25519_public_from_address(address: &str) -> Result<Sr25519Public, ()> {
+    Sr25519Public::from_ss58check(address).map_err(|_| ())
+}
+
 // Now that we have some familiarity with seeds, phrases, and password derivation, let's look a
 // little into how seed phrases actually work! BIP39 uses a fixed english dictionary, and maps
 // those words into specific bit sequences. BIP39 is designed to represent entropy in a
@@ -292,6 +303,20 @@ mod tests {
         )
     }
 
+    #[test]
+    fn generated_phrases_give_their_address_test() {
+        let (pair, phrase) = generate_sr25519_phrase();
+        assert_eq!(
+            Sr25519Pair::from_phrase(&phrase, None).unwrap().0.public(),
+            pair.public()
+        );
+        let address = pair.public().to_ss58check();
+        assert_eq!(sr25519_public_from_address(&address), Ok(pair.public()));
+        assert!(
+            sr25519_public_from_address("5GEkFD1WxzmfasT7yMUERDprkEueFEDrSojE3ajwxXvfYYa").is_err()
+        );
+    }
+
     #[test]
     fn generate_ed25519_pair_test() {
         // You might expect that an ed25519 key from the same seed as an sr25519 key would be the
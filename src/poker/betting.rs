@@ -0,0 +1,433 @@
+//! Betting rounds, with the usual poker actions: check, bet, call, raise and fold.
+//!
+//! Players act in turn, and the betting goes on until every player still in has either put in as
+//! much as the highest bet or folded. A bet must be at least the minimum bet, and a raise must
+//! raise by at least as much as the previous bet or raise.
+//!
+//! Nobody can put more than their own stack in the pot. A player who can't match the highest bet
+//! calls with their whole stack, and a bet or raise of the whole stack is allowed even if it is
+//! smaller than the minimum. A player who put in their whole stack is all-in: they don't act
+//! anymore, but they stay in the round, and the pot is split into side pots (see `ledger`).
+//!
+//! A round can also start with forced bets: an ante from every player, which is dead money, and
+//! the small and the big blind from the first two players after the button, which count towards
+//! their bets. The big blind is then the bet to call, and the player who posted it acts last.
+
+use std::fmt;
+
+/// An action of a player in a betting round
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Pass without putting anything in, only possible when nobody has bet yet
+    Check,
+    /// Put in the first bet of the round
+    Bet(u32),
+    /// Put in as much as the highest bet
+    Call,
+    /// Raise the highest bet up to this amount
+    Raise(u32),
+    /// Give up, losing whatever was already put in
+    Fold,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Check => write!(f, "checks"),
+            Action::Bet(amount) => write!(f, "bets {}", amount),
+            Action::Call => write!(f, "calls"),
+            Action::Raise(amount) => write!(f, "raises to {}", amount),
+            Action::Fold => write!(f, "folds"),
+        }
+    }
+}
+
+/// The forced bets of a round, none by default
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Blinds {
+    /// Posted by the first player after the button
+    pub small: u32,
+    /// Posted by the second player after the button
+    pub big: u32,
+    /// Posted by every player before the cards are dealt
+    pub ante: u32,
+}
+
+impl fmt::Display for Blinds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.small, self.big, self.ante)
+    }
+}
+
+impl Blinds {
+    /// Parse blinds written as small/big/ante
+    pub fn from_text(s: &str) -> Result<Self, ()> {
+        let amounts: Vec<u32> = s
+            .split('/')
+            .map(|amount| amount.parse().map_err(|_| ()))
+            .collect::<Result<_, _>>()?;
+        match amounts[..] {
+            [small, big, ante] if small <= big => Ok(Blinds { small, big, ante }),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What a player sees of the betting round when it is their turn to act
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BettingView {
+    /// How much the player has put in the pot so far in this round
+    pub contributed: u32,
+    /// How much more the player has to put in to call
+    pub to_call: u32,
+    /// The highest bet of the round so far
+    pub current_bet: u32,
+    /// The smallest amount the highest bet can be raised to
+    pub min_raise_to: u32,
+    /// The smallest bet allowed when nobody bet yet, unless the player puts in their whole stack
+    pub min_bet: u32,
+    /// How much the player has put in once they are all-in, which is the most they can put in
+    pub all_in: u32,
+    /// The total put in the pot so far by every player
+    pub pot: u32,
+}
+
+/// The state of a betting round between some players
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BettingRound {
+    /// The seats of the players, in turn order
+    seats: Vec<usize>,
+    /// How much each player has put in so far, in turn order
+    contributions: Vec<u32>,
+    /// Whether each player has folded, in turn order
+    folded: Vec<bool>,
+    /// Whether each player still has to act, in turn order
+    pending: Vec<bool>,
+    /// The position in turn order of the next player to act
+    turn: usize,
+    /// The highest bet so far
+    current_bet: u32,
+    /// The size of the last bet or raise, which the next raise must at least match
+    last_raise: u32,
+    /// The smallest bet allowed
+    min_bet: u32,
+    /// The most each player can put in, in turn order: what is left of their stack
+    stacks: Vec<u32>,
+    /// Every action taken so far, with the seat of the player who took it
+    pub actions: Vec<(usize, Action)>,
+}
+
+impl BettingRound {
+    /// Start a betting round between the players in the given seats, in turn order, with what is
+    /// left of their stacks. Nobody can put in less than the minimum bet unless they go all-in, or
+    /// more than their stack. Players with nothing left are already all-in, and never act.
+    pub fn new(seats: Vec<usize>, min_bet: u32, stacks: Vec<u32>) -> Self {
+        let players = seats.len();
+        let mut betting = BettingRound {
+            seats,
+            contributions: vec![0; players],
+            folded: vec![false; players],
+            pending: stacks.iter().map(|stack| *stack > 0).collect(),
+            turn: 0,
+            current_bet: 0,
+            last_raise: min_bet,
+            min_bet,
+            stacks,
+            actions: Vec::new(),
+        };
+        if !betting.pending[0] {
+            betting.advance();
+        }
+        betting
+    }
+
+    /// Post the small and the big blind for the first two players in turn order, before anybody
+    /// acts, with what they have if it is less. The big blind is the bet to call and the smallest
+    /// raise, and the first player to act is the one after the big blind.
+    pub fn post_blinds(&mut self, blinds: &Blinds) {
+        let players = self.seats.len();
+        for (position, blind) in [(0, blinds.small), (1 % players, blinds.big)] {
+            self.contributions[position] = blind.min(self.stacks[position]);
+        }
+        self.current_bet = blinds.big;
+        self.last_raise = self.last_raise.max(blinds.big);
+        for position in 0..players {
+            self.pending[position] = self.can_act(position);
+        }
+        self.turn = 2 % players;
+        if !self.pending[self.turn] {
+            self.advance();
+        }
+    }
+
+    /// The seat of the next player to act, or None if the round is over
+    pub fn next_to_act(&self) -> Option<usize> {
+        if self.is_over() {
+            return None;
+        }
+        Some(self.seats[self.turn])
+    }
+
+    /// The round is over once everybody but one player folded, or nobody has to act anymore. A
+    /// player who is the only one not all-in has nobody to bet against, so they only act to call.
+    pub fn is_over(&self) -> bool {
+        if self.remaining().len() < 2 {
+            return true;
+        }
+        let active: Vec<usize> = (0..self.seats.len())
+            .filter(|position| self.can_act(*position))
+            .collect();
+        match active[..] {
+            [] => true,
+            [only] => !self.pending[only] || self.contributions[only] >= self.current_bet,
+            _ => !active.iter().any(|position| self.pending[*position]),
+        }
+    }
+
+    /// What the next player to act sees of the round
+    pub fn view(&self) -> BettingView {
+        let contributed = self.contributions[self.turn];
+        let all_in = self.stacks[self.turn];
+        BettingView {
+            contributed,
+            to_call: self.current_bet.min(all_in) - contributed,
+            current_bet: self.current_bet,
+            min_raise_to: (self.current_bet + self.last_raise).min(all_in),
+            min_bet: self.min_bet,
+            all_in,
+            pot: self.pot(),
+        }
+    }
+
+    /// Take an action for the next player to act. Errors if the action is not allowed, in which
+    /// case nothing changes.
+    pub fn act(&mut self, action: Action) -> Result<(), ()> {
+        let seat = self.next_to_act().ok_or(())?;
+        let position = self.turn;
+        let stack = self.stacks[position];
+        match action {
+            Action::Check if self.contributions[position] == self.current_bet => {}
+            // a bet of the whole stack is allowed even if it is smaller than the minimum bet
+            Action::Bet(amount)
+                if self.current_bet == 0
+                    && amount <= stack
+                    && (amount >= self.min_bet || amount == stack) =>
+            {
+                self.raise_to(position, amount);
+            }
+            // a player who can't match the highest bet calls with their whole stack
+            Action::Call if self.contributions[position] < self.current_bet => {
+                self.contributions[position] = self.current_bet.min(stack);
+            }
+            // a raise of the whole stack is allowed even if it is smaller than the last raise
+            Action::Raise(amount)
+                if self.current_bet > 0
+                    && amount > self.current_bet
+                    && amount <= stack
+                    && (amount >= self.current_bet + self.last_raise || amount == stack) =>
+            {
+                self.raise_to(position, amount);
+            }
+            Action::Fold => self.folded[position] = true,
+            _ => return Err(()),
+        }
+        self.pending[position] = false;
+        self.actions.push((seat, action));
+        self.advance();
+        Ok(())
+    }
+
+    /// Whether the next player to act is allowed to take an action
+    pub fn allows(&self, action: Action) -> bool {
+        self.clone().act(action).is_ok()
+    }
+
+    /// Put in a new highest bet, so every other player still in and not all-in has to act again
+    fn raise_to(&mut self, position: usize, amount: u32) {
+        self.last_raise = self.last_raise.max(amount - self.current_bet);
+        self.current_bet = amount;
+        self.contributions[position] = amount;
+        for other in 0..self.seats.len() {
+            self.pending[other] = self.can_act(other);
+        }
+    }
+
+    /// Whether the player in a position is still in and has something left to put in
+    fn can_act(&self, position: usize) -> bool {
+        !self.folded[position] && self.contributions[position] < self.stacks[position]
+    }
+
+    /// Move the turn to the next player who still has to act
+    fn advance(&mut self) {
+        for _ in 0..self.seats.len() {
+            self.turn = (self.turn + 1) % self.seats.len();
+            if self.pending[self.turn] && !self.folded[self.turn] {
+                return;
+            }
+        }
+    }
+
+    /// The seats of the players who have not folded, in turn order
+    pub fn remaining(&self) -> Vec<usize> {
+        self.seats
+            .iter()
+            .zip(&self.folded)
+            .filter(|(_, folded)| !**folded)
+            .map(|(seat, _)| *seat)
+            .collect()
+    }
+
+    /// How much the player in a seat has put in so far
+    pub fn contribution(&self, seat: usize) -> u32 {
+        self.seats
+            .iter()
+            .position(|s| *s == seat)
+            .map(|position| self.contributions[position])
+            .unwrap_or(0)
+    }
+
+    /// The total put in the pot so far
+    pub fn pot(&self) -> u32 {
+        self.contributions.iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn everybody_checks() {
+        let mut betting = BettingRound::new(vec![2, 0, 1], 1, vec![100; 3]);
+        assert_eq!(betting.next_to_act(), Some(2));
+        betting.act(Action::Check).unwrap();
+        assert_eq!(betting.next_to_act(), Some(0));
+        betting.act(Action::Check).unwrap();
+        betting.act(Action::Check).unwrap();
+        assert!(betting.is_over());
+        assert_eq!(betting.pot(), 0);
+        assert_eq!(betting.remaining(), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn bet_reopens_the_action() {
+        let mut betting = BettingRound::new(vec![0, 1, 2], 1, vec![100; 3]);
+        betting.act(Action::Check).unwrap();
+        betting.act(Action::Bet(10)).unwrap();
+        betting.act(Action::Call).unwrap();
+        // the first player checked, so they still have to act
+        assert_eq!(betting.next_to_act(), Some(0));
+        assert_eq!(betting.view().to_call, 10);
+        betting.act(Action::Call).unwrap();
+        assert!(betting.is_over());
+        assert_eq!(betting.pot(), 30);
+        assert_eq!(betting.contribution(0), 10);
+    }
+
+    #[test]
+    fn raises_must_be_at_least_the_last_raise() {
+        let mut betting = BettingRound::new(vec![0, 1], 1, vec![100; 2]);
+        betting.act(Action::Bet(10)).unwrap();
+        assert_eq!(betting.view().min_raise_to, 20);
+        assert!(betting.act(Action::Raise(15)).is_err());
+        betting.act(Action::Raise(25)).unwrap();
+        assert_eq!(betting.view().min_raise_to, 40);
+        assert!(betting.act(Action::Raise(39)).is_err());
+        betting.act(Action::Raise(40)).unwrap();
+        betting.act(Action::Call).unwrap();
+        assert!(betting.is_over());
+        assert_eq!(betting.pot(), 80);
+    }
+
+    #[test]
+    fn raise_of_the_whole_stack_can_be_short() {
+        let mut betting = BettingRound::new(vec![0, 1], 1, vec![30; 2]);
+        betting.act(Action::Bet(20)).unwrap();
+        assert_eq!(betting.view().min_raise_to, 30);
+        assert!(betting.act(Action::Raise(31)).is_err());
+        betting.act(Action::Raise(30)).unwrap();
+        // nobody can raise above their stack
+        assert!(betting.act(Action::Raise(30)).is_err());
+        betting.act(Action::Call).unwrap();
+        assert_eq!(betting.pot(), 60);
+    }
+
+    #[test]
+    fn short_stack_calls_all_in() {
+        let mut betting = BettingRound::new(vec![0, 1, 2], 1, vec![100, 30, 100]);
+        betting.act(Action::Bet(50)).unwrap();
+        assert_eq!(betting.view().to_call, 30);
+        assert_eq!(betting.view().all_in, 30);
+        assert!(betting.act(Action::Raise(60)).is_err());
+        betting.act(Action::Call).unwrap();
+        assert_eq!(betting.contribution(1), 30);
+        // the all-in player doesn't act anymore, even when the bet is raised
+        betting.act(Action::Raise(100)).unwrap();
+        assert_eq!(betting.next_to_act(), Some(0));
+        betting.act(Action::Call).unwrap();
+        assert!(betting.is_over());
+        assert_eq!(betting.remaining(), vec![0, 1, 2]);
+        assert_eq!(betting.pot(), 230);
+    }
+
+    #[test]
+    fn short_all_in_bets_and_raises_are_allowed() {
+        let mut betting = BettingRound::new(vec![0, 1, 2], 10, vec![5, 25, 100]);
+        assert!(betting.act(Action::Bet(4)).is_err());
+        betting.act(Action::Bet(5)).unwrap();
+        // the minimum raise is to 15, but a raise of the whole stack can be short
+        assert!(betting.act(Action::Raise(12)).is_err());
+        betting.act(Action::Raise(25)).unwrap();
+        betting.act(Action::Call).unwrap();
+        // nobody is left to bet against the last player who has chips
+        assert!(betting.is_over());
+        assert_eq!(betting.pot(), 55);
+    }
+
+    #[test]
+    fn players_all_in_from_the_start_never_act() {
+        let betting = BettingRound::new(vec![0, 1, 2], 1, vec![0, 50, 50]);
+        assert_eq!(betting.next_to_act(), Some(1));
+        assert!(BettingRound::new(vec![0, 1], 1, vec![0, 50]).is_over());
+        assert!(BettingRound::new(vec![0, 1], 1, vec![0, 0]).is_over());
+    }
+
+    #[test]
+    fn illegal_actions_are_rejected() {
+        let mut betting = BettingRound::new(vec![0, 1], 5, vec![100; 2]);
+        assert!(!betting.allows(Action::Call));
+        assert!(betting.allows(Action::Bet(5)));
+        assert!(betting.act(Action::Call).is_err());
+        assert!(betting.act(Action::Raise(10)).is_err());
+        assert!(betting.act(Action::Bet(4)).is_err());
+        assert!(betting.act(Action::Bet(101)).is_err());
+        betting.act(Action::Bet(5)).unwrap();
+        assert!(betting.act(Action::Check).is_err());
+        assert!(betting.act(Action::Bet(10)).is_err());
+        assert!(betting.actions.len() == 1);
+    }
+
+    #[test]
+    fn folding_leaves_one_player() {
+        let mut betting = BettingRound::new(vec![0, 1, 2], 1, vec![100; 3]);
+        betting.act(Action::Bet(10)).unwrap();
+        betting.act(Action::Fold).unwrap();
+        betting.act(Action::Fold).unwrap();
+        assert!(betting.is_over());
+        assert_eq!(betting.next_to_act(), None);
+        assert_eq!(betting.remaining(), vec![0]);
+        assert!(betting.act(Action::Check).is_err());
+    }
+
+    #[test]
+    fn folded_players_are_skipped() {
+        let mut betting = BettingRound::new(vec![0, 1, 2], 1, vec![100; 3]);
+        betting.act(Action::Check).unwrap();
+        betting.act(Action::Fold).unwrap();
+        betting.act(Action::Bet(5)).unwrap();
+        assert_eq!(betting.next_to_act(), Some(0));
+        betting.act(Action::Call).unwrap();
+        assert!(betting.is_over());
+        assert_eq!(betting.remaining(), vec![0, 2]);
+    }
+}
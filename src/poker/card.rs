@@ -0,0 +1,191 @@
+//! Cards of the infinite deck.
+//!
+//! Every draw is independent and each of the 52 cards has exactly 1/52 probability, no matter who
+//! has already drawn what.
+
+use std::fmt;
+
+use sp_core::blake2_256;
+use strum::{EnumIter, IntoEnumIterator};
+
+/// The number of cards in a deck
+pub const DECK_SIZE: u8 = 52;
+
+/// Random bytes below this value are used to pick a card, the others are rejected. It is the
+/// largest multiple of 52 that fits in a byte, so every card is picked by exactly 4 byte values.
+const ACCEPT_BELOW: u8 = 208;
+
+/// The rank of a card, from 2 up to the ace
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, EnumIter)]
+pub enum Rank {
+    Two = 2,
+    Three,
+    Four,
+    Five,
+    Six,
+    Seven,
+    Eight,
+    Nine,
+    Ten,
+    Jack,
+    Queen,
+    King,
+    Ace,
+}
+
+impl Rank {
+    /// The index of the rank, from 0 for a 2 up to 12 for an ace
+    pub fn index(self) -> u8 {
+        self as u8 - Rank::Two as u8
+    }
+
+    /// The rank with the given index, from 0 for a 2 up to 12 for an ace
+    pub fn from_index(index: u8) -> Option<Rank> {
+        Rank::iter().nth(index as usize)
+    }
+}
+
+impl fmt::Display for Rank {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            Rank::Ten => "10",
+            Rank::Jack => "J",
+            Rank::Queen => "Q",
+            Rank::King => "K",
+            Rank::Ace => "A",
+            number => return write!(f, "{}", *number as u8),
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// The suit of a card. Suits do not rank, so they are only compared for equality.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, EnumIter)]
+pub enum Suit {
+    Clubs,
+    Diamonds,
+    Hearts,
+    Spades,
+}
+
+impl fmt::Display for Suit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            Suit::Clubs => "♣",
+            Suit::Diamonds => "♦",
+            Suit::Hearts => "♥",
+            Suit::Spades => "♠",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// A card, with its rank and suit
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Card {
+    pub rank: Rank,
+    pub suit: Suit,
+}
+
+impl Card {
+    /// The card at some index of an ordered deck, going through every rank of a suit before the
+    /// next suit. Errors if the index is not below 52.
+    pub fn from_index(index: u8) -> Result<Card, ()> {
+        if index >= DECK_SIZE {
+            return Err(());
+        }
+        Ok(Card {
+            rank: Rank::from_index(index % 13).ok_or(())?,
+            suit: Suit::iter().nth((index / 13) as usize).ok_or(())?,
+        })
+    }
+
+    /// The index of the card in an ordered deck, the inverse of `from_index`
+    pub fn index(&self) -> u8 {
+        self.suit as u8 * 13 + self.rank.index()
+    }
+
+    /// Derive a card from some random bytes, such as a VRF output.
+    ///
+    /// A single byte mod 52 would be biased, because 256 is not a multiple of 52. Instead we use
+    /// rejection sampling: we hash the bytes with a counter and take the first byte of the hash
+    /// below 208, so every card is equally likely. Should every byte be rejected, we hash again
+    /// with the next counter.
+    pub fn from_random_bytes(bytes: &[u8]) -> Card {
+        let mut counter: u32 = 0;
+        loop {
+            let mut data = bytes.to_vec();
+            data.extend_from_slice(&counter.to_le_bytes());
+            if let Some(byte) = blake2_256(&data).into_iter().find(|b| *b < ACCEPT_BELOW) {
+                return Card::from_index(byte % DECK_SIZE).unwrap();
+            }
+            counter += 1;
+        }
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.rank, self.suit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn card_display() {
+        let queen_of_hearts = Card {
+            rank: Rank::Queen,
+            suit: Suit::Hearts,
+        };
+        assert_eq!(queen_of_hearts.to_string(), "Q♥");
+        let ten_of_clubs = Card {
+            rank: Rank::Ten,
+            suit: Suit::Clubs,
+        };
+        assert_eq!(ten_of_clubs.to_string(), "10♣");
+        let two_of_spades = Card {
+            rank: Rank::Two,
+            suit: Suit::Spades,
+        };
+        assert_eq!(two_of_spades.to_string(), "2♠");
+    }
+
+    #[test]
+    fn index_round_trips() {
+        for index in 0..DECK_SIZE {
+            assert_eq!(Card::from_index(index).unwrap().index(), index);
+        }
+        assert!(Card::from_index(DECK_SIZE).is_err());
+        assert_eq!(Rank::from_index(12), Some(Rank::Ace));
+        assert_eq!(Rank::from_index(13), None);
+    }
+
+    #[test]
+    fn ranks_are_ordered() {
+        assert!(Rank::Ace > Rank::King);
+        assert!(Rank::Three > Rank::Two);
+        assert_eq!(Rank::Ace.index(), 12);
+    }
+
+    #[test]
+    fn cards_from_random_bytes_are_uniform() {
+        // Pearson's chi-squared test with 51 degrees of freedom. The critical value for a
+        // significance level of 0.001 is about 87.97.
+        const DRAWS_PER_CARD: u32 = 1000;
+        let draws = DRAWS_PER_CARD * DECK_SIZE as u32;
+        let mut counts = [0u32; DECK_SIZE as usize];
+        for i in 0..draws {
+            let card = Card::from_random_bytes(&blake2_256(&i.to_le_bytes()));
+            counts[card.index() as usize] += 1;
+        }
+        let expected = DRAWS_PER_CARD as f64;
+        let chi_squared: f64 = counts
+            .iter()
+            .map(|count| (*count as f64 - expected).powi(2) / expected)
+            .sum();
+        assert!(chi_squared < 87.97, "chi squared was {}", chi_squared);
+    }
+}
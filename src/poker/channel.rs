@@ -0,0 +1,256 @@
+//! Encrypted channels between two players.
+//!
+//! Most messages of the game are public, but some are meant for a single player, and nobody else
+//! should read them, not even the host who relays them. Two players open a channel with an x25519
+//! handshake:
+//!
+//! 1. Each player generates a fresh x25519 key for the channel, and signs its public key with the
+//!    key they sign the game with, bound to the game and to both players, so nobody can take their
+//!    place in the handshake or replay it to another player.
+//! 2. Each player checks the signature of the other, and they both compute the shared secret of
+//!    their x25519 keys. Hashing it with both handshakes gives one AES-256-GCM-SIV key for each
+//!    direction of the channel.
+//! 3. Every message is encrypted with the key of its direction and a nonce made of the number of
+//!    messages sent before it in that direction, so no nonce is ever used twice with a key. The
+//!    receiver counts the messages too, and only opens the next one: a message that was tampered
+//!    with, replayed, reordered or dropped does not open.
+//!
+//! The x25519 keys are forgotten with the channel, so the messages of a channel can't be read
+//! later even if the game keys leak.
+
+use aes_gcm_siv::aead::{Aead, KeyInit};
+use aes_gcm_siv::{Aes256GcmSiv, Nonce};
+use rand::Rng;
+use schnorrkel::{signing_context, Keypair, PublicKey, Signature};
+use sha2::{Digest, Sha512};
+use x25519_dalek_ng::{PublicKey as ChannelPublic, StaticSecret};
+
+/// The signing context of the handshakes
+const HANDSHAKE_CONTEXT: &[u8] = b"infinite deck poker channel handshake";
+
+/// The domain of the hash from the shared secret of a channel to its keys
+const KEY_CONTEXT: &[u8] = b"infinite deck poker channel keys";
+
+/// What a player sends to open a channel: their x25519 public key for the channel, signed with
+/// their game key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Handshake {
+    pub ephemeral: [u8; 32],
+    pub signature: Signature,
+}
+
+/// Our side of a channel, once we sent our handshake and until we get the other player's
+#[derive(Clone)]
+pub struct PendingChannel {
+    secret: StaticSecret,
+    ours: Handshake,
+    /// Our game key, and the one of the other player
+    public: PublicKey,
+    their_public: PublicKey,
+    game_id: u64,
+}
+
+/// Start opening a channel in a game with the player of a public key, with the keypair we sign
+/// the game with. Returns the handshake to send them, and what we keep to finish the channel.
+pub fn handshake(
+    keypair: &Keypair,
+    their_public: &PublicKey,
+    game_id: u64,
+    rng: &mut impl Rng,
+) -> (Handshake, PendingChannel) {
+    let secret = StaticSecret::from(rng.gen::<[u8; 32]>());
+    let ephemeral = ChannelPublic::from(&secret).to_bytes();
+    let message = signing_message(game_id, &keypair.public, their_public, &ephemeral);
+    let ours = Handshake {
+        ephemeral,
+        signature: keypair.sign(signing_context(HANDSHAKE_CONTEXT).bytes(&message)),
+    };
+    let pending = PendingChannel {
+        secret,
+        ours: ours.clone(),
+        public: keypair.public,
+        their_public: *their_public,
+        game_id,
+    };
+    (ours, pending)
+}
+
+/// The message a player signs in their handshake, bound to the game and to both players
+fn signing_message(
+    game_id: u64,
+    public: &PublicKey,
+    their_public: &PublicKey,
+    ephemeral: &[u8; 32],
+) -> Vec<u8> {
+    let mut message = game_id.to_be_bytes().to_vec();
+    message.extend_from_slice(&public.to_bytes());
+    message.extend_from_slice(&their_public.to_bytes());
+    message.extend_from_slice(ephemeral);
+    message
+}
+
+impl PendingChannel {
+    /// Finish the channel with the handshake of the other player. Errors if it is not signed by
+    /// them for this game and for us.
+    pub fn finish(self, theirs: &Handshake) -> Result<Channel, ()> {
+        let message = signing_message(
+            self.game_id,
+            &self.their_public,
+            &self.public,
+            &theirs.ephemeral,
+        );
+        self.their_public
+            .verify(
+                signing_context(HANDSHAKE_CONTEXT).bytes(&message),
+                &theirs.signature,
+            )
+            .map_err(|_| ())?;
+        let shared = self
+            .secret
+            .diffie_hellman(&ChannelPublic::from(theirs.ephemeral));
+
+        // both players hash the handshakes in the same order, the one of the smaller game key first
+        let ours_first = self.public.to_bytes() < self.their_public.to_bytes();
+        let (first, second) = match ours_first {
+            true => (&self.ours, theirs),
+            false => (theirs, &self.ours),
+        };
+        let mut hash = Sha512::new();
+        hash.update(KEY_CONTEXT);
+        hash.update(shared.as_bytes());
+        hash.update(first.ephemeral);
+        hash.update(second.ephemeral);
+        let keys = hash.finalize();
+        let (first_key, second_key) = keys.split_at(32);
+        let (send_key, receive_key) = match ours_first {
+            true => (first_key, second_key),
+            false => (second_key, first_key),
+        };
+        Ok(Channel {
+            send: Aes256GcmSiv::new_from_slice(send_key).map_err(|_| ())?,
+            receive: Aes256GcmSiv::new_from_slice(receive_key).map_err(|_| ())?,
+            sent: 0,
+            received: 0,
+        })
+    }
+}
+
+/// An open channel with another player
+#[derive(Clone)]
+pub struct Channel {
+    /// The cipher of the messages we send, and of the ones we receive
+    send: Aes256GcmSiv,
+    receive: Aes256GcmSiv,
+    /// The number of messages we sent, and opened
+    sent: u64,
+    received: u64,
+}
+
+/// The nonce of the message sent after some number of others in a direction of a channel
+fn nonce(count: u64) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&count.to_be_bytes());
+    Nonce::from(nonce)
+}
+
+impl Channel {
+    /// Encrypt the next message we send to the other player
+    pub fn seal(&mut self, message: &[u8]) -> Vec<u8> {
+        let sealed = self
+            .send
+            .encrypt(&nonce(self.sent), message)
+            .expect("messages of the game fit in a single ciphertext");
+        self.sent += 1;
+        sealed
+    }
+
+    /// Decrypt the next message the other player sent us. Errors if it is not the next message
+    /// they sealed, or if it was tampered with.
+    pub fn open(&mut self, sealed: &[u8]) -> Result<Vec<u8>, ()> {
+        let message = self
+            .receive
+            .decrypt(&nonce(self.received), sealed)
+            .map_err(|_| ())?;
+        self.received += 1;
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn open_channels(rng: &mut StdRng) -> (Channel, Channel) {
+        let alice = Keypair::generate_with(rand_core::OsRng);
+        let bob = Keypair::generate_with(rand_core::OsRng);
+        let (to_bob, alice_pending) = handshake(&alice, &bob.public, 3, rng);
+        let (to_alice, bob_pending) = handshake(&bob, &alice.public, 3, rng);
+        (
+            alice_pending.finish(&to_alice).unwrap(),
+            bob_pending.finish(&to_bob).unwrap(),
+        )
+    }
+
+    #[test]
+    fn messages_go_both_ways() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let (mut alice, mut bob) = open_channels(&mut rng);
+        for message in [&b"hint: the flop is wet"[..], b"", b"second"] {
+            let sealed = alice.seal(message);
+            assert_ne!(&sealed[..], message);
+            assert_eq!(bob.open(&sealed), Ok(message.to_vec()));
+        }
+        let sealed = bob.seal(b"thanks");
+        assert_eq!(alice.open(&sealed), Ok(b"thanks".to_vec()));
+    }
+
+    #[test]
+    fn tampered_messages_do_not_open() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let (mut alice, mut bob) = open_channels(&mut rng);
+        let mut sealed = alice.seal(b"decryption share");
+        sealed[0] ^= 1;
+        assert!(bob.open(&sealed).is_err());
+        let last = sealed.len() - 1;
+        sealed[0] ^= 1;
+        sealed[last] ^= 1;
+        assert!(bob.open(&sealed).is_err());
+        // the message opens once it is back to what was sent
+        sealed[last] ^= 1;
+        assert!(bob.open(&sealed).is_ok());
+        // but only once, and not in the other direction
+        assert!(bob.open(&sealed).is_err());
+        assert!(alice.open(&alice.clone().seal(b"echo")).is_err());
+    }
+
+    #[test]
+    fn messages_open_in_order() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let (mut alice, mut bob) = open_channels(&mut rng);
+        let first = alice.seal(b"first");
+        let second = alice.seal(b"second");
+        assert!(bob.open(&second).is_err());
+        assert_eq!(bob.open(&first), Ok(b"first".to_vec()));
+        assert_eq!(bob.open(&second), Ok(b"second".to_vec()));
+    }
+
+    #[test]
+    fn handshakes_are_bound_to_the_players_and_the_game() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let alice = Keypair::generate_with(rand_core::OsRng);
+        let bob = Keypair::generate_with(rand_core::OsRng);
+        let carol = Keypair::generate_with(rand_core::OsRng);
+        let (to_bob, _) = handshake(&alice, &bob.public, 3, &mut rng);
+        // carol can't pass the handshake alice sent to bob as her own, nor reuse it herself
+        let (_, bob_pending) = handshake(&bob, &carol.public, 3, &mut rng);
+        assert!(bob_pending.finish(&to_bob).is_err());
+        let (_, carol_pending) = handshake(&carol, &alice.public, 3, &mut rng);
+        assert!(carol_pending.finish(&to_bob).is_err());
+        // and it is no good in another game
+        let (_, bob_pending) = handshake(&bob, &alice.public, 4, &mut rng);
+        assert!(bob_pending.finish(&to_bob).is_err());
+        let (_, bob_pending) = handshake(&bob, &alice.public, 3, &mut rng);
+        assert!(bob_pending.finish(&to_bob).is_ok());
+    }
+}
@@ -0,0 +1,203 @@
+//! Detecting cheaters.
+//!
+//! Every check the players make on each other's messages either passes or produces evidence that
+//! names the cheater and carries the messages that prove it, so anyone can check the evidence
+//! independently. The game then makes the cheater forfeit instead of aborting.
+
+use std::fmt;
+
+use curve25519_dalek::ristretto::RistrettoPoint;
+
+use super::deadline::Phase;
+use super::vrf::CardDraw;
+use crate::p1_hashing::HashValue;
+
+/// Evidence that a player cheated during a round. The points and draws it carries are boxed, so
+/// the evidence stays small in the results of the rounds that return it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheatingEvidence {
+    /// The player revealed something that does not open their commitment for this game and round
+    BadReveal {
+        seat: usize,
+        commitment: HashValue,
+        reveal: String,
+    },
+    /// The player published a card draw whose VRF proof does not verify on the input of its slot
+    /// and draw round in the round
+    InvalidVrfProof {
+        seat: usize,
+        input: Vec<u8>,
+        draw: Box<CardDraw>,
+    },
+    /// The player posted more than one commitment in the same round
+    DoubleCommitment {
+        seat: usize,
+        round: u32,
+        first: HashValue,
+        second: HashValue,
+    },
+    /// The player showed different card draws to different players in the same round
+    Equivocation {
+        seat: usize,
+        round: u32,
+        first: Box<CardDraw>,
+        second: Box<CardDraw>,
+    },
+    /// The player published a shuffle of the finite deck whose proof does not hold: the opening
+    /// of one of their shadow shuffles does not answer its challenge
+    BadShuffle {
+        seat: usize,
+        round: u32,
+        challenge: usize,
+    },
+    /// The player removed their layer of encryption from a card of the finite deck without a valid
+    /// proof that they removed the layer of the key they shuffled with, or removed the last layer
+    /// and left a point that is not a card or a card already shown in the round
+    BadDecryption {
+        seat: usize,
+        round: u32,
+        position: usize,
+        point: Box<RistrettoPoint>,
+    },
+}
+
+impl CheatingEvidence {
+    /// The seat of the player who cheated
+    pub fn seat(&self) -> usize {
+        match self {
+            Self::BadReveal { seat, .. }
+            | Self::InvalidVrfProof { seat, .. }
+            | Self::DoubleCommitment { seat, .. }
+            | Self::Equivocation { seat, .. }
+            | Self::BadShuffle { seat, .. }
+            | Self::BadDecryption { seat, .. } => *seat,
+        }
+    }
+}
+
+impl fmt::Display for CheatingEvidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BadReveal { seat, .. } => write!(
+                f,
+                "player {} revealed a value that does not open their commitment",
+                seat
+            ),
+            Self::InvalidVrfProof { seat, .. } => {
+                write!(f, "player {} published an invalid VRF proof", seat)
+            }
+            Self::DoubleCommitment { seat, round, .. } => write!(
+                f,
+                "player {} posted two commitments in round {}",
+                seat, round
+            ),
+            Self::Equivocation { seat, round, .. } => write!(
+                f,
+                "player {} showed different cards to different players in round {}",
+                seat, round
+            ),
+            Self::BadShuffle { seat, round, .. } => write!(
+                f,
+                "player {} could not prove their shuffle of the deck in round {}",
+                seat, round
+            ),
+            Self::BadDecryption {
+                seat,
+                round,
+                position,
+                ..
+            } => write!(
+                f,
+                "player {} decrypted card {} of the deck to a bad card in round {}",
+                seat, position, round
+            ),
+        }
+    }
+}
+
+/// The ways a player can be told to cheat, to play games against dishonest players from tests or
+/// bots. Each one is caught by the corresponding `CheatingEvidence`, except stalling, which the
+/// deadlines catch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cheat {
+    /// Reveal a different value than the one committed to
+    BadReveal,
+    /// Publish a card draw evaluated on another input
+    InvalidVrfProof,
+    /// Post a second commitment to another value
+    DoubleCommitment,
+    /// Show the real card draw to the first player, and a draw on another input to the others
+    Equivocation,
+    /// Shuffle the finite deck with a copy of one card in place of another
+    BadShuffle,
+    /// Remove our layer from the cards of the finite deck to other points than the cards
+    BadDecryption,
+    /// Never answer in a phase, to abort the round when it goes badly for us
+    Stall(Phase),
+}
+
+impl Cheat {
+    /// Every way to cheat
+    pub const ALL: [Cheat; 9] = [
+        Cheat::BadReveal,
+        Cheat::InvalidVrfProof,
+        Cheat::DoubleCommitment,
+        Cheat::Equivocation,
+        Cheat::BadShuffle,
+        Cheat::BadDecryption,
+        Cheat::Stall(Phase::Commit),
+        Cheat::Stall(Phase::Reveal),
+        Cheat::Stall(Phase::Showdown),
+    ];
+
+    /// The name of the cheat, as given on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Cheat::BadReveal => "bad-reveal",
+            Cheat::InvalidVrfProof => "invalid-vrf-proof",
+            Cheat::DoubleCommitment => "double-commitment",
+            Cheat::Equivocation => "equivocation",
+            Cheat::BadShuffle => "bad-shuffle",
+            Cheat::BadDecryption => "bad-decryption",
+            Cheat::Stall(Phase::Commit) => "stall-commit",
+            Cheat::Stall(Phase::Reveal) => "stall-reveal",
+            Cheat::Stall(Phase::Showdown) => "stall-showdown",
+        }
+    }
+
+    /// The cheat with a name. Errors if there is none.
+    pub fn from_name(name: &str) -> Result<Self, ()> {
+        Cheat::ALL
+            .into_iter()
+            .find(|cheat| cheat.name() == name)
+            .ok_or(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evidence_names_the_cheater() {
+        let evidence = CheatingEvidence::DoubleCommitment {
+            seat: 3,
+            round: 5,
+            first: [0u8; 16],
+            second: [1u8; 16],
+        };
+        assert_eq!(evidence.seat(), 3);
+        assert_eq!(
+            evidence.to_string(),
+            "player 3 posted two commitments in round 5"
+        );
+    }
+
+    #[test]
+    fn cheats_have_names() {
+        for cheat in Cheat::ALL {
+            assert_eq!(Cheat::from_name(cheat.name()), Ok(cheat));
+        }
+        assert!(Cheat::from_name("stall").is_err());
+    }
+}
@@ -0,0 +1,258 @@
+//! Commit-reveal of the players' contributions to the common randomness.
+//!
+//! A commitment is posted on a `PublicMessageBoard` and binds the random value to the player's
+//! public key, the game id, the round number and the street, together with a 32-byte random salt.
+//! The salt makes it impossible to brute-force the value from the commitment, and the binding
+//! makes it impossible to replay someone else's commitment, or one from another game, round or
+//! street.
+//!
+//! Once every value is revealed, they are hashed together into the common random value. On the
+//! first street of a round it is the input of the players' VRFs, and on the later streets of
+//! hold'em it deals the community cards.
+
+use schnorrkel::PublicKey;
+use sp_core::blake2_256;
+
+use crate::p1_hashing::{HashValue, PublicMessageBoard};
+
+/// The size of the random salt added to every commitment
+pub const SALT_SIZE: usize = 32;
+
+/// A player's contribution to the common randomness of a round
+pub type RandomValue = [u8; 32];
+
+/// The domain tag hashed with the reveals, so the common random value cannot collide with a hash
+/// computed for anything else
+const COMBINE_DOMAIN: &[u8] = b"infinite deck poker/common randomness";
+
+/// The number of hex characters the message board appends to every committed message
+const BOARD_RANDOMNESS_LEN: usize = 8;
+
+/// The contents of a commitment, as recovered from its reveal
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommittedValue {
+    pub public: [u8; 32],
+    pub game_id: u64,
+    pub round: u32,
+    pub street: u32,
+    pub value: RandomValue,
+    pub salt: [u8; SALT_SIZE],
+}
+
+impl CommittedValue {
+    /// The message committed to on the board, before the board adds its own randomness
+    pub fn to_message(&self) -> String {
+        format!(
+            "{}/{}/{}/{}/{}/{}",
+            self.game_id,
+            self.round,
+            self.street,
+            hex::encode(self.public),
+            hex::encode(self.value),
+            hex::encode(self.salt)
+        )
+    }
+
+    /// Parse a revealed message, with the randomness added by the message board at the end.
+    /// Errors if the message is malformed.
+    pub fn from_reveal(reveal: &str) -> Result<Self, ()> {
+        let message = reveal
+            .len()
+            .checked_sub(BOARD_RANDOMNESS_LEN)
+            .and_then(|len| reveal.get(..len))
+            .ok_or(())?;
+        let parts: Vec<&str> = message.split('/').collect();
+        if parts.len() != 6 {
+            return Err(());
+        }
+        Ok(CommittedValue {
+            game_id: parts[0].parse().map_err(|_| ())?,
+            round: parts[1].parse().map_err(|_| ())?,
+            street: parts[2].parse().map_err(|_| ())?,
+            public: decode_hex(parts[3])?,
+            value: decode_hex(parts[4])?,
+            salt: decode_hex(parts[5])?,
+        })
+    }
+}
+
+fn decode_hex<const N: usize>(s: &str) -> Result<[u8; N], ()> {
+    let mut bytes = [0u8; N];
+    hex::decode_to_slice(s, &mut bytes).map_err(|_| ())?;
+    Ok(bytes)
+}
+
+/// Post a commitment to a value on the board, returning the reveal to keep secret until the reveal
+/// phase and the commitment to share.
+pub fn commit(board: &mut PublicMessageBoard, committed: &CommittedValue) -> (String, HashValue) {
+    board.post_commitment(committed.to_message())
+}
+
+/// Post a reveal on the board and check that it opens the commitment of the owner of a public key
+/// for a given game, round and street, returning the revealed value.
+///
+/// Errors if the reveal does not match the commitment, was already revealed, or was made by
+/// another player or for another game, round or street.
+pub fn open(
+    board: &mut PublicMessageBoard,
+    commitment: HashValue,
+    reveal: &str,
+    public: &PublicKey,
+    game_id: u64,
+    round: u32,
+    street: u32,
+) -> Result<RandomValue, ()> {
+    let value = check_reveal(commitment, reveal, public, game_id, round, street)?;
+    board.post_reveal(reveal.to_string())?;
+    Ok(value)
+}
+
+/// Check that a reveal opens the commitment of the owner of a public key for a given game, round
+/// and street, returning the revealed value. Unlike `open`, this does not need the message board,
+/// so anyone can check a reveal after the fact.
+pub fn check_reveal(
+    commitment: HashValue,
+    reveal: &str,
+    public: &PublicKey,
+    game_id: u64,
+    round: u32,
+    street: u32,
+) -> Result<RandomValue, ()> {
+    if PublicMessageBoard::reveal_to_commit(reveal) != commitment {
+        return Err(());
+    }
+    let committed = CommittedValue::from_reveal(reveal)?;
+    if committed.public != public.to_bytes()
+        || committed.game_id != game_id
+        || committed.round != round
+        || committed.street != street
+    {
+        return Err(());
+    }
+    Ok(committed.value)
+}
+
+/// Combine the revealed values of every player into the common random value of a street of a
+/// round.
+///
+/// The values are sorted, so the result does not depend on the order they were revealed in, and
+/// hashed together with a domain tag, the game id, the round number and the street, so the result
+/// is unique per game, round and street even if the same values are revealed again. Works for any
+/// number of players.
+pub fn combine_randomness(
+    reveals: &[RandomValue],
+    game_id: u64,
+    round: u32,
+    street: u32,
+) -> [u8; 32] {
+    let mut sorted = reveals.to_vec();
+    sorted.sort();
+
+    let mut data = COMBINE_DOMAIN.to_vec();
+    data.extend_from_slice(&game_id.to_le_bytes());
+    data.extend_from_slice(&round.to_le_bytes());
+    data.extend_from_slice(&street.to_le_bytes());
+    data.extend_from_slice(&(sorted.len() as u32).to_le_bytes());
+    for value in sorted {
+        data.extend_from_slice(&value);
+    }
+    blake2_256(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::OsRng;
+    use schnorrkel::Keypair;
+
+    fn committed_value(public: &PublicKey) -> CommittedValue {
+        CommittedValue {
+            public: public.to_bytes(),
+            game_id: 7,
+            round: 3,
+            street: 1,
+            value: [1u8; 32],
+            salt: [9u8; SALT_SIZE],
+        }
+    }
+
+    #[test]
+    fn reveal_opens_commitment() {
+        let keypair = Keypair::generate_with(OsRng);
+        let mut board = PublicMessageBoard::new(2023);
+        let committed = committed_value(&keypair.public);
+        let (reveal, commitment) = commit(&mut board, &committed);
+
+        assert_eq!(CommittedValue::from_reveal(&reveal), Ok(committed));
+        assert_eq!(
+            open(&mut board, commitment, &reveal, &keypair.public, 7, 3, 1),
+            Ok([1u8; 32])
+        );
+        assert_eq!(board.check_commitment(commitment), Ok(Some(reveal.clone())));
+        // it cannot be revealed twice
+        assert!(open(&mut board, commitment, &reveal, &keypair.public, 7, 3, 1).is_err());
+    }
+
+    #[test]
+    fn reveal_is_bound_to_player_game_round_and_street() {
+        let keypair = Keypair::generate_with(OsRng);
+        let other = Keypair::generate_with(OsRng);
+        let mut board = PublicMessageBoard::new(2023);
+        let (reveal, commitment) = commit(&mut board, &committed_value(&keypair.public));
+
+        assert!(open(&mut board, commitment, &reveal, &other.public, 7, 3, 1).is_err());
+        assert!(open(&mut board, commitment, &reveal, &keypair.public, 8, 3, 1).is_err());
+        assert!(open(&mut board, commitment, &reveal, &keypair.public, 7, 4, 1).is_err());
+        assert!(open(&mut board, commitment, &reveal, &keypair.public, 7, 3, 2).is_err());
+        // none of the failed attempts revealed the commitment
+        assert_eq!(board.check_commitment(commitment), Ok(None));
+    }
+
+    #[test]
+    fn tampered_reveal_is_rejected() {
+        let keypair = Keypair::generate_with(OsRng);
+        let mut board = PublicMessageBoard::new(2023);
+        let (reveal, commitment) = commit(&mut board, &committed_value(&keypair.public));
+
+        let mut tampered = committed_value(&keypair.public);
+        tampered.value = [2u8; 32];
+        let tampered_reveal = format!(
+            "{}{}",
+            tampered.to_message(),
+            &reveal[reveal.len() - BOARD_RANDOMNESS_LEN..]
+        );
+        assert!(open(
+            &mut board,
+            commitment,
+            &tampered_reveal,
+            &keypair.public,
+            7,
+            3,
+            1
+        )
+        .is_err());
+        assert!(CommittedValue::from_reveal("not a reveal").is_err());
+    }
+
+    #[test]
+    fn combined_randomness_is_canonical() {
+        let reveals = [[1u8; 32], [2u8; 32], [3u8; 32]];
+        let shuffled = [[3u8; 32], [1u8; 32], [2u8; 32]];
+        assert_eq!(
+            combine_randomness(&reveals, 1, 0, 0),
+            combine_randomness(&shuffled, 1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn combined_randomness_is_unique_per_game_round_and_street() {
+        let reveals = [[1u8; 32], [2u8; 32]];
+        let common = combine_randomness(&reveals, 1, 0, 0);
+        assert_ne!(common, combine_randomness(&reveals, 2, 0, 0));
+        assert_ne!(common, combine_randomness(&reveals, 1, 1, 0));
+        assert_ne!(common, combine_randomness(&reveals, 1, 0, 1));
+        assert_ne!(common, combine_randomness(&reveals[..1], 1, 0, 0));
+        // values that would add up to the same sum give a different result
+        assert_ne!(common, combine_randomness(&[[0u8; 32], [3u8; 32]], 1, 0, 0));
+    }
+}
@@ -0,0 +1,157 @@
+//! Deadlines for the phases of a round, in logical ticks.
+//!
+//! Nothing forces a player to answer. In a commit-reveal the last player to reveal sees every other
+//! value first, and could abort whenever the common random value is bad for them, so a player who
+//! stalls has to lose something. Every phase that needs all the players has a deadline: when it is
+//! their turn to commit, reveal or show their cards at the showdown, each player has as many ticks
+//! of the game clock as the deadline of the phase to answer.
+//!
+//! A player who misses a deadline leaves the table: they forfeit the pot of the round, which goes
+//! to the other players still in, and the deposit they posted when they sat down with the rest of
+//! their stack, which go to the other players of the round. Stalling never costs less than being
+//! caught cheating, so no player is better off walking away from a round that goes badly. A message
+//! that was never sent can't be signed, so every other player of the phase signs a timeout in the
+//! transcript instead, and the verifier only lets a player go when all of them witnessed it.
+//!
+//! The clock is logical: it only moves when a player answers, by the number of ticks they took, or
+//! when a deadline passes, so games and their tests stay deterministic.
+
+use std::fmt;
+
+/// A phase of a round that every player still in has to answer in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Post the commitment to a random value
+    Commit,
+    /// Reveal the random value
+    Reveal,
+    /// Show the cards at the showdown: publish the VRF outputs and proofs of the cards, or remove
+    /// the last layer of encryption from the cards of the finite deck
+    Showdown,
+}
+
+impl Phase {
+    /// The name of the phase, as written in transcripts
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Commit => "commit",
+            Phase::Reveal => "reveal",
+            Phase::Showdown => "showdown",
+        }
+    }
+
+    /// The phase with some name. Errors if there is none.
+    pub fn from_name(name: &str) -> Result<Self, ()> {
+        match name {
+            "commit" => Ok(Phase::Commit),
+            "reveal" => Ok(Phase::Reveal),
+            "showdown" => Ok(Phase::Showdown),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The number of ticks a player has to answer in each phase
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Deadlines {
+    pub commit: u64,
+    pub reveal: u64,
+    pub showdown: u64,
+}
+
+impl Default for Deadlines {
+    fn default() -> Self {
+        Deadlines {
+            commit: 10,
+            reveal: 10,
+            showdown: 10,
+        }
+    }
+}
+
+impl Deadlines {
+    /// The deadline of a phase
+    pub fn of(&self, phase: Phase) -> u64 {
+        match phase {
+            Phase::Commit => self.commit,
+            Phase::Reveal => self.reveal,
+            Phase::Showdown => self.showdown,
+        }
+    }
+}
+
+/// The logical clock of a game
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Clock {
+    now: u64,
+}
+
+impl Clock {
+    /// The current tick
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Wait for the answer of a player who takes some ticks to answer, or never answers with
+    /// None, with a deadline. Returns the tick the answer came in, or errors with the tick the
+    /// deadline passed if it didn't come in time.
+    pub fn wait(&mut self, ticks: Option<u64>, deadline: u64) -> Result<u64, u64> {
+        match ticks {
+            Some(ticks) if ticks <= deadline => {
+                self.now += ticks;
+                Ok(self.now)
+            }
+            _ => {
+                self.now += deadline;
+                Err(self.now)
+            }
+        }
+    }
+}
+
+/// A player who did not answer in a phase before its deadline
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MissedDeadline {
+    pub seat: usize,
+    pub phase: Phase,
+}
+
+impl fmt::Display for MissedDeadline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "player {} missed the {} deadline and left the table",
+            self.seat, self.phase
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phases_have_names() {
+        for phase in [Phase::Commit, Phase::Reveal, Phase::Showdown] {
+            assert_eq!(Phase::from_name(phase.name()), Ok(phase));
+        }
+        assert!(Phase::from_name("bet").is_err());
+    }
+
+    #[test]
+    fn clock_moves_with_the_answers() {
+        let mut clock = Clock::default();
+        assert_eq!(clock.wait(Some(3), 10), Ok(3));
+        assert_eq!(clock.wait(Some(10), 10), Ok(13));
+        // a late answer and no answer at all both wait until the deadline
+        assert_eq!(clock.wait(Some(11), 10), Err(23));
+        assert_eq!(clock.wait(None, 5), Err(28));
+        assert_eq!(clock.now(), 28);
+    }
+}
@@ -0,0 +1,324 @@
+//! Finite-deck dealing with commutative encryption on Ristretto, also known as mental poker.
+//!
+//! Drawing every card from its own infinite deck means the same card can be drawn twice. In the
+//! finite-deck mode the players deal from a single deck of 52 cards instead:
+//!
+//! 1. Every card is a Ristretto point, hashed from its index.
+//! 2. In turn, each player multiplies every point of the deck by their secret scalar for the
+//!    round, and shuffles it. Multiplying by scalars commutes, so once everybody has shuffled,
+//!    every card is encrypted under the product of all the scalars, in an order nobody knows.
+//!    Every shuffle comes with a proof that it is a permutation of the deck under a single key
+//!    (see `shuffle`), which everybody checks before any card is dealt.
+//! 3. A card is dealt from the top of the deck by having every player remove their own layer,
+//!    multiplying by the inverse of their scalar. To deal a private card, every player but its
+//!    owner removes their layer in public, and the owner removes the last one alone. To show it,
+//!    the owner removes their layer in public too.
+//!
+//! Every position of the deck is dealt at most once, so no card can appear twice in a round.
+//!
+//! Each player publishes the public key of their scalar with their shuffle, and every layer they
+//! remove comes with a Chaum-Pedersen proof that the same scalar takes the base point to their
+//! public key and the decrypted point back to the encrypted one, so nobody can remove a layer with
+//! another key than the one they shuffled with.
+
+use std::sync::OnceLock;
+
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_TABLE;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use rand::{CryptoRng, Rng, RngCore};
+use schnorrkel::context::SigningTranscript;
+use schnorrkel::signing_context;
+use sha2::Sha512;
+
+use super::card::{Card, DECK_SIZE};
+use super::shuffle::{self, Shuffle, ShuffleSecret};
+
+/// The domain of the hash from the index of a card to its point
+const CARD_POINT_CONTEXT: &[u8] = b"finite deck poker card";
+
+/// The signing context of the Fiat-Shamir transcript of the decryption proofs
+const DECRYPTION_CONTEXT: &[u8] = b"finite deck poker decryption";
+
+/// The point of a card, which nobody knows the discrete logarithm of
+pub fn card_point(card: Card) -> RistrettoPoint {
+    let mut data = CARD_POINT_CONTEXT.to_vec();
+    data.push(card.index());
+    RistrettoPoint::hash_from_bytes::<Sha512>(&data)
+}
+
+/// The card of a fully decrypted point, if it is the point of a card
+pub fn card_from_point(point: &RistrettoPoint) -> Option<Card> {
+    let index = card_points().iter().position(|p| p == point)?;
+    Card::from_index(index as u8).ok()
+}
+
+/// The points of the 52 cards, in order, before anybody encrypted or shuffled them
+pub fn new_deck() -> Vec<RistrettoPoint> {
+    card_points().to_vec()
+}
+
+/// The points of the 52 cards, hashed only once
+fn card_points() -> &'static [RistrettoPoint] {
+    static POINTS: OnceLock<Vec<RistrettoPoint>> = OnceLock::new();
+    POINTS.get_or_init(|| {
+        (0..DECK_SIZE)
+            .map(|index| card_point(Card::from_index(index).unwrap()))
+            .collect()
+    })
+}
+
+/// The secret scalar a player encrypts the deck of a round with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeckKey {
+    secret: Scalar,
+}
+
+impl DeckKey {
+    /// Generate a fresh key for a round
+    pub fn generate(rng: &mut impl Rng) -> Self {
+        DeckKey {
+            secret: random_scalar(rng),
+        }
+    }
+
+    /// The public key of our scalar: the base point multiplied by it
+    pub fn public(&self) -> RistrettoPoint {
+        &self.secret * &RISTRETTO_BASEPOINT_TABLE
+    }
+
+    /// Add our layer of encryption to a point
+    pub fn encrypt(&self, point: &RistrettoPoint) -> RistrettoPoint {
+        self.secret * point
+    }
+
+    /// Remove our layer of encryption from a point
+    pub fn decrypt(&self, point: &RistrettoPoint) -> RistrettoPoint {
+        self.secret.invert() * point
+    }
+
+    /// Remove our layer of encryption from a point, with the proof that we removed the layer of our
+    /// own key. The nonce of the proof is drawn from the transcript of the statement rekeyed with
+    /// our key, so it stays secret even if the RNG is predictable.
+    pub fn decrypt_proven(
+        &self,
+        point: &RistrettoPoint,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (RistrettoPoint, DecryptionProof) {
+        let decrypted = self.decrypt(point);
+        let mut nonce = [0u8; 64];
+        decryption_transcript(&self.public(), point, &decrypted).witness_bytes_rng(
+            b"nonce",
+            &mut nonce,
+            &[self.secret.as_bytes()],
+            rng,
+        );
+        let nonce = Scalar::from_bytes_mod_order_wide(&nonce);
+        let challenge = decryption_challenge(
+            &self.public(),
+            point,
+            &decrypted,
+            &(&nonce * &RISTRETTO_BASEPOINT_TABLE),
+            &(nonce * decrypted),
+        );
+        let response = nonce + challenge * self.secret;
+        (
+            decrypted,
+            DecryptionProof {
+                challenge,
+                response,
+            },
+        )
+    }
+
+    /// Encrypt every card of a deck and shuffle it, with the proof of the shuffle and what we keep
+    /// to open it
+    pub fn shuffle(&self, deck: &[RistrettoPoint], rng: &mut impl Rng) -> (Shuffle, ShuffleSecret) {
+        shuffle::shuffle(self.secret, deck, rng)
+    }
+}
+
+/// A Chaum-Pedersen proof that a player removed the layer of their own key from a point: the
+/// discrete logarithm of their public key to the base point is the one of the encrypted point to
+/// the decrypted one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecryptionProof {
+    pub challenge: Scalar,
+    pub response: Scalar,
+}
+
+/// Verify that a point was decrypted with the key behind a public key. Errors if the proof does
+/// not hold.
+pub fn verify_decryption(
+    public: &RistrettoPoint,
+    point: &RistrettoPoint,
+    decrypted: &RistrettoPoint,
+    proof: &DecryptionProof,
+) -> Result<(), ()> {
+    // the commitments of the prover, recomputed from the challenge and the response
+    let commitment_base = &proof.response * &RISTRETTO_BASEPOINT_TABLE - proof.challenge * public;
+    let commitment_point = proof.response * decrypted - proof.challenge * point;
+    let challenge = decryption_challenge(
+        public,
+        point,
+        decrypted,
+        &commitment_base,
+        &commitment_point,
+    );
+    match challenge == proof.challenge {
+        true => Ok(()),
+        false => Err(()),
+    }
+}
+
+/// The transcript of the statement of a decryption proof
+fn decryption_transcript(
+    public: &RistrettoPoint,
+    point: &RistrettoPoint,
+    decrypted: &RistrettoPoint,
+) -> impl SigningTranscript {
+    let mut transcript = signing_context(DECRYPTION_CONTEXT).bytes(b"dleq");
+    transcript.append_message(b"public", public.compress().as_bytes());
+    transcript.append_message(b"point", point.compress().as_bytes());
+    transcript.append_message(b"decrypted", decrypted.compress().as_bytes());
+    transcript
+}
+
+/// The Fiat-Shamir challenge of a decryption proof, from a transcript of the statement and the
+/// commitments of the prover
+fn decryption_challenge(
+    public: &RistrettoPoint,
+    point: &RistrettoPoint,
+    decrypted: &RistrettoPoint,
+    commitment_base: &RistrettoPoint,
+    commitment_point: &RistrettoPoint,
+) -> Scalar {
+    let mut transcript = decryption_transcript(public, point, decrypted);
+    transcript.commit_bytes(b"commitment_base", commitment_base.compress().as_bytes());
+    transcript.commit_bytes(b"commitment_point", commitment_point.compress().as_bytes());
+    let mut bytes = [0u8; 64];
+    transcript.challenge_bytes(b"challenge", &mut bytes);
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+/// A uniformly random scalar
+pub fn random_scalar(rng: &mut impl Rng) -> Scalar {
+    let mut bytes = [0u8; 64];
+    rng.fill(&mut bytes[..]);
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+/// The hex encoding of a point, as written in transcripts
+pub fn point_to_hex(point: &RistrettoPoint) -> String {
+    hex::encode(point.compress().as_bytes())
+}
+
+/// Parse a point from its hex encoding. Errors if it is not a valid point.
+pub fn point_from_hex(s: &str) -> Result<RistrettoPoint, ()> {
+    let mut bytes = [0u8; 32];
+    hex::decode_to_slice(s, &mut bytes).map_err(|_| ())?;
+    CompressedRistretto(bytes).decompress().ok_or(())
+}
+
+/// The hex encoding of a scalar, as written in transcripts
+pub fn scalar_to_hex(scalar: &Scalar) -> String {
+    hex::encode(scalar.as_bytes())
+}
+
+/// Parse a scalar from its hex encoding. Errors if it is not a canonical scalar.
+pub fn scalar_from_hex(s: &str) -> Result<Scalar, ()> {
+    let mut bytes = [0u8; 32];
+    hex::decode_to_slice(s, &mut bytes).map_err(|_| ())?;
+    Scalar::from_canonical_bytes(bytes).ok_or(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn card_points_are_distinct() {
+        let deck = new_deck();
+        for (index, point) in deck.iter().enumerate() {
+            assert_eq!(card_from_point(point), Card::from_index(index as u8).ok());
+            assert_eq!(point_from_hex(&point_to_hex(point)), Ok(*point));
+        }
+        assert_eq!(card_from_point(&(deck[0] + deck[1])), None);
+    }
+
+    #[test]
+    fn layers_can_be_removed_in_any_order() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let alice = DeckKey::generate(&mut rng);
+        let bob = DeckKey::generate(&mut rng);
+        let point = card_point(Card::from_index(7).unwrap());
+        let encrypted = bob.encrypt(&alice.encrypt(&point));
+        assert_ne!(
+            card_from_point(&encrypted),
+            Some(Card::from_index(7).unwrap())
+        );
+        assert_eq!(alice.decrypt(&bob.decrypt(&encrypted)), point);
+        assert_eq!(bob.decrypt(&alice.decrypt(&encrypted)), point);
+    }
+
+    #[test]
+    fn decryptions_are_proven_with_the_key() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let alice = DeckKey::generate(&mut rng);
+        let bob = DeckKey::generate(&mut rng);
+        let point = alice.encrypt(&card_point(Card::from_index(7).unwrap()));
+        let (decrypted, proof) = alice.decrypt_proven(&point, &mut rng);
+        assert_eq!(decrypted, alice.decrypt(&point));
+        assert_eq!(
+            verify_decryption(&alice.public(), &point, &decrypted, &proof),
+            Ok(())
+        );
+        // the proof holds neither for another key nor for another point
+        assert!(verify_decryption(&bob.public(), &point, &decrypted, &proof).is_err());
+        let other = decrypted + decrypted;
+        assert!(verify_decryption(&alice.public(), &point, &other, &proof).is_err());
+        // and a decryption with another key can't be proven with ours
+        let (wrong, _) = bob.decrypt_proven(&point, &mut rng);
+        assert!(verify_decryption(&alice.public(), &point, &wrong, &proof).is_err());
+        assert_eq!(
+            scalar_from_hex(&scalar_to_hex(&proof.response)),
+            Ok(proof.response)
+        );
+    }
+
+    #[test]
+    fn decryption_nonces_are_bound_to_the_statement() {
+        let alice = DeckKey::generate(&mut StdRng::seed_from_u64(3));
+        let points =
+            [7, 8].map(|index| alice.encrypt(&card_point(Card::from_index(index).unwrap())));
+        // two proofs from the same RNG state would give the key away if they shared their nonce
+        let proofs = points.map(|point| {
+            alice
+                .decrypt_proven(&point, &mut StdRng::seed_from_u64(4))
+                .1
+        });
+        let key = (proofs[0].response - proofs[1].response)
+            * (proofs[0].challenge - proofs[1].challenge).invert();
+        assert_ne!(key, alice.secret);
+    }
+
+    #[test]
+    fn shuffled_deck_holds_every_card_once() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let keys: Vec<DeckKey> = (0..3).map(|_| DeckKey::generate(&mut rng)).collect();
+        let deck = keys
+            .iter()
+            .fold(new_deck(), |deck, key| key.shuffle(&deck, &mut rng).0.deck);
+        let mut cards: Vec<u8> = deck
+            .iter()
+            .map(|point| {
+                let point = keys.iter().fold(*point, |point, key| key.decrypt(&point));
+                card_from_point(&point).unwrap().index()
+            })
+            .collect();
+        assert_ne!(cards, (0..DECK_SIZE).collect::<Vec<u8>>());
+        cards.sort_unstable();
+        assert_eq!(cards, (0..DECK_SIZE).collect::<Vec<u8>>());
+    }
+}
@@ -0,0 +1,1028 @@
+use std::convert::Infallible;
+
+use curve25519_dalek::ristretto::RistrettoPoint;
+use schnorrkel::PublicKey;
+
+use super::betting::Blinds;
+use super::betting::{Action, BettingRound};
+use super::card::Card;
+use super::cheating::CheatingEvidence;
+use super::deadline::{Clock, Deadlines, Phase};
+use super::deck::DecryptionProof;
+use super::ledger::Ledger;
+use super::player::Player;
+use super::round::{self, Dealing, Messages, Round, Seating, Variant};
+use super::shuffle::{Opening, Shuffle};
+use super::tournament::Schedule;
+use super::transcript::{Entry, Transcript};
+use super::vrf::CardDraw;
+use crate::p1_hashing::{HashValue, PublicMessageBoard};
+
+/// The minimum number of players at a table
+pub const MIN_PLAYERS: usize = 2;
+
+/// The maximum number of players at a table
+pub const MAX_PLAYERS: usize = 10;
+
+/// The smallest bet allowed
+pub const MIN_BET: u32 = 1;
+
+/// A game of infinite deck poker between 2 to 10 players.
+///
+/// Each round is played by every player who still has points, and goes as follows:
+///
+/// 1. Each player generates a random value and commits to it on the message board, with a random
+///    salt and bound to their public key, the game id, the round number and the street.
+/// 2. They reveal and verify each other's values against the commitments.
+/// 3. They hash the values together with the game id, round number and street in order to
+///    generate a common random value (the VRF input).
+/// 4. Each player evaluates their VRF on the common random value followed by the slot of each card
+///    to draw their private cards, which only they know: five cards, or two hole cards in hold'em.
+/// 5. They bet following the usual poker rules, with their own strategy. The button moves to the
+///    next player still in every round, and the first player to act is the next one after it. In
+///    a tournament, every player posts an ante and the first two after the button post the blinds
+///    first, following the schedule of the game (see `tournament`). Nobody can put in more than
+///    their own stack over the whole round: a player who can't match a bet can call with
+///    everything they have and go all-in, and stays in until the showdown without betting anymore.
+/// 6. In hold'em, the players still in run a fresh commit-reveal for each of the flop, the turn
+///    and the river, deal the community cards from its common random value, and bet again. In
+///    five-card draw, they announce which cards they discard, draw the replacements with their
+///    VRF on an input that includes the draw round and the slot, and bet again.
+/// 7. If everybody but one player folded, that player wins the pot without showing their cards.
+///    Otherwise the players left publish their VRF outputs and proofs, for the replaced cards as
+///    well, and read each other's cards only after verifying the proofs. The pot is split into a
+///    main pot and a side pot for each all-in (see `ledger`), and the players with the best poker
+///    hand, counting the community cards, among the ones who can win a pot split it. Suits do not
+///    rank, so several players can tie. If a pot can't be split evenly, the remaining points go
+///    to the tied winners in seat order.
+///
+/// In the finite-deck mode, step 4 is replaced by a shuffle between the commitments and the
+/// reveals: every player encrypts the deck with a fresh key and shuffles it in turn, and proves
+/// their shuffle on the common random value once it is revealed. The cards are then dealt from
+/// the deck by having the players remove their layers of encryption (see `deck`). The community
+/// cards are dealt from the deck too, and at the showdown the players remove their own layer from
+/// their cards in public.
+///
+/// Any failed check stops the round with evidence against the cheater, who forfeits all their
+/// points to the other players.
+///
+/// Every player has a deadline to commit, to reveal and to show their cards at the showdown. A
+/// player who misses one stops the round and leaves the table: they forfeit their deposit, what
+/// they put in the pot and the rest of their stack to the other players (see `deadline`).
+///
+/// The game continues until only one player has points left.
+pub struct Game {
+    /// The id of the game, which every commitment is bound to
+    pub game_id: u64,
+    /// The variant of poker played
+    pub variant: Variant,
+    /// How the cards are dealt
+    pub dealing: Dealing,
+    /// The blinds of every hand, none unless the game is a tournament
+    pub schedule: Schedule,
+    /// The ticks every player has to answer in each phase
+    pub deadlines: Deadlines,
+    /// The players at the table, by seat
+    pub players: Vec<Player>,
+    /// The rounds played so far
+    pub rounds: Vec<Round>,
+    /// The evidence against the players caught cheating so far
+    pub evidence: Vec<CheatingEvidence>,
+    /// The message board the players post their commitments and reveals to
+    pub board: PublicMessageBoard,
+    /// Every message of the game, signed by the player who sent it, with the points after each
+    /// round
+    pub transcript: Transcript,
+    /// The number of the next round, counting the rounds stopped by a cheater
+    next_round: u32,
+    /// The seat of the button in the last round
+    button: Option<usize>,
+    /// The clock the deadlines are counted on
+    clock: Clock,
+}
+
+impl Game {
+    /// Create a new game of five-card poker between the players, who enter it with the child key
+    /// of the game derived from their master key. Errors if there are less than 2 or more than 10
+    /// players.
+    pub fn new(game_id: u64, mut players: Vec<Player>) -> Result<Self, ()> {
+        if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&players.len()) {
+            return Err(());
+        }
+        for player in players.iter_mut() {
+            player.enter_game(game_id);
+        }
+        Ok(Game {
+            game_id,
+            variant: Variant::default(),
+            dealing: Dealing::default(),
+            schedule: Schedule::default(),
+            deadlines: Deadlines::default(),
+            transcript: Transcript::new(game_id, &players),
+            players,
+            rounds: Vec::new(),
+            evidence: Vec::new(),
+            board: PublicMessageBoard::new(game_id),
+            next_round: 0,
+            button: None,
+            clock: Clock::default(),
+        })
+    }
+
+    /// Play another variant of poker, before the first round
+    pub fn with_variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self.transcript.variant = variant;
+        self
+    }
+
+    /// Deal the cards in another mode, before the first round
+    pub fn with_dealing(mut self, dealing: Dealing) -> Self {
+        self.dealing = dealing;
+        self.transcript.dealing = dealing;
+        self
+    }
+
+    /// Post blinds following a schedule, before the first round
+    pub fn with_schedule(mut self, schedule: Schedule) -> Self {
+        self.transcript.schedule = schedule.clone();
+        self.schedule = schedule;
+        self
+    }
+
+    /// Have every player put aside a deposit when they sit down, before the first round
+    pub fn with_deposit(mut self, deposit: u32) -> Self {
+        for player in self.players.iter_mut() {
+            player.deposit = deposit;
+        }
+        self.transcript.deposit = deposit;
+        self
+    }
+
+    /// The blinds of the next round
+    pub fn blinds(&self) -> Blinds {
+        self.schedule.blinds(self.next_round)
+    }
+
+    /// The seats of the players who missed a deadline and left the table
+    pub fn left(&self) -> Vec<usize> {
+        self.rounds
+            .iter()
+            .filter_map(|round| round.missed)
+            .map(|missed| missed.seat)
+            .collect()
+    }
+
+    /// The seats of the players who still have points and did not leave the table
+    pub fn seats_in(&self) -> Vec<usize> {
+        let left = self.left();
+        (0..self.players.len())
+            .filter(|seat| self.players[*seat].points > 0 && !left.contains(seat))
+            .collect()
+    }
+
+    /// The game is over once only one player has points left
+    pub fn is_over(&self) -> bool {
+        self.seats_in().len() < MIN_PLAYERS
+    }
+
+    /// The seat of the player who won the game, if it is over
+    pub fn winner(&self) -> Option<usize> {
+        if !self.is_over() {
+            return None;
+        }
+        self.seats_in().first().copied()
+    }
+
+    /// Play a single round of the game, returning what happened in it.
+    ///
+    /// If a player cheats, the round stops and returns the evidence against them. The cheater
+    /// forfeits all their points to the other players of the round, so they are out of the game.
+    /// If a player misses a deadline, the round stops without a showdown and records it, and the
+    /// player leaves the table.
+    pub fn play_round(&mut self) -> Result<Round, CheatingEvidence> {
+        let number = self.next_round;
+        self.next_round += 1;
+        // the header can't change once the game started, so every player joins it now
+        if number == 0 {
+            let header = self.transcript.header();
+            for (seat, player) in self.players.iter().enumerate() {
+                self.transcript
+                    .sign(Entry::Join { seat, header }, &player.keypair);
+            }
+        }
+        let seats = self.seats_in();
+        let button = round::next_button(self.button, &seats);
+        self.button = Some(button);
+        let seating = Seating {
+            stacks: seats
+                .iter()
+                .map(|seat| self.players[*seat].points)
+                .collect(),
+            seats: seats.clone(),
+            button,
+        };
+
+        let mut table = Table {
+            game_id: self.game_id,
+            players: &mut self.players,
+            board: &mut self.board,
+            transcript: &mut self.transcript,
+            history: &self.rounds,
+            clock: &mut self.clock,
+            deadlines: self.deadlines,
+        };
+        let result = round::play(
+            &mut table,
+            self.game_id,
+            self.variant,
+            self.dealing,
+            self.schedule.blinds(number),
+            number,
+            &seating,
+        );
+        let result = match result {
+            Ok(result) => result,
+            Err(never) => match never {},
+        };
+
+        let mut ledger = Ledger::new(self.players.iter().map(|player| player.points).collect())
+            .with_deposits(self.players.iter().map(|player| player.deposit).collect());
+        ledger.settle(&seats, &result);
+        for (seat, player) in self.players.iter_mut().enumerate() {
+            player.points = ledger.stacks()[seat];
+            player.deposit = ledger.deposits()[seat];
+        }
+        match &result {
+            Ok(round) => self.rounds.push(round.clone()),
+            Err(evidence) => self.evidence.push(evidence.clone()),
+        }
+        self.transcript.record(Entry::Points {
+            round: number,
+            points: ledger.stacks().to_vec(),
+        });
+        result
+    }
+}
+
+/// The players at the table while a round is played live. Every message they send is signed into
+/// the transcript.
+struct Table<'a> {
+    game_id: u64,
+    players: &'a mut [Player],
+    board: &'a mut PublicMessageBoard,
+    transcript: &'a mut Transcript,
+    /// The rounds played before this one
+    history: &'a [Round],
+    clock: &'a mut Clock,
+    deadlines: Deadlines,
+}
+
+impl Table<'_> {
+    /// Record a message of the player in a seat in the transcript, signed with their key
+    fn sign(&mut self, seat: usize, entry: Entry) {
+        self.transcript.sign(entry, &self.players[seat].keypair);
+    }
+}
+
+impl Messages for Table<'_> {
+    type Error = Infallible;
+
+    fn public(&self, seat: usize) -> PublicKey {
+        self.players[seat].public()
+    }
+
+    fn commitments(
+        &mut self,
+        seat: usize,
+        number: u32,
+        street: u32,
+    ) -> Result<Vec<HashValue>, Infallible> {
+        let posted = self.players[seat].commit(self.board, self.game_id, number, street);
+        for commitment in &posted {
+            self.sign(
+                seat,
+                Entry::Commitment {
+                    round: number,
+                    seat,
+                    street,
+                    commitment: *commitment,
+                },
+            );
+        }
+        Ok(posted)
+    }
+
+    fn on_time(
+        &mut self,
+        seat: usize,
+        number: u32,
+        phase: Phase,
+        seats: &[usize],
+    ) -> Result<bool, Infallible> {
+        let ticks = self.players[seat].answer_time(phase);
+        match self.clock.wait(ticks, self.deadlines.of(phase)) {
+            Ok(_) => Ok(true),
+            // nobody can sign the message that never came, so every other player of the phase
+            // signs that they saw the deadline pass
+            Err(tick) => {
+                for witness in seats.iter().copied().filter(|s| *s != seat) {
+                    let timeout = Entry::Timeout {
+                        round: number,
+                        seat,
+                        phase,
+                        tick,
+                        witness,
+                    };
+                    self.sign(witness, timeout);
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    fn reveal(&mut self, seat: usize, number: u32, street: u32) -> Result<String, Infallible> {
+        let reveal = self.players[seat].reveal().unwrap_or_default();
+        self.sign(
+            seat,
+            Entry::Reveal {
+                round: number,
+                seat,
+                street,
+                reveal: reveal.clone(),
+            },
+        );
+        // the board only takes reveals of its own commitments, and the round checks the rest
+        let _ = self.board.post_reveal(reveal.clone());
+        Ok(reveal)
+    }
+
+    fn deal(&mut self, seat: usize, common_random: &[u8; 32], cards: usize) {
+        self.players[seat].draw_hand(common_random, cards);
+    }
+
+    fn shuffle(
+        &mut self,
+        seat: usize,
+        number: u32,
+        deck: &[RistrettoPoint],
+    ) -> Result<Shuffle, Infallible> {
+        let shuffle = self.players[seat].shuffle(deck);
+        self.sign(
+            seat,
+            Entry::Shuffle {
+                round: number,
+                seat,
+                shuffle: shuffle.clone(),
+            },
+        );
+        Ok(shuffle)
+    }
+
+    fn open_shuffle(
+        &mut self,
+        seat: usize,
+        number: u32,
+        challenges: &[bool],
+    ) -> Result<Vec<Opening>, Infallible> {
+        let openings = self.players[seat].open_shuffle(challenges);
+        self.sign(
+            seat,
+            Entry::Opening {
+                round: number,
+                seat,
+                openings: openings.clone(),
+            },
+        );
+        Ok(openings)
+    }
+
+    fn decrypt(
+        &mut self,
+        seat: usize,
+        number: u32,
+        position: usize,
+        point: &RistrettoPoint,
+    ) -> Result<(RistrettoPoint, DecryptionProof), Infallible> {
+        let (decrypted, proof) = self.players[seat].decrypt(point);
+        self.sign(
+            seat,
+            Entry::Decrypt {
+                round: number,
+                seat,
+                position,
+                point: decrypted,
+                proof,
+            },
+        );
+        Ok((decrypted, proof))
+    }
+
+    fn receive(&mut self, seat: usize, slot: usize, point: &RistrettoPoint) {
+        self.players[seat].receive(slot, point);
+    }
+
+    fn redraw(&mut self, seat: usize, draw_random: &[u8; 32], draw_round: u32, slots: &[usize]) {
+        self.players[seat].redraw(draw_random, draw_round, slots);
+    }
+
+    fn action(
+        &mut self,
+        seat: usize,
+        number: u32,
+        seats: &[usize],
+        betting: &BettingRound,
+        community: &[Card],
+    ) -> Result<Action, Infallible> {
+        // each player bets knowing only their own cards and the community cards
+        let cards = [self.players[seat].hand.as_slice(), community].concat();
+        let opponent_stacks: Vec<u32> = seats
+            .iter()
+            .filter(|other| **other != seat)
+            .map(|other| self.players[*other].points)
+            .collect();
+        let action =
+            self.players[seat].act(&cards, &betting.view(), &opponent_stacks, self.history);
+        // an action that is not allowed counts as a fold
+        let action = match betting.allows(action) {
+            true => action,
+            false => Action::Fold,
+        };
+        self.sign(
+            seat,
+            Entry::Action {
+                round: number,
+                seat,
+                action,
+            },
+        );
+        Ok(action)
+    }
+
+    fn discard(
+        &mut self,
+        seat: usize,
+        number: u32,
+        draw_round: u32,
+        hand_size: usize,
+        most: usize,
+    ) -> Result<Vec<usize>, Infallible> {
+        // only the slots of the hand can be discarded, each of them once
+        let mut slots = self.players[seat].discard();
+        slots.sort_unstable();
+        slots.dedup();
+        slots.retain(|slot| *slot < hand_size);
+        slots.truncate(most);
+        self.sign(
+            seat,
+            Entry::Discard {
+                round: number,
+                seat,
+                draw_round,
+                slots: slots.clone(),
+            },
+        );
+        Ok(slots)
+    }
+
+    fn draws(
+        &mut self,
+        seat: usize,
+        number: u32,
+        common_random: &[u8; 32],
+        recipients: &[usize],
+        cards: &[(usize, u32)],
+    ) -> Result<Vec<Vec<CardDraw>>, Infallible> {
+        let draws = self.players[seat].publish_draws(common_random, recipients.len());
+        for (recipient, sent) in recipients.iter().zip(&draws) {
+            for ((slot, draw_round), draw) in cards.iter().zip(sent) {
+                self.sign(
+                    seat,
+                    Entry::Draw {
+                        round: number,
+                        seat,
+                        recipient: *recipient,
+                        slot: *slot,
+                        draw_round: *draw_round,
+                        draw: draw.clone(),
+                    },
+                );
+            }
+        }
+        Ok(draws)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poker::cheating::Cheat;
+    use crate::poker::deadline::MissedDeadline;
+    use crate::poker::hand::{self, HAND_SIZE};
+    use crate::poker::keys;
+    use crate::poker::strategy::ConstantStrategy;
+    use crate::poker::transcript::verify_transcript;
+    use crate::poker::vrf;
+    use rand_core::OsRng;
+    use schnorrkel::Keypair;
+
+    const SEED1: &str =
+        "lunar antique thank school space garden soda cigar glove despair master pumpkin";
+    const SEED2: &str = "parade method erupt shock bacon wait follow limb onion laugh exhibit whip";
+
+    fn new_game() -> Game {
+        Game::new(
+            0,
+            vec![Player::new(SEED1, 100, 0), Player::new(SEED2, 100, 1)],
+        )
+        .unwrap()
+    }
+
+    fn new_table(players: usize) -> Game {
+        let players = (0..players)
+            .map(|i| Player::from_keypair(Keypair::generate_with(OsRng), 100, i as u64))
+            .collect();
+        Game::new(1, players).unwrap()
+    }
+
+    fn total_points(game: &Game) -> u32 {
+        game.players.iter().map(|p| p.points).sum()
+    }
+
+    #[test]
+    fn round_moves_pot_to_winner() {
+        let mut game = new_game();
+        let round = game.play_round().unwrap();
+        let points: Vec<u32> = game.players.iter().map(|p| p.points).collect();
+        // the pot is split, so each player's loss is the other's gain
+        let winners = round.winners();
+        let won = |seat: usize| winners.contains(&seat) as u32 * round.pot() / winners.len() as u32;
+        assert_eq!(points[0], 100 - round.contributions[0] + won(0));
+        assert_eq!(points[1], 100 - round.contributions[1] + won(1));
+        assert_eq!(total_points(&game), 200);
+    }
+
+    #[test]
+    fn points_are_conserved_until_game_is_over() {
+        let mut game = new_game();
+        while !game.is_over() {
+            game.play_round().unwrap();
+            assert_eq!(total_points(&game), 200);
+        }
+        assert!(game.winner().is_some());
+        assert_eq!(
+            game.rounds.len() as u32,
+            game.rounds.last().unwrap().number + 1
+        );
+    }
+
+    #[test]
+    fn games_with_same_seeds_are_identical() {
+        let mut game_1 = new_game();
+        let mut game_2 = new_game();
+        for _ in 0..5 {
+            assert_eq!(game_1.play_round(), game_2.play_round());
+        }
+    }
+
+    #[test]
+    fn commitments_are_revealed_on_the_board() {
+        let mut game = new_game();
+        let round = game.play_round().unwrap();
+        for commitment in round.commitments {
+            assert!(matches!(
+                game.board.check_commitment(commitment),
+                Ok(Some(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn players_use_the_child_key_of_the_game() {
+        let game = new_game();
+        for player in &game.players {
+            assert_ne!(player.public(), player.master_public());
+            assert!(keys::is_game_public(
+                &player.master_public(),
+                game.game_id,
+                &player.public()
+            ));
+        }
+        assert_eq!(game.transcript.players[0], game.players[0].public());
+    }
+
+    #[test]
+    fn table_size_is_checked() {
+        assert!(Game::new(0, vec![Player::new(SEED1, 100, 0)]).is_err());
+        let players = (0..11)
+            .map(|i| Player::from_keypair(Keypair::generate_with(OsRng), 100, i))
+            .collect();
+        assert!(Game::new(0, players).is_err());
+    }
+
+    #[test]
+    fn table_game_runs_until_one_player_is_left() {
+        let mut game = new_table(MAX_PLAYERS);
+        while !game.is_over() {
+            let seats_before = game.seats_in();
+            let round = game.play_round().unwrap();
+            assert_eq!(round.seats, seats_before);
+            assert!(!round.pots.is_empty());
+            for pot in &round.pots {
+                assert!(!pot.winners.is_empty());
+                assert!(pot.winners.iter().all(|seat| pot.eligible.contains(seat)));
+                assert!(pot.eligible.iter().all(|seat| round.seats.contains(seat)));
+            }
+            assert_eq!(
+                round.pots.iter().map(|pot| pot.amount).sum::<u32>(),
+                round.pot()
+            );
+            assert_eq!(total_points(&game), 100 * MAX_PLAYERS as u32);
+        }
+        let winner = game.winner().unwrap();
+        assert_eq!(game.players[winner].points, 100 * MAX_PLAYERS as u32);
+    }
+
+    #[test]
+    fn players_out_of_points_sit_out() {
+        let mut game = new_table(3);
+        game.players[1].points = 0;
+        let round = game.play_round().unwrap();
+        assert_eq!(round.seats, vec![0, 2]);
+        assert_eq!(game.players[1].points, 0);
+    }
+
+    fn play_against_cheater(cheat: Cheat) -> (Game, CheatingEvidence) {
+        let mut game = new_table(3);
+        // everybody calls the first bet, so the cheater has to show their hand
+        for player in game.players.iter_mut() {
+            player.strategy = Box::new(ConstantStrategy(10));
+        }
+        game.players[1].cheat = Some(cheat);
+        let evidence = game.play_round().unwrap_err();
+        assert_eq!(evidence.seat(), 1);
+        assert_eq!(game.evidence, vec![evidence.clone()]);
+        // the cheater forfeits their points to the others, and is out of the game
+        assert_eq!(game.players[1].points, 0);
+        assert_eq!(game.players[0].points, 150);
+        assert_eq!(game.players[2].points, 150);
+        (game, evidence)
+    }
+
+    #[test]
+    fn bad_reveal_is_caught() {
+        let (game, evidence) = play_against_cheater(Cheat::BadReveal);
+        match evidence {
+            CheatingEvidence::BadReveal {
+                commitment, reveal, ..
+            } => assert_ne!(PublicMessageBoard::reveal_to_commit(&reveal), commitment),
+            _ => panic!("wrong evidence {:?}", evidence),
+        }
+        assert!(game.rounds.is_empty());
+    }
+
+    #[test]
+    fn invalid_vrf_proof_is_caught() {
+        let (game, evidence) = play_against_cheater(Cheat::InvalidVrfProof);
+        match evidence {
+            CheatingEvidence::InvalidVrfProof { input, draw, .. } => {
+                assert!(vrf::verify(&game.players[1].public(), &input, &draw).is_err())
+            }
+            _ => panic!("wrong evidence {:?}", evidence),
+        }
+    }
+
+    #[test]
+    fn double_commitment_is_caught() {
+        let (_, evidence) = play_against_cheater(Cheat::DoubleCommitment);
+        match evidence {
+            CheatingEvidence::DoubleCommitment {
+                round,
+                first,
+                second,
+                ..
+            } => {
+                assert_eq!(round, 0);
+                assert_ne!(first, second);
+            }
+            _ => panic!("wrong evidence {:?}", evidence),
+        }
+    }
+
+    #[test]
+    fn equivocation_is_caught() {
+        let (_, evidence) = play_against_cheater(Cheat::Equivocation);
+        match evidence {
+            CheatingEvidence::Equivocation { first, second, .. } => assert_ne!(first, second),
+            _ => panic!("wrong evidence {:?}", evidence),
+        }
+    }
+
+    #[test]
+    fn game_goes_on_after_cheater_is_out() {
+        let (mut game, _) = play_against_cheater(Cheat::BadReveal);
+        let round = game.play_round().unwrap();
+        assert_eq!(round.number, 1);
+        assert_eq!(round.seats, vec![0, 2]);
+    }
+
+    #[test]
+    fn staller_forfeits_the_deposit_the_pot_and_the_stack() {
+        let mut game = new_table(3).with_deposit(20);
+        for player in game.players.iter_mut() {
+            player.strategy = Box::new(ConstantStrategy(10));
+        }
+        game.players[1].cheat = Some(Cheat::Stall(Phase::Showdown));
+        let round = game.play_round().unwrap();
+        assert_eq!(
+            round.missed,
+            Some(MissedDeadline {
+                seat: 1,
+                phase: Phase::Showdown
+            })
+        );
+        assert_eq!(round.contributions, vec![10, 10, 10]);
+        assert_eq!(round.winners(), vec![0, 2]);
+        // the others split the pot, and the deposit and the rest of the stack of the staller
+        let points: Vec<u32> = game.players.iter().map(|p| p.points).collect();
+        assert_eq!(points, vec![160, 0, 160]);
+        let deposits: Vec<u32> = game.players.iter().map(|p| p.deposit).collect();
+        assert_eq!(deposits, vec![20, 0, 20]);
+        // the staller left the table, and the game goes on without them
+        assert_eq!(game.seats_in(), vec![0, 2]);
+        assert_eq!(game.play_round().unwrap().seats, vec![0, 2]);
+        let verified = verify_transcript(&game.transcript).unwrap();
+        assert_eq!(
+            verified.points,
+            game.players.iter().map(|p| p.points).collect::<Vec<_>>()
+        );
+        assert_eq!(verified.missed, vec![round.missed.unwrap()]);
+    }
+
+    #[test]
+    fn slow_player_misses_the_deadline() {
+        let mut game = new_table(2);
+        game.players[0].latency = game.deadlines.commit + 1;
+        let round = game.play_round().unwrap();
+        assert_eq!(round.missed.map(|missed| missed.phase), Some(Phase::Commit));
+        // nobody put anything in yet, but the staller still loses their whole stack
+        assert!(round.pots.is_empty());
+        assert_eq!(game.players[0].points, 0);
+        assert_eq!(total_points(&game), 200);
+        assert_eq!(game.winner(), Some(1));
+    }
+
+    #[test]
+    fn players_bet_with_their_own_strategy() {
+        let players = vec![
+            Player::new(SEED1, 100, 0).with_strategy(Box::new(ConstantStrategy(5))),
+            Player::new(SEED2, 100, 1).with_strategy(Box::new(ConstantStrategy(20))),
+        ];
+        let mut game = Game::new(0, players).unwrap();
+        let round = game.play_round().unwrap();
+        assert_eq!(
+            round.actions,
+            vec![
+                (0, Action::Bet(5)),
+                (1, Action::Raise(20)),
+                (0, Action::Fold)
+            ]
+        );
+        assert_eq!(round.contributions, vec![5, 20]);
+        // nobody shows their hand when everybody else folded
+        assert_eq!(round.hands, vec![None, None]);
+        assert_eq!(round.winners(), vec![1]);
+        assert_eq!(game.players[0].points, 95);
+        assert_eq!(game.players[1].points, 105);
+    }
+
+    #[test]
+    fn first_to_act_moves_around_the_table() {
+        let mut game = new_table(3);
+        for player in game.players.iter_mut() {
+            player.strategy = Box::new(ConstantStrategy(0));
+        }
+        for number in 0..3 {
+            let round = game.play_round().unwrap();
+            assert_eq!(round.actions[0], (number, Action::Check));
+            assert_eq!(round.actions.len(), 3);
+            // everybody checked, so everybody shows their hand
+            assert!(round.hands.iter().all(|hand| hand.is_some()));
+            assert_eq!(round.pot(), 0);
+        }
+    }
+
+    #[test]
+    fn best_hand_wins_the_showdown() {
+        let mut game = new_table(4);
+        for player in game.players.iter_mut() {
+            player.strategy = Box::new(ConstantStrategy(10));
+        }
+        let round = game.play_round().unwrap();
+        let values: Vec<_> = round
+            .hands
+            .iter()
+            .map(|shown| hand::best_hand(shown.as_ref().unwrap()))
+            .collect();
+        assert!(round
+            .hands
+            .iter()
+            .flatten()
+            .all(|shown| shown.len() == HAND_SIZE));
+        let best = values.iter().max().unwrap();
+        for (seat, value) in round.seats.iter().zip(&values) {
+            assert_eq!(round.winners().contains(seat), value == best);
+        }
+    }
+
+    #[test]
+    fn short_stack_goes_all_in_for_the_main_pot() {
+        let mut game = new_table(3);
+        for player in game.players.iter_mut() {
+            player.strategy = Box::new(ConstantStrategy(1000));
+        }
+        game.players[2].points = 30;
+        let round = game.play_round().unwrap();
+        assert_eq!(round.contributions, vec![100, 100, 30]);
+        assert_eq!(round.pots[0].amount, 90);
+        assert_eq!(round.pots[0].eligible, vec![0, 1, 2]);
+        assert_eq!(round.pots[1].amount, 140);
+        assert_eq!(round.pots[1].eligible, vec![0, 1]);
+        // the short stack can only win the main pot
+        assert!(!round.pots[1].winners.contains(&2));
+        match round.pots[0].winners[..] {
+            [2] => assert_eq!(game.players[2].points, 90),
+            _ => assert!(game.players[2].points < 90),
+        }
+        assert_eq!(total_points(&game), 230);
+    }
+
+    #[test]
+    fn uncalled_chips_go_back_to_the_big_stack() {
+        let mut game = new_game();
+        for player in game.players.iter_mut() {
+            player.strategy = Box::new(ConstantStrategy(1000));
+        }
+        game.players[1].points = 40;
+        let round = game.play_round().unwrap();
+        assert_eq!(round.contributions, vec![100, 40]);
+        assert_eq!(round.pots[1].amount, 60);
+        assert_eq!(round.pots[1].winners, vec![0]);
+        assert_eq!(total_points(&game), 140);
+        assert!(game.players[0].points >= 60);
+    }
+
+    #[test]
+    fn holdem_deals_five_community_cards() {
+        let mut game = new_table(3).with_variant(Variant::Holdem);
+        for player in game.players.iter_mut() {
+            player.strategy = Box::new(ConstantStrategy(0));
+        }
+        let round = game.play_round().unwrap();
+        assert_eq!(round.community.len(), 5);
+        assert!(round
+            .hands
+            .iter()
+            .all(|hand| hand.as_ref().unwrap().len() == 2));
+        // a fresh commit-reveal on each street, and everybody checks on each street
+        assert_eq!(round.commitments.len(), 3 * 4);
+        assert_eq!(round.actions.len(), 3 * 4);
+        for commitment in round.commitments {
+            assert!(matches!(
+                game.board.check_commitment(commitment),
+                Ok(Some(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn folded_players_sit_out_the_later_streets() {
+        let mut game = new_table(3).with_variant(Variant::Holdem);
+        game.players[0].strategy = Box::new(ConstantStrategy(10));
+        game.players[1].strategy = Box::new(ConstantStrategy(10));
+        game.players[2].strategy = Box::new(ConstantStrategy(0));
+        let round = game.play_round().unwrap();
+        assert_eq!(
+            &round.actions[..3],
+            &[(0, Action::Bet(10)), (1, Action::Call), (2, Action::Fold)]
+        );
+        // only the two players still in commit on the flop, the turn and the river
+        assert_eq!(round.commitments.len(), 3 + 2 * 3);
+        assert_eq!(round.contributions, vec![40, 40, 0]);
+        assert_eq!(round.hands[2], None);
+        assert_eq!(total_points(&game), 300);
+    }
+
+    #[test]
+    fn holdem_game_runs_until_one_player_is_left() {
+        let mut game = new_table(4).with_variant(Variant::Holdem);
+        while !game.is_over() {
+            let round = game.play_round().unwrap();
+            assert!(round.community.is_empty() || round.community.len() >= 3);
+            assert_eq!(total_points(&game), 400);
+        }
+    }
+
+    #[test]
+    fn draw_replaces_the_discarded_cards() {
+        let mut game = new_table(3).with_variant(Variant::Draw);
+        for player in game.players.iter_mut() {
+            player.strategy = Box::new(ConstantStrategy(5));
+        }
+        let round = game.play_round().unwrap();
+        // everybody calls, so everybody discards in turn order and shows their hand
+        let seats: Vec<usize> = round.discards.iter().map(|(seat, _, _)| *seat).collect();
+        assert_eq!(seats, vec![0, 1, 2]);
+        for (seat, draw_round, slots) in &round.discards {
+            assert_eq!(*draw_round, 1);
+            let drawn_in = round.drawn_in[*seat].as_ref().unwrap();
+            assert_eq!(drawn_in.len(), HAND_SIZE);
+            for (slot, draw_round) in drawn_in.iter().enumerate() {
+                assert_eq!(*draw_round == 1, slots.contains(&slot));
+            }
+            // the hand the player kept is the one they showed
+            assert_eq!(round.hands[*seat].as_ref(), Some(&game.players[*seat].hand));
+        }
+        assert_eq!(round.contributions, vec![10, 10, 10]);
+    }
+
+    #[test]
+    fn finite_deck_deals_every_card_once() {
+        for variant in [Variant::FiveCard, Variant::Holdem, Variant::Draw] {
+            let mut game = new_table(4)
+                .with_variant(variant)
+                .with_dealing(Dealing::Deck);
+            for player in game.players.iter_mut() {
+                player.strategy = Box::new(ConstantStrategy(0));
+            }
+            let round = game.play_round().unwrap();
+            // the commit-reveal of the first street only gives the challenges of the shuffles
+            assert_eq!(round.commitments.len(), 4);
+            let mut cards: Vec<u8> = round
+                .hands
+                .iter()
+                .flat_map(|hand| hand.as_ref().unwrap())
+                .chain(&round.community)
+                .map(|card| card.index())
+                .collect();
+            let dealt = 4 * variant.hole_cards() + variant.community_cards().iter().sum::<usize>();
+            assert_eq!(cards.len(), dealt);
+            cards.sort_unstable();
+            cards.dedup();
+            assert_eq!(cards.len(), dealt);
+            // the players learned their own cards from the deck
+            for (seat, hand) in round.seats.iter().zip(&round.hands) {
+                assert_eq!(hand.as_ref(), Some(&game.players[*seat].hand));
+            }
+        }
+    }
+
+    #[test]
+    fn bad_shuffle_is_caught() {
+        let mut game = new_table(3).with_dealing(Dealing::Deck);
+        game.players[1].cheat = Some(Cheat::BadShuffle);
+        let evidence = game.play_round().unwrap_err();
+        assert!(matches!(
+            evidence,
+            CheatingEvidence::BadShuffle {
+                seat: 1,
+                round: 0,
+                ..
+            }
+        ));
+        // nobody was dealt a card from the tampered deck
+        assert!(game.players.iter().all(|player| player.hand.is_empty()));
+        assert_eq!(game.players[1].points, 0);
+        assert_eq!(total_points(&game), 300);
+    }
+
+    #[test]
+    fn bad_decryption_is_caught() {
+        let mut game = new_table(3).with_dealing(Dealing::Deck);
+        game.players[1].cheat = Some(Cheat::BadDecryption);
+        let evidence = game.play_round().unwrap_err();
+        // the first card the cheater decrypts is the first card of the first player
+        assert!(matches!(
+            evidence,
+            CheatingEvidence::BadDecryption {
+                seat: 1,
+                round: 0,
+                position: 0,
+                ..
+            }
+        ));
+        assert_eq!(game.players[1].points, 0);
+        assert_eq!(total_points(&game), 300);
+    }
+
+    #[test]
+    fn finite_deck_game_runs_until_one_player_is_left() {
+        let mut game = new_table(2)
+            .with_variant(Variant::Draw)
+            .with_dealing(Dealing::Deck);
+        while !game.is_over() {
+            game.play_round().unwrap();
+            assert_eq!(total_points(&game), 200);
+        }
+    }
+}
@@ -0,0 +1,307 @@
+//! Poker hands and their ranking.
+//!
+//! Hands are ranked with the standard poker categories, from high card to straight flush, with the
+//! ranks of the cards breaking ties within a category. The ace plays high, or low in the 5-high
+//! straight. Since every card is drawn from its own infinite deck, a hand can hold the same card
+//! twice, and even five cards of the same rank, which rank above a straight flush.
+//!
+//! Hands of fewer than five cards can be evaluated too, for instance the hole cards of hold'em
+//! before the flop. They can only make the categories that do not need five cards.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use strum::EnumIter;
+
+use super::card::{Card, Rank};
+
+/// The number of cards in a poker hand
+pub const HAND_SIZE: usize = 5;
+
+/// The probability of each category for five cards from the infinite deck, from high card to five
+/// of a kind
+const CATEGORY_PROBABILITIES: [f32; 10] = [
+    0.411_107_7,
+    0.460_363_3,
+    0.069_054_5,
+    0.046_036_3,
+    0.003_219_3,
+    0.003_868_9,
+    0.004_201_5,
+    0.002_100_8,
+    0.000_012_6,
+    0.000_035_0,
+];
+
+/// The category of a poker hand, from the weakest to the strongest
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, EnumIter)]
+pub enum Category {
+    HighCard,
+    Pair,
+    TwoPair,
+    ThreeOfAKind,
+    Straight,
+    Flush,
+    FullHouse,
+    FourOfAKind,
+    StraightFlush,
+    FiveOfAKind,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Category::HighCard => "high card",
+            Category::Pair => "pair",
+            Category::TwoPair => "two pair",
+            Category::ThreeOfAKind => "three of a kind",
+            Category::Straight => "straight",
+            Category::Flush => "flush",
+            Category::FullHouse => "full house",
+            Category::FourOfAKind => "four of a kind",
+            Category::StraightFlush => "straight flush",
+            Category::FiveOfAKind => "five of a kind",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The value of a hand. Hands compare by category first, then by the ranks that break ties.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HandValue {
+    pub category: Category,
+    /// The ranks that break ties within the category, most significant first: the ranks of the
+    /// groups from the largest group down, the highest card of a straight, or every card of a
+    /// flush or a high card hand
+    pub ranks: Vec<Rank>,
+}
+
+impl Ord for HandValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.category
+            .cmp(&other.category)
+            .then_with(|| self.ranks.cmp(&other.ranks))
+    }
+}
+
+impl PartialOrd for HandValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl HandValue {
+    /// Roughly the probability that this hand beats five random cards of the infinite deck,
+    /// counting a tie as half a win. It is exact between categories, and spreads each category
+    /// evenly over the rank of its most significant card.
+    pub fn strength(&self) -> f32 {
+        let category = self.category as usize;
+        let below: f32 = CATEGORY_PROBABILITIES[..category].iter().sum();
+        let top = self.ranks.first().map_or(0.0, |rank| rank.index() as f32);
+        below + CATEGORY_PROBABILITIES[category] * (top + 0.5) / 13.0
+    }
+}
+
+impl fmt::Display for HandValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.category)?;
+        for rank in &self.ranks {
+            write!(f, " {}", rank)?;
+        }
+        Ok(())
+    }
+}
+
+/// Evaluate a hand of at most five cards
+pub fn evaluate(cards: &[Card]) -> HandValue {
+    assert!(cards.len() <= HAND_SIZE, "a hand has at most five cards");
+
+    // group the cards by rank, largest groups first, then highest ranks first
+    let mut groups: Vec<(usize, Rank)> = Vec::new();
+    for card in cards {
+        match groups.iter_mut().find(|(_, rank)| *rank == card.rank) {
+            Some((count, _)) => *count += 1,
+            None => groups.push((1, card.rank)),
+        }
+    }
+    groups.sort_by(|a, b| b.cmp(a));
+    let counts: Vec<usize> = groups.iter().map(|(count, _)| *count).collect();
+    let group_ranks: Vec<Rank> = groups.iter().map(|(_, rank)| *rank).collect();
+
+    let mut all_ranks: Vec<Rank> = cards.iter().map(|card| card.rank).collect();
+    all_ranks.sort_by(|a, b| b.cmp(a));
+    let flush = cards.len() == HAND_SIZE && cards.iter().all(|card| card.suit == cards[0].suit);
+    let straight = straight_high(&group_ranks);
+
+    let (category, ranks) = match (counts.as_slice(), straight, flush) {
+        ([5], _, _) => (Category::FiveOfAKind, group_ranks),
+        (_, Some(high), true) => (Category::StraightFlush, vec![high]),
+        ([4, ..], _, _) => (Category::FourOfAKind, group_ranks),
+        ([3, 2], _, _) => (Category::FullHouse, group_ranks),
+        (_, _, true) => (Category::Flush, all_ranks),
+        (_, Some(high), _) => (Category::Straight, vec![high]),
+        ([3, ..], _, _) => (Category::ThreeOfAKind, group_ranks),
+        ([2, 2, ..], _, _) => (Category::TwoPair, group_ranks),
+        ([2, ..], _, _) => (Category::Pair, group_ranks),
+        _ => (Category::HighCard, all_ranks),
+    };
+    HandValue { category, ranks }
+}
+
+/// The highest card of the straight made by five distinct ranks, sorted from the highest, if they
+/// make one. The ace can also play low, in the 5-high straight.
+fn straight_high(ranks: &[Rank]) -> Option<Rank> {
+    if ranks.len() != HAND_SIZE {
+        return None;
+    }
+    if ranks[0].index() - ranks[4].index() == 4 {
+        return Some(ranks[0]);
+    }
+    let wheel = [Rank::Ace, Rank::Five, Rank::Four, Rank::Three, Rank::Two];
+    (ranks == wheel).then_some(Rank::Five)
+}
+
+/// The value of the best hand of at most five cards among some cards, like the two hole cards and
+/// five community cards of hold'em
+pub fn best_hand(cards: &[Card]) -> HandValue {
+    if cards.len() <= HAND_SIZE {
+        return evaluate(cards);
+    }
+    (0u32..1 << cards.len())
+        .filter(|subset| subset.count_ones() as usize == HAND_SIZE)
+        .map(|subset| {
+            let hand: Vec<Card> = (0..cards.len())
+                .filter(|i| subset & (1 << i) != 0)
+                .map(|i| cards[i])
+                .collect();
+            evaluate(&hand)
+        })
+        .max()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poker::card::Suit;
+
+    /// Parse a hand like "AS KS 10H 2C 2D"
+    fn hand(s: &str) -> Vec<Card> {
+        s.split(' ')
+            .map(|card| {
+                let (rank, suit) = card.split_at(card.len() - 1);
+                let rank = match rank {
+                    "J" => Rank::Jack,
+                    "Q" => Rank::Queen,
+                    "K" => Rank::King,
+                    "A" => Rank::Ace,
+                    n => Rank::from_index(n.parse::<u8>().unwrap() - 2).unwrap(),
+                };
+                let suit = match suit {
+                    "C" => Suit::Clubs,
+                    "D" => Suit::Diamonds,
+                    "H" => Suit::Hearts,
+                    _ => Suit::Spades,
+                };
+                Card { rank, suit }
+            })
+            .collect()
+    }
+
+    fn category(s: &str) -> Category {
+        evaluate(&hand(s)).category
+    }
+
+    #[test]
+    fn categories_are_recognized() {
+        assert_eq!(category("AS KS 10H 3C 2D"), Category::HighCard);
+        assert_eq!(category("AS KS 10H 2C 2D"), Category::Pair);
+        assert_eq!(category("AS KS KH 2C 2D"), Category::TwoPair);
+        assert_eq!(category("AS 2S 10H 2C 2D"), Category::ThreeOfAKind);
+        assert_eq!(category("6S 5S 4H 3C 2D"), Category::Straight);
+        assert_eq!(category("AS 5S 4H 3C 2D"), Category::Straight);
+        assert_eq!(category("AS KS 10S 3S 2S"), Category::Flush);
+        assert_eq!(category("KS KH KD 2C 2D"), Category::FullHouse);
+        assert_eq!(category("KS KH KD KC 2D"), Category::FourOfAKind);
+        assert_eq!(category("6S 5S 4S 3S 2S"), Category::StraightFlush);
+        assert_eq!(category("KS KH KD KC KC"), Category::FiveOfAKind);
+        // the ace does not wrap around
+        assert_eq!(category("3S 2S AH KC QD"), Category::HighCard);
+        // a flush with a pair is possible on the infinite deck
+        assert_eq!(category("AS AS 10S 3S 2S"), Category::Flush);
+    }
+
+    #[test]
+    fn categories_rank_in_order() {
+        let hands = [
+            "AS KS 10H 3C 2D",
+            "2S 2H 3C 4D 6S",
+            "2S 2H 3C 3D 4S",
+            "2S 2H 2C 3D 4S",
+            "AS 5S 4H 3C 2D",
+            "7S 5S 4S 3S 2S",
+            "2S 2H 2D 3C 3D",
+            "2S 2H 2D 2C 3D",
+            "AS 5S 4S 3S 2S",
+            "2S 2H 2D 2C 2D",
+        ];
+        for pair in hands.windows(2) {
+            assert!(evaluate(&hand(pair[0])) < evaluate(&hand(pair[1])));
+        }
+    }
+
+    #[test]
+    fn kickers_break_ties() {
+        // the pair comes before the kickers
+        assert!(evaluate(&hand("3S 3H AC KD QS")) < evaluate(&hand("4S 4H 2C 3D 5S")));
+        // then the kickers, from the highest
+        assert!(evaluate(&hand("AS AH KC QD 2S")) < evaluate(&hand("AD AC KH QS 3H")));
+        // the higher pair of two pairs first, then the lower pair, then the kicker
+        assert!(evaluate(&hand("KS KH 2C 2D AS")) < evaluate(&hand("KD KC 3H 3S 4S")));
+        assert!(evaluate(&hand("KS KH 3C 3D 4S")) < evaluate(&hand("KD KC 3H 3S 5S")));
+        // the three of a kind of a full house first
+        assert!(evaluate(&hand("2S 2H 2C AD AS")) < evaluate(&hand("3S 3H 3C 2D 2S")));
+        // the 5-high straight is the lowest
+        assert!(evaluate(&hand("AS 5S 4H 3C 2D")) < evaluate(&hand("6S 5S 4H 3C 2D")));
+        // suits do not break ties
+        assert_eq!(
+            evaluate(&hand("AS KS 10H 3C 2D")),
+            evaluate(&hand("AD KD 10C 3S 2H"))
+        );
+    }
+
+    #[test]
+    fn best_hand_picks_five_of_seven() {
+        let value = best_hand(&hand("AS KS 10S 3S 2H 2S 2D"));
+        assert_eq!(value.category, Category::Flush);
+        assert_eq!(
+            value.ranks,
+            vec![Rank::Ace, Rank::King, Rank::Ten, Rank::Three, Rank::Two]
+        );
+        let value = best_hand(&hand("AS KS QH JC 10D 9S 9H"));
+        assert_eq!(value.category, Category::Straight);
+        assert_eq!(value.ranks, vec![Rank::Ace]);
+    }
+
+    #[test]
+    fn short_hands_are_evaluated() {
+        assert_eq!(category("AS AH"), Category::Pair);
+        assert_eq!(category("AS KS"), Category::HighCard);
+        assert!(evaluate(&hand("AS KS")) < evaluate(&hand("2S 2H")));
+    }
+
+    #[test]
+    fn strength_grows_with_the_hand() {
+        let hands = [
+            "7S 5H 4C 3D 2S",
+            "AS KS 10H 3C 2D",
+            "2S 2H 3C 4D 6S",
+            "2S 2H 2D 2C 3D",
+        ];
+        for pair in hands.windows(2) {
+            assert!(evaluate(&hand(pair[0])).strength() < evaluate(&hand(pair[1])).strength());
+        }
+        let total: f32 = CATEGORY_PROBABILITIES.iter().sum();
+        assert!((total - 1.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,231 @@
+//! Player identities.
+//!
+//! A player's identity is created once per game, from a mnemonic they supply, from a fresh key
+//! generated with `OsRng`, or from a keystore file saved by an earlier game, and is then used for
+//! every round. Players are identified by the SS58 address of their public key, which is the same
+//! as the sr25519 address of the mnemonic in any Substrate wallet.
+//!
+//! Players never sign or draw cards with their master key. For every game they derive a child key
+//! on the soft path `/poker/<game_id>`, the same as the Substrate secret URI
+//! `<mnemonic>/poker/<id>`, so a VRF output or a signature from one table can't be replayed at
+//! another. The keys of different games are not unlinkable: the derivation is soft, so anyone
+//! holding a player's master public key, which the SS58 address printed for them reveals, can
+//! derive the public key they use in any game and tie their games together.
+//!
+//! A keystore file holds the secret of a single identity as one line of text: the mnemonic, or
+//! the hex encoded mini secret key for generated keys. It is not encrypted, so it should be kept
+//! private.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use rand_core::OsRng;
+use schnorrkel::{
+    derive::{ChainCode, Derivation},
+    ExpansionMode, Keypair, MiniSecretKey, PublicKey,
+};
+use sp_core::crypto::{DeriveJunction, Ss58Codec};
+use sp_core::sr25519::{Pair as Sr25519Pair, Public as Sr25519Public};
+use sp_core::Pair;
+
+/// Where a player's identity comes from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeySource {
+    /// A bip39 mnemonic supplied by the player
+    Mnemonic(String),
+    /// A fresh key generated with the OS random number generator
+    Random,
+    /// A keystore file saved by an earlier game
+    Keystore(PathBuf),
+}
+
+/// A player's keypair, together with the secret it was created from so it can be stored
+pub struct Identity {
+    pub keypair: Keypair,
+    /// The mnemonic, or the hex encoded mini secret key prefixed with 0x
+    secret: String,
+}
+
+impl Identity {
+    /// Create an identity from a source. Errors if the mnemonic or the keystore file is invalid.
+    pub fn from_source(source: &KeySource) -> Result<Self, ()> {
+        match source {
+            KeySource::Mnemonic(phrase) => Self::from_mnemonic(phrase),
+            KeySource::Random => Ok(Self::generate()),
+            KeySource::Keystore(path) => Self::load(path),
+        }
+    }
+
+    /// Create the identity of a bip39 mnemonic. Errors if the mnemonic is invalid.
+    ///
+    /// The keypair is expanded from the mini secret key of the mnemonic the same way sp_core does
+    /// for sr25519, so it has the same public key (and SS58 address) as `Sr25519Pair::from_phrase`.
+    pub fn from_mnemonic(phrase: &str) -> Result<Self, ()> {
+        let seed = Sr25519Pair::from_phrase(phrase, None).map_err(|_| ())?.1;
+        Ok(Identity {
+            keypair: expand(seed.as_ref())?,
+            secret: phrase.to_string(),
+        })
+    }
+
+    /// Generate a fresh identity with the OS random number generator
+    pub fn generate() -> Self {
+        let mini_secret = MiniSecretKey::generate_with(OsRng);
+        Identity {
+            keypair: mini_secret.expand_to_keypair(ExpansionMode::Ed25519),
+            secret: format!("0x{}", hex::encode(mini_secret.to_bytes())),
+        }
+    }
+
+    /// Read an identity from a keystore file. Errors if the file can't be read or does not hold a
+    /// mnemonic or a mini secret key.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ()> {
+        let contents = fs::read_to_string(path).map_err(|_| ())?;
+        let secret = contents.trim();
+        match secret.strip_prefix("0x") {
+            Some(seed) => {
+                let mut bytes = [0u8; 32];
+                hex::decode_to_slice(seed, &mut bytes).map_err(|_| ())?;
+                Ok(Identity {
+                    keypair: expand(&bytes)?,
+                    secret: secret.to_string(),
+                })
+            }
+            None => Self::from_mnemonic(secret),
+        }
+    }
+
+    /// Write the identity to a keystore file, readable only by its owner on unix
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        writeln!(options.open(path)?, "{}", self.secret)
+    }
+
+    /// The public key of the identity
+    pub fn public(&self) -> PublicKey {
+        self.keypair.public
+    }
+
+    /// The SS58 address of the identity
+    pub fn address(&self) -> String {
+        ss58_address(&self.keypair.public)
+    }
+}
+
+/// Expand a mini secret key into a keypair the same way sp_core does for sr25519
+fn expand(mini_secret: &[u8]) -> Result<Keypair, ()> {
+    Ok(MiniSecretKey::from_bytes(mini_secret)
+        .map_err(|_| ())?
+        .expand_to_keypair(ExpansionMode::Ed25519))
+}
+
+/// The soft derivation path of the child key of a game, `/poker/<game_id>`
+pub fn game_path(game_id: u64) -> [DeriveJunction; 2] {
+    [DeriveJunction::soft("poker"), DeriveJunction::soft(game_id)]
+}
+
+/// Derive the child keypair a player uses in a game from their master keypair
+pub fn derive_game_keypair(master: &Keypair, game_id: u64) -> Keypair {
+    game_path(game_id)
+        .into_iter()
+        .fold(master.clone(), |key, junction| {
+            key.derived_key_simple(ChainCode(junction.unwrap_inner()), [])
+                .0
+        })
+}
+
+/// Derive the public key a player uses in a game from their master public key
+pub fn derive_game_public(master: &PublicKey, game_id: u64) -> PublicKey {
+    game_path(game_id)
+        .into_iter()
+        .fold(*master, |key, junction| {
+            key.derived_key_simple(ChainCode(junction.unwrap_inner()), [])
+                .0
+        })
+}
+
+/// Check that a public key is the one the owner of a master public key uses in a game
+pub fn is_game_public(master: &PublicKey, game_id: u64, public: &PublicKey) -> bool {
+    derive_game_public(master, game_id) == *public
+}
+
+/// The SS58 address of a public key, with the generic Substrate prefix
+pub fn ss58_address(public: &PublicKey) -> String {
+    Sr25519Public::from_raw(public.to_bytes()).to_ss58check()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: &str =
+        "lunar antique thank school space garden soda cigar glove despair master pumpkin";
+
+    fn keystore_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("poker-{}-{}.key", name, std::process::id()))
+    }
+
+    #[test]
+    fn mnemonic_identity_has_the_sr25519_address() {
+        let identity = Identity::from_mnemonic(SEED).unwrap();
+        let sr25519 = Sr25519Pair::from_phrase(SEED, None).unwrap().0;
+        assert_eq!(identity.address(), sr25519.public().to_ss58check());
+        assert!(Identity::from_mnemonic("not a mnemonic").is_err());
+    }
+
+    #[test]
+    fn generated_identities_are_different() {
+        let identity_1 = Identity::generate();
+        let identity_2 = Identity::generate();
+        assert_ne!(identity_1.address(), identity_2.address());
+    }
+
+    #[test]
+    fn identities_survive_the_keystore() {
+        for identity in [Identity::from_mnemonic(SEED).unwrap(), Identity::generate()] {
+            let path = keystore_path(&identity.address());
+            identity.save(&path).unwrap();
+            let loaded = Identity::from_source(&KeySource::Keystore(path.clone())).unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_eq!(loaded.public(), identity.public());
+            assert_eq!(loaded.address(), identity.address());
+        }
+    }
+
+    #[test]
+    fn game_key_matches_the_substrate_derivation() {
+        let identity = Identity::from_mnemonic(SEED).unwrap();
+        let derived = derive_game_keypair(&identity.keypair, 7);
+        let sr25519 = Sr25519Pair::from_string(&format!("{}/poker/7", SEED), None).unwrap();
+        assert_eq!(derived.public.to_bytes(), sr25519.public().0);
+        assert!(is_game_public(&identity.public(), 7, &derived.public));
+    }
+
+    #[test]
+    fn game_keys_are_different_for_every_game() {
+        let identity = Identity::generate();
+        let game_1 = derive_game_keypair(&identity.keypair, 1);
+        let game_2 = derive_game_keypair(&identity.keypair, 2);
+        assert_ne!(game_1.public, game_2.public);
+        assert_ne!(game_1.public, identity.public());
+        assert!(!is_game_public(&identity.public(), 2, &game_1.public));
+        assert!(!is_game_public(
+            &Identity::generate().public(),
+            1,
+            &game_1.public
+        ));
+    }
+
+    #[test]
+    fn bad_keystore_is_rejected() {
+        let path = keystore_path("bad");
+        fs::write(&path, "0x1234").unwrap();
+        assert!(Identity::load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+        assert!(Identity::load(&path).is_err());
+    }
+}
@@ -0,0 +1,290 @@
+//! The chip ledger: the stacks of the players, and the pots every round is paid out from.
+//!
+//! Every player can put in as much as their whole stack. A player who is all-in can't win more
+//! from each other player than what they put in themselves, so the pot of a round is split into
+//! layers, one for each amount a player still in went all-in for:
+//!
+//! 1. The main pot holds everybody's contributions up to the smallest all-in, and every player
+//!    still in can win it.
+//! 2. Each side pot holds the contributions between one all-in and the next, and only the players
+//!    who put in at least as much can win it.
+//!
+//! The contributions of the players who folded go to the layers they reach, but they can't win
+//! any. Every pot is won by the best hand among the players who can win it, so a short stack can
+//! win the main pot while somebody else wins a side pot.
+//!
+//! The ledger pays out every round from these pots, and checks that no chip was ever created or
+//! lost. It also holds the deposits the players put aside when they sat down: a player who misses
+//! a deadline forfeits theirs to the other players of the round with their stack (see
+//! `deadline`).
+
+use super::cheating::CheatingEvidence;
+use super::round::Round;
+
+/// A pot of a round, with the players who could win it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pot {
+    /// The chips in the pot
+    pub amount: u32,
+    /// The seats of the players still in who put in enough to win the pot
+    pub eligible: Vec<usize>,
+    /// The seats of the players who won the pot and split it, among the eligible players
+    pub winners: Vec<usize>,
+}
+
+/// Build the main pot and the side pots of a round from what the players in some seats put in,
+/// in the same order, and the seats of the players still in. The winners are left for the
+/// showdown to find.
+pub fn pots(seats: &[usize], contributions: &[u32], remaining: &[usize]) -> Vec<Pot> {
+    let still_in = |i: &usize| remaining.contains(&seats[*i]);
+    let mut levels: Vec<u32> = (0..seats.len())
+        .filter(still_in)
+        .map(|i| contributions[i])
+        .collect();
+    levels.sort_unstable();
+    levels.dedup();
+
+    let mut pots: Vec<Pot> = Vec::new();
+    let mut previous = 0;
+    for level in levels {
+        let amount = contributions
+            .iter()
+            .map(|contribution| (*contribution).min(level) - (*contribution).min(previous))
+            .sum();
+        let eligible = (0..seats.len())
+            .filter(|i| still_in(i) && contributions[*i] >= level)
+            .map(|i| seats[i])
+            .collect();
+        pots.push(Pot {
+            amount,
+            eligible,
+            winners: Vec::new(),
+        });
+        previous = level;
+    }
+    // whatever a folded player put in above everybody still in goes to the last pot
+    let above: u32 = contributions
+        .iter()
+        .map(|contribution| contribution.saturating_sub(previous))
+        .sum();
+    if let Some(last) = pots.last_mut() {
+        last.amount += above;
+    }
+    // a layer nobody put anything in is not a pot
+    pots.retain(|pot| pot.amount > 0);
+    pots
+}
+
+/// The stacks and the deposits of the players of a game, by seat
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ledger {
+    stacks: Vec<u32>,
+    deposits: Vec<u32>,
+    /// The chips in play, deposits included, which never change
+    total: u32,
+}
+
+impl Ledger {
+    /// Open a ledger with the stacks of the players, by seat, and no deposits
+    pub fn new(stacks: Vec<u32>) -> Self {
+        let total = stacks.iter().sum();
+        let deposits = vec![0; stacks.len()];
+        Ledger {
+            stacks,
+            deposits,
+            total,
+        }
+    }
+
+    /// Hold the deposits of the players too, by seat
+    pub fn with_deposits(mut self, deposits: Vec<u32>) -> Self {
+        self.total = self.stacks.iter().sum::<u32>() + deposits.iter().sum::<u32>();
+        self.deposits = deposits;
+        self
+    }
+
+    /// The stacks of the players, by seat
+    pub fn stacks(&self) -> &[u32] {
+        &self.stacks
+    }
+
+    /// The deposits of the players, by seat
+    pub fn deposits(&self) -> &[u32] {
+        &self.deposits
+    }
+
+    /// Pay out a round between the players in some seats: take what every player put in and pay
+    /// every pot to its winners, or take all the chips of a cheater and split them among the other
+    /// players of the round. A player who missed a deadline forfeits their deposit and the rest of
+    /// their stack to the other players of the round too.
+    ///
+    /// Panics if the chips in play changed, which would be a bug of the game.
+    pub fn settle(&mut self, seats: &[usize], result: &Result<Round, CheatingEvidence>) {
+        match result {
+            Ok(round) => {
+                for (seat, contribution) in round.seats.iter().zip(&round.contributions) {
+                    self.stacks[*seat] -= contribution;
+                }
+                for pot in &round.pots {
+                    for (seat, share) in
+                        pot.winners.iter().zip(split(pot.amount, pot.winners.len()))
+                    {
+                        self.stacks[*seat] += share;
+                    }
+                }
+                // stalling costs as much as cheating: the staller forfeits their whole stack too
+                if let Some(missed) = round.missed {
+                    let forfeited = std::mem::take(&mut self.deposits[missed.seat])
+                        + std::mem::take(&mut self.stacks[missed.seat]);
+                    self.share(seats, missed.seat, forfeited);
+                }
+            }
+            Err(evidence) => {
+                let cheater = evidence.seat();
+                let forfeited = std::mem::take(&mut self.stacks[cheater]);
+                self.share(seats, cheater, forfeited);
+            }
+        }
+        assert_eq!(
+            self.stacks.iter().sum::<u32>() + self.deposits.iter().sum::<u32>(),
+            self.total,
+            "chips were created or lost"
+        );
+    }
+
+    /// Split the points a player forfeited among the other players in some seats
+    fn share(&mut self, seats: &[usize], forfeiter: usize, forfeited: u32) {
+        let others: Vec<usize> = seats.iter().copied().filter(|s| *s != forfeiter).collect();
+        for (seat, share) in others.iter().zip(split(forfeited, others.len())) {
+            self.stacks[*seat] += share;
+        }
+    }
+}
+
+/// Split some points evenly into shares for some players. If they can't be split evenly, the
+/// remaining points go to the first players.
+pub fn split(points: u32, players: usize) -> Vec<u32> {
+    let share = points / players as u32;
+    let remainder = points % players as u32;
+    (0..players as u32)
+        .map(|i| share + u32::from(i < remainder))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A round that went to the showdown, where the players of the first seats put in some points
+    fn showdown(contributions: Vec<u32>, pots: Vec<Pot>) -> Round {
+        let players = contributions.len();
+        Round {
+            number: 0,
+            seats: (0..players).collect(),
+            button: players - 1,
+            blinds: Default::default(),
+            commitments: Vec::new(),
+            common_random: [0; 32],
+            draw_randoms: Vec::new(),
+            community: Vec::new(),
+            discards: Vec::new(),
+            actions: Vec::new(),
+            contributions,
+            hands: vec![None; players],
+            drawn_in: vec![None; players],
+            pots,
+            missed: None,
+        }
+    }
+
+    fn amounts(pots: &[Pot]) -> Vec<(u32, Vec<usize>)> {
+        pots.iter()
+            .map(|pot| (pot.amount, pot.eligible.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn points_are_split_evenly() {
+        assert_eq!(split(10, 3), vec![4, 3, 3]);
+        assert_eq!(split(9, 3), vec![3, 3, 3]);
+    }
+
+    #[test]
+    fn equal_contributions_make_a_single_pot() {
+        let pots = pots(&[0, 1, 2], &[20, 20, 20], &[0, 1, 2]);
+        assert_eq!(amounts(&pots), vec![(60, vec![0, 1, 2])]);
+        // nobody put anything in
+        assert!(super::pots(&[0, 1], &[0, 0], &[0, 1]).is_empty());
+    }
+
+    #[test]
+    fn all_ins_make_side_pots() {
+        // seat 3 went all-in for 10 and seat 1 for 40, seat 2 folded after putting in 30
+        let pots = pots(&[0, 1, 2, 3], &[100, 40, 30, 10], &[0, 1, 3]);
+        assert_eq!(
+            amounts(&pots),
+            vec![(40, vec![0, 1, 3]), (80, vec![0, 1]), (60, vec![0])]
+        );
+        assert_eq!(pots.iter().map(|pot| pot.amount).sum::<u32>(), 180);
+    }
+
+    #[test]
+    fn folded_chips_above_everybody_go_to_the_last_pot() {
+        let pots = pots(&[0, 1, 2], &[50, 20, 20], &[1, 2]);
+        assert_eq!(amounts(&pots), vec![(90, vec![1, 2])]);
+    }
+
+    #[test]
+    fn every_pot_is_paid_to_its_winners() {
+        let mut ledger = Ledger::new(vec![100, 40, 10]);
+        let mut pots = pots(&[0, 1, 2], &[100, 40, 10], &[0, 1, 2]);
+        // the short stack wins the main pot, the middle stack the side pot, and the big stack
+        // only gets back what nobody could call
+        pots[0].winners = vec![2];
+        pots[1].winners = vec![1];
+        pots[2].winners = vec![0];
+        let round = Round {
+            number: 0,
+            seats: vec![0, 1, 2],
+            button: 2,
+            blinds: Default::default(),
+            commitments: Vec::new(),
+            common_random: [0; 32],
+            draw_randoms: Vec::new(),
+            community: Vec::new(),
+            discards: Vec::new(),
+            actions: Vec::new(),
+            contributions: vec![100, 40, 10],
+            hands: vec![None; 3],
+            drawn_in: vec![None; 3],
+            pots,
+            missed: None,
+        };
+        ledger.settle(&[0, 1, 2], &Ok(round));
+        assert_eq!(ledger.stacks(), &[60, 60, 30]);
+    }
+
+    #[test]
+    fn tied_winners_split_the_pot() {
+        let mut ledger = Ledger::new(vec![100, 100, 100]);
+        let mut pots = pots(&[0, 1, 2], &[5, 5, 5], &[0, 1, 2]);
+        // two players tie, and neither of them takes the pot alone
+        pots[0].winners = vec![0, 1];
+        ledger.settle(&[0, 1, 2], &Ok(showdown(vec![5, 5, 5], pots)));
+        // the odd point goes to the first of them
+        assert_eq!(ledger.stacks(), &[103, 102, 95]);
+    }
+
+    #[test]
+    fn loser_pays_only_what_the_winner_matched() {
+        // the winner went all-in for 10 and the loser bet 40: the loser pays the 10 that were
+        // matched, not their whole bet, and takes back the 30 nobody called
+        let mut ledger = Ledger::new(vec![10, 100]);
+        let mut pots = pots(&[0, 1], &[10, 40], &[0, 1]);
+        assert_eq!(amounts(&pots), vec![(20, vec![0, 1]), (30, vec![1])]);
+        pots[0].winners = vec![0];
+        pots[1].winners = vec![1];
+        ledger.settle(&[0, 1], &Ok(showdown(vec![10, 40], pots)));
+        assert_eq!(ledger.stacks(), &[20, 90]);
+    }
+}
@@ -0,0 +1,56 @@
+//! Verifiable poker, from an infinite deck drawn with VRFs or from a shuffled finite deck.
+//!
+//! This module holds the game engine: the players with their keys and points, and the game that
+//! runs rounds of commit-reveal, card drawing and betting between them, in five-card poker,
+//! hold'em or five-card draw, from the infinite deck or from a finite deck. The binary in
+//! `main.rs` is just a thin driver on top of it, so the same engine can be used from tests, bots
+//! or a UI, `net` plays it between players in separate processes over TCP, and `simulation` plays
+//! it many times over to compare betting strategies.
+//!
+//! The engine comes from a loop in `main.rs` where the player with the lower card paid their own
+//! bet to the other, and player 1 won a tie. Two rules replaced that payout, each tested where it
+//! is made. Tied hands split the pots they win, since games of more than two players have no
+//! player 1 to break a tie (see `ledger::split`). And every round is settled from what the players
+//! put in during its betting rounds, so a loser never pays more than the winner matched (see
+//! `betting` and `ledger::pots`). A player caught cheating stops the round with the
+//! `CheatingEvidence` against them instead of a panic.
+
+pub mod betting;
+pub mod card;
+pub mod channel;
+pub mod cheating;
+pub mod commit;
+pub mod deadline;
+pub mod deck;
+pub mod game;
+pub mod hand;
+pub mod keys;
+pub mod ledger;
+pub mod net;
+pub mod player;
+pub mod round;
+pub mod shuffle;
+pub mod simulation;
+pub mod strategy;
+pub mod tournament;
+pub mod transcript;
+pub mod vrf;
+
+pub use betting::{Action, BettingRound, BettingView, Blinds};
+pub use card::{Card, Rank, Suit};
+pub use channel::{Channel, Handshake};
+pub use cheating::{Cheat, CheatingEvidence};
+pub use deadline::{Deadlines, MissedDeadline, Phase};
+pub use game::Game;
+pub use hand::{best_hand, Category, HandValue};
+pub use keys::{Identity, KeySource};
+pub use ledger::{Ledger, Pot};
+pub use net::{Message, NetError, Peer};
+pub use player::Player;
+pub use round::{Dealing, Round, Variant};
+pub use simulation::{Report, Simulation, StrategyKind};
+pub use strategy::{
+    BettingStrategy, ConstantStrategy, KellyStrategy, LinearStrategy, RandomBluffStrategy,
+};
+pub use tournament::{verify_tournament, Schedule, Standing, Tournament};
+pub use transcript::{verify_transcript, Transcript, TranscriptError};
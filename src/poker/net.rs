@@ -0,0 +1,1508 @@
+//! Networked play over TCP.
+//!
+//! Every player runs their own peer, in their own process, and the peers exchange the signed
+//! messages of the game over TCP. One peer hosts the game and sits in seat 0: it accepts the
+//! connections of the others, which sit in the order they connected, and relays every message a
+//! player sends to all the other players. Every peer then runs the very same `round::play` as the
+//! local game, with its own player answering for their seat and the messages of the other seats
+//! read from the network, so each peer checks every signature, reveal and VRF proof itself and
+//! keeps its own copy of the transcript, which `verify_transcript` accepts.
+//!
+//! The wire protocol is a sequence of frames, each one a message:
+//!
+//! 1. The length of the rest of the frame, as a big-endian u32.
+//! 2. The version of the protocol, a single byte, so peers that don't speak the same version
+//!    refuse each other's messages instead of misreading them.
+//! 3. The kind of the message, a single byte.
+//! 4. The body of the message, as UTF-8 text. Every message of a round carries a record of the
+//!    transcript, in the same line format as the transcript file, with its signature. A `Commit`
+//!    carries every commitment the player posted for the street, one per line, so a double
+//!    commitment reaches the other peers whole and they catch it like the local game does.
+//!
+//! A client says `Hello` with the game it wants to join and the public key and points of its
+//! player, and the host answers once every seat is taken with a `Hello` listing every player by
+//! seat. Both sides refuse a player who does not start with the stack of the table. Every player
+//! then signs a `Join` of the header of the transcript, so nobody can claim later that they agreed
+//! to other starting points. After every round each peer sends the points it computed in a
+//! `Result` signed by its player, and the game stops with an error as soon as two peers disagree.
+//!
+//! The rounds themselves only send public messages. Players who want to talk privately, between
+//! rounds, have every peer call `Peer::open_channels` at the same point: every two players then
+//! open an encrypted channel with a `Handshake` each (see `channel`), which the host relays to
+//! the other player only, so they can send each other `Private` messages that nobody else reads,
+//! the host included.
+//!
+//! The host keeps the deadlines of the game: it waits for the message of a player in a phase with
+//! a deadline at most as many ticks of `TICK` as the deadline of the phase, or until they drop
+//! their connection, and then tells everybody with a `Missed` that they left the table. Every
+//! other player of the round then signs a `Timeout` at the same tick of the clock of the game, and
+//! the player who missed the deadline forfeits like in the local game. The host is trusted to keep
+//! the game going: a client that hears nothing from the host for a few deadlines ends the game
+//! with an error, and so does a player who stops answering where there is no deadline.
+//!
+//! Only the infinite deck is dealt over the network, in five-card poker and hold'em, without
+//! blinds.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use curve25519_dalek::ristretto::RistrettoPoint;
+use schnorrkel::{PublicKey, Signature};
+
+use super::betting::{Action, BettingRound, Blinds};
+use super::card::Card;
+use super::channel::{self, Channel, Handshake};
+use super::cheating::CheatingEvidence;
+use super::deadline::{Clock, Deadlines, Phase};
+use super::deck::DecryptionProof;
+use super::game::{MAX_PLAYERS, MIN_PLAYERS};
+use super::ledger::Ledger;
+use super::player::Player;
+use super::round::{self, Dealing, Messages, Round, Seating, Variant};
+use super::shuffle::{Opening, Shuffle};
+use super::transcript::{Entry, Record, Replay, Transcript, TranscriptError};
+use super::vrf::CardDraw;
+use crate::p1_hashing::{HashValue, PublicMessageBoard};
+
+/// The version of the wire protocol
+pub const PROTOCOL_VERSION: u8 = 5;
+
+/// The largest frame a peer accepts, so a bad length can't make it allocate without bounds
+pub const MAX_FRAME: u32 = 1 << 20;
+
+/// How long a tick of the deadlines lasts over the network, unless the peer says otherwise
+pub const TICK: Duration = Duration::from_millis(100);
+
+/// How many times the deadline of a phase a client waits for the host, which decides who missed
+/// it, before it gives up on the host itself
+const CLIENT_PATIENCE: u32 = 3;
+
+/// A message of the wire protocol
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// The game a peer wants to play and the players it knows of, with their public key for the
+    /// game and their points: only its own player from a client, every player by seat from the
+    /// host
+    Hello {
+        game_id: u64,
+        variant: Variant,
+        players: Vec<(PublicKey, u32)>,
+    },
+    /// A player joined the game of the header of the transcript
+    Join(Record),
+    /// A player posted their commitments to their random value for a street, all in one message
+    /// so that a second commitment of a double committer arrives with the first
+    Commit(Vec<Record>),
+    /// A player revealed their random value for a street
+    Reveal(Record),
+    /// A player sent the VRF output and proof of a card to another player at the showdown
+    VrfReveal(Record),
+    /// A player checked, bet, called or raised
+    Bet(Record),
+    /// A player folded
+    Fold(Record),
+    /// The points of every player after a round, as computed and signed by the player of the
+    /// peer who sends it
+    Result(Record),
+    /// The player in a seat opens a channel with the player in another
+    Handshake {
+        from: usize,
+        to: usize,
+        handshake: Handshake,
+    },
+    /// The player in a seat sends a message sealed in their channel to the player in another
+    Private {
+        from: usize,
+        to: usize,
+        sealed: Vec<u8>,
+    },
+    /// The host saw the player in a seat miss the deadline of a phase of a round
+    Missed {
+        round: u32,
+        seat: usize,
+        phase: Phase,
+    },
+    /// A player witnessed that another one missed a deadline
+    Timeout(Record),
+}
+
+impl Message {
+    /// The message that carries a signed record of the transcript. Errors if no message carries
+    /// its entry.
+    pub fn signed(record: Record) -> Result<Self, ()> {
+        match record.entry {
+            Entry::Join { .. } => Ok(Message::Join(record)),
+            Entry::Commitment { .. } => Ok(Message::Commit(vec![record])),
+            Entry::Reveal { .. } => Ok(Message::Reveal(record)),
+            Entry::Draw { .. } => Ok(Message::VrfReveal(record)),
+            Entry::Action {
+                action: Action::Fold,
+                ..
+            } => Ok(Message::Fold(record)),
+            Entry::Action { .. } => Ok(Message::Bet(record)),
+            Entry::Timeout { .. } => Ok(Message::Timeout(record)),
+            _ => Err(()),
+        }
+    }
+
+    /// The kind of the message, as written in its frame
+    pub fn kind(&self) -> u8 {
+        match self {
+            Message::Hello { .. } => 0,
+            Message::Commit(_) => 1,
+            Message::Reveal(_) => 2,
+            Message::VrfReveal(_) => 3,
+            Message::Bet(_) => 4,
+            Message::Fold(_) => 5,
+            Message::Result(_) => 6,
+            Message::Handshake { .. } => 7,
+            Message::Private { .. } => 8,
+            Message::Join(_) => 9,
+            Message::Missed { .. } => 10,
+            Message::Timeout(_) => 11,
+        }
+    }
+
+    /// The seat of the only player the message is for, if it is not for everybody
+    pub fn to(&self) -> Option<usize> {
+        match self {
+            Message::Handshake { to, .. } | Message::Private { to, .. } => Some(*to),
+            _ => None,
+        }
+    }
+
+    /// The body of the message, as written in its frame
+    fn body(&self) -> String {
+        match self {
+            Message::Hello {
+                game_id,
+                variant,
+                players,
+            } => {
+                let mut lines = vec![
+                    format!("game {}", game_id),
+                    format!("variant {}", variant.name()),
+                ];
+                for (public, points) in players {
+                    lines.push(format!(
+                        "player {} {}",
+                        hex::encode(public.to_bytes()),
+                        points
+                    ));
+                }
+                lines.join("\n")
+            }
+            Message::Join(record)
+            | Message::Reveal(record)
+            | Message::VrfReveal(record)
+            | Message::Bet(record)
+            | Message::Fold(record)
+            | Message::Result(record)
+            | Message::Timeout(record) => record.to_line(),
+            Message::Commit(records) => {
+                let lines: Vec<String> = records.iter().map(|record| record.to_line()).collect();
+                lines.join("\n")
+            }
+            Message::Handshake {
+                from,
+                to,
+                handshake,
+            } => format!(
+                "{} {} {} {}",
+                from,
+                to,
+                hex::encode(handshake.ephemeral),
+                hex::encode(handshake.signature.to_bytes())
+            ),
+            Message::Private { from, to, sealed } => {
+                format!("{} {} {}", from, to, hex::encode(sealed))
+            }
+            Message::Missed { round, seat, phase } => format!("{} {} {}", round, seat, phase),
+        }
+    }
+
+    /// Parse a message from its kind and its body. Errors if the body is malformed or is not of
+    /// the kind.
+    fn from_body(kind: u8, body: &str) -> Result<Self, ()> {
+        let message = match kind {
+            0 => {
+                let mut lines = body.lines();
+                let mut field = |name: &str| {
+                    lines
+                        .next()
+                        .and_then(|line| line.strip_prefix(name))
+                        .ok_or(())
+                };
+                let game_id = field("game ")?.parse().map_err(|_| ())?;
+                let variant = Variant::from_name(field("variant ")?)?;
+                let players = lines
+                    .map(|line| {
+                        let player = line.strip_prefix("player ").ok_or(())?;
+                        let (public, points) = player.split_once(' ').ok_or(())?;
+                        let public = PublicKey::from_bytes(&hex::decode(public).map_err(|_| ())?)
+                            .map_err(|_| ())?;
+                        Ok((public, points.parse().map_err(|_| ())?))
+                    })
+                    .collect::<Result<_, ()>>()?;
+                Message::Hello {
+                    game_id,
+                    variant,
+                    players,
+                }
+            }
+            // one commitment, or two from a double committer
+            1 => {
+                let records: Vec<Record> = body
+                    .lines()
+                    .map(Record::from_line)
+                    .collect::<Result<_, ()>>()?;
+                let commitments = records
+                    .iter()
+                    .all(|record| matches!(record.entry, Entry::Commitment { .. }));
+                match commitments && (1..=2).contains(&records.len()) {
+                    true => Message::Commit(records),
+                    false => return Err(()),
+                }
+            }
+            // the points are recorded unsigned in the transcript, but not sent unsigned
+            6 => {
+                let (points, signature) = body.rsplit_once(' ').ok_or(())?;
+                let signature = hex::decode(signature).map_err(|_| ())?;
+                match Entry::from_line(points)? {
+                    entry @ Entry::Points { .. } => Message::Result(Record {
+                        entry,
+                        signature: Some(Signature::from_bytes(&signature).map_err(|_| ())?),
+                    }),
+                    _ => return Err(()),
+                }
+            }
+            7 | 8 => {
+                let parts: Vec<&str> = body.split(' ').collect();
+                let seat = |i: usize| -> Result<usize, ()> {
+                    parts.get(i).ok_or(())?.parse().map_err(|_| ())
+                };
+                let (from, to) = (seat(0)?, seat(1)?);
+                match (kind, parts.len()) {
+                    (7, 4) => {
+                        let mut ephemeral = [0u8; 32];
+                        hex::decode_to_slice(parts[2], &mut ephemeral).map_err(|_| ())?;
+                        let signature = hex::decode(parts[3]).map_err(|_| ())?;
+                        Message::Handshake {
+                            from,
+                            to,
+                            handshake: Handshake {
+                                ephemeral,
+                                signature: Signature::from_bytes(&signature).map_err(|_| ())?,
+                            },
+                        }
+                    }
+                    (8, 3) => Message::Private {
+                        from,
+                        to,
+                        sealed: hex::decode(parts[2]).map_err(|_| ())?,
+                    },
+                    _ => return Err(()),
+                }
+            }
+            10 => match body.split(' ').collect::<Vec<_>>()[..] {
+                [round, seat, phase] => Message::Missed {
+                    round: round.parse().map_err(|_| ())?,
+                    seat: seat.parse().map_err(|_| ())?,
+                    phase: Phase::from_name(phase)?,
+                },
+                _ => return Err(()),
+            },
+            _ => Message::signed(Record::from_line(body)?)?,
+        };
+        match message.kind() == kind {
+            true => Ok(message),
+            false => Err(()),
+        }
+    }
+
+    /// The frame of the message, with its length, the version of the protocol and its kind
+    pub fn encode(&self) -> Vec<u8> {
+        let body = self.body();
+        let mut frame = Vec::with_capacity(body.len() + 6);
+        frame.extend_from_slice(&(body.len() as u32 + 2).to_be_bytes());
+        frame.push(PROTOCOL_VERSION);
+        frame.push(self.kind());
+        frame.extend_from_slice(body.as_bytes());
+        frame
+    }
+
+    /// Parse a message from the rest of its frame, after the length. Errors if the frame is of
+    /// another version of the protocol or is malformed.
+    pub fn decode(frame: &[u8]) -> Result<Self, NetError> {
+        match frame {
+            [PROTOCOL_VERSION, kind, body @ ..] => {
+                let body = std::str::from_utf8(body).map_err(|_| NetError::Malformed)?;
+                Message::from_body(*kind, body).map_err(|_| NetError::Malformed)
+            }
+            [version, ..] => Err(NetError::Version(*version)),
+            [] => Err(NetError::Malformed),
+        }
+    }
+}
+
+/// Write a message to a stream, in its frame
+pub fn write_message(stream: &mut impl Write, message: &Message) -> Result<(), NetError> {
+    stream.write_all(&message.encode())?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Read the next message from a stream. Errors if the stream is closed, or if the frame is too
+/// long, of another version of the protocol or malformed.
+pub fn read_message(stream: &mut impl Read) -> Result<Message, NetError> {
+    let mut length = [0u8; 4];
+    stream.read_exact(&mut length)?;
+    let length = u32::from_be_bytes(length);
+    if length > MAX_FRAME {
+        return Err(NetError::Malformed);
+    }
+    let mut frame = vec![0u8; length as usize];
+    stream.read_exact(&mut frame)?;
+    Message::decode(&frame)
+}
+
+/// Why a networked game can't go on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetError {
+    /// The connection to another peer failed or was closed
+    Io(io::ErrorKind),
+    /// A peer speaks this other version of the protocol
+    Version(u8),
+    /// A peer sent a frame that is not a message
+    Malformed,
+    /// A peer wants to play another game or variant, or the game can't be played over the
+    /// network
+    Rejected,
+    /// A peer sent a message that is not signed by the player of its seat
+    BadSignature(usize),
+    /// A peer sent a message that the rules of the game don't allow next, at this index of the
+    /// transcript
+    Transcript(TranscriptError),
+    /// The peer of a seat computed other points after a round
+    Disagreement { round: u32, seat: usize },
+    /// A private message from the player in a seat does not open in our channel with them
+    Tampered(usize),
+    /// Our own player stopped answering in a phase, and left the table
+    Left(Phase),
+    /// We have no channel open with the player in a seat
+    NoChannel(usize),
+}
+
+impl From<io::Error> for NetError {
+    fn from(error: io::Error) -> Self {
+        NetError::Io(error.kind())
+    }
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(kind) => write!(f, "the connection failed: {}", kind),
+            Self::Version(version) => {
+                write!(f, "a peer speaks version {} of the protocol", version)
+            }
+            Self::Malformed => write!(f, "a peer sent a malformed message"),
+            Self::Rejected => write!(f, "the game can't be played with this peer"),
+            Self::BadSignature(seat) => {
+                write!(f, "a message of player {} has a bad signature", seat)
+            }
+            Self::Transcript(error) => write!(f, "a peer broke the rules: {}", error),
+            Self::Disagreement { round, seat } => write!(
+                f,
+                "player {} computed other points after round {}",
+                seat, round
+            ),
+            Self::Tampered(seat) => {
+                write!(f, "a private message of player {} does not open", seat)
+            }
+            Self::Left(phase) => write!(f, "our player stopped answering in the {} phase", phase),
+            Self::NoChannel(seat) => write!(f, "there is no channel open with player {}", seat),
+        }
+    }
+}
+
+/// The connections of a peer to the others
+enum Link {
+    /// The host, with the connection to the peer of every other seat, from seat 1, until the
+    /// player of the seat misses a deadline and leaves the table
+    Host(Vec<Option<TcpStream>>),
+    /// A client, with its connection to the host
+    Client(TcpStream),
+}
+
+impl Link {
+    /// Send a message of our own player to every other peer, or only to the one it is for
+    fn send(&mut self, message: &Message) -> Result<(), NetError> {
+        match self {
+            Link::Host(clients) => relay(clients, 0, message),
+            Link::Client(host) => write_message(host, message),
+        }
+    }
+
+    /// Receive the next message of the player in another seat. The host relays it to the peers
+    /// of the other seats, or only to the one it is for.
+    fn receive(&mut self, seat: usize) -> Result<Message, NetError> {
+        match self {
+            Link::Host(clients) => {
+                let client = clients[seat - 1]
+                    .as_mut()
+                    .ok_or(NetError::Io(io::ErrorKind::NotConnected))?;
+                let message = read_message(client)?;
+                relay(clients, seat, &message)?;
+                Ok(message)
+            }
+            Link::Client(host) => read_message(host),
+        }
+    }
+
+    /// Wait some time for the next message of the player in another seat. The host gives up on
+    /// a player who does not answer in time or drops their connection, returns None and never
+    /// talks to them again. A client only gives up on the host, with an error.
+    fn wait(&mut self, seat: usize, timeout: Duration) -> Result<Option<Message>, NetError> {
+        let stream = match self {
+            Link::Host(clients) => clients[seat - 1]
+                .as_mut()
+                .ok_or(NetError::Io(io::ErrorKind::NotConnected))?,
+            Link::Client(host) => host,
+        };
+        stream.set_read_timeout(Some(timeout))?;
+        let read = read_message(stream);
+        // a stream that timed out in the middle of a frame is never read again
+        let _ = stream.set_read_timeout(None);
+        match (self, read) {
+            (Link::Host(clients), Ok(message)) => {
+                relay(clients, seat, &message)?;
+                Ok(Some(message))
+            }
+            (Link::Host(clients), Err(NetError::Io(kind))) if gone(kind) => {
+                clients[seat - 1] = None;
+                Ok(None)
+            }
+            (_, read) => read.map(Some),
+        }
+    }
+}
+
+/// Write a message from the player in a seat to the peers of every other seat still at the table,
+/// or only to the one it is for
+fn relay(
+    clients: &mut [Option<TcpStream>],
+    from: usize,
+    message: &Message,
+) -> Result<(), NetError> {
+    for (i, client) in clients.iter_mut().enumerate() {
+        if let Some(client) = client {
+            if i + 1 != from && message.to().unwrap_or(i + 1) == i + 1 {
+                write_message(client, message)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether a read failed because the peer did not answer in time or left
+fn gone(kind: io::ErrorKind) -> bool {
+    matches!(
+        kind,
+        io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+    )
+}
+
+/// A player's side of a game played over the network
+pub struct Peer {
+    /// The seat of our player
+    pub seat: usize,
+    /// Our player
+    pub player: Player,
+    /// The variant of poker played
+    pub variant: Variant,
+    /// The rounds played so far
+    pub rounds: Vec<Round>,
+    /// The evidence against the players caught cheating so far
+    pub evidence: Vec<CheatingEvidence>,
+    /// Every message of the game, signed by the player who sent it, with the points after each
+    /// round, the same on every peer
+    pub transcript: Transcript,
+    link: Link,
+    /// Our channel with the player of every other seat
+    channels: Vec<Option<Channel>>,
+    ledger: Ledger,
+    board: PublicMessageBoard,
+    /// The stacks of the players of the current round, by seat
+    stacks: Vec<u32>,
+    /// The number of the next round, counting the rounds stopped by a cheater
+    next_round: u32,
+    /// The seat of the button in the last round
+    button: Option<usize>,
+    /// The number of ticks the players have to answer in each phase
+    deadlines: Deadlines,
+    /// How long a tick lasts
+    tick: Duration,
+    /// The logical clock of the game, which only moves when a deadline passes so that it is the
+    /// same on every peer
+    clock: Clock,
+    /// A message of another player that arrived while we waited for it before its deadline
+    early: Option<Message>,
+}
+
+impl Peer {
+    /// Host a game of a variant between our player, in seat 0, and the players of some number of
+    /// clients, who sit in the order they connect to the listener, every player starting with a
+    /// stack of points. Returns once every client said hello. Errors if a client wants to play
+    /// another game, starts with other points or with the key of another player, or if the game
+    /// can't be played over the network.
+    pub fn host(
+        listener: &TcpListener,
+        mut player: Player,
+        game_id: u64,
+        variant: Variant,
+        stack: u32,
+        clients: usize,
+    ) -> Result<Self, NetError> {
+        if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&(clients + 1))
+            || variant.draws() > 0
+            || player.points != stack
+        {
+            return Err(NetError::Rejected);
+        }
+        player.enter_game(game_id);
+        let mut players = vec![(player.public(), player.points)];
+        let mut streams = Vec::new();
+        for _ in 0..clients {
+            let (mut stream, _) = listener.accept()?;
+            match read_message(&mut stream)? {
+                Message::Hello {
+                    game_id: g,
+                    variant: v,
+                    players: hello,
+                } if g == game_id
+                    && v == variant
+                    && hello.len() == 1
+                    && hello[0].1 == stack
+                    && players.iter().all(|(public, _)| *public != hello[0].0) =>
+                {
+                    players.push(hello[0])
+                }
+                _ => return Err(NetError::Rejected),
+            }
+            streams.push(stream);
+        }
+        let mut link = Link::Host(streams.into_iter().map(Some).collect());
+        link.send(&Message::Hello {
+            game_id,
+            variant,
+            players: players.clone(),
+        })?;
+        let mut peer = Peer::new(0, player, game_id, variant, &players, link);
+        peer.join_game()?;
+        Ok(peer)
+    }
+
+    /// Join the game of a variant hosted at an address with our player, every player starting
+    /// with a stack of points, and wait until every seat is taken. Errors if the host can't be
+    /// reached, plays another game or seats players with other points.
+    pub fn join(
+        address: impl ToSocketAddrs,
+        mut player: Player,
+        game_id: u64,
+        variant: Variant,
+        stack: u32,
+    ) -> Result<Self, NetError> {
+        if player.points != stack {
+            return Err(NetError::Rejected);
+        }
+        player.enter_game(game_id);
+        let mut stream = TcpStream::connect(address)?;
+        let hello = Message::Hello {
+            game_id,
+            variant,
+            players: vec![(player.public(), player.points)],
+        };
+        write_message(&mut stream, &hello)?;
+        let players = match read_message(&mut stream)? {
+            Message::Hello {
+                game_id: g,
+                variant: v,
+                players,
+            } if g == game_id
+                && v == variant
+                && (MIN_PLAYERS..=MAX_PLAYERS).contains(&players.len())
+                && players.iter().all(|(_, points)| *points == stack) =>
+            {
+                players
+            }
+            _ => return Err(NetError::Rejected),
+        };
+        let mut seats = (0..players.len()).filter(|seat| players[*seat].0 == player.public());
+        let seat = match (seats.next(), seats.next()) {
+            (Some(seat), None) => seat,
+            _ => return Err(NetError::Rejected),
+        };
+        let link = Link::Client(stream);
+        let mut peer = Peer::new(seat, player, game_id, variant, &players, link);
+        peer.join_game()?;
+        Ok(peer)
+    }
+
+    fn new(
+        seat: usize,
+        player: Player,
+        game_id: u64,
+        variant: Variant,
+        players: &[(PublicKey, u32)],
+        link: Link,
+    ) -> Self {
+        let (publics, points): (Vec<PublicKey>, Vec<u32>) = players.iter().copied().unzip();
+        let mut transcript = Transcript::new(game_id, &[]);
+        transcript.variant = variant;
+        transcript.players = publics;
+        transcript.starting_points = points.clone();
+        Peer {
+            seat,
+            player,
+            variant,
+            rounds: Vec::new(),
+            evidence: Vec::new(),
+            transcript,
+            link,
+            channels: vec![None; players.len()],
+            ledger: Ledger::new(points.clone()),
+            board: PublicMessageBoard::new(game_id),
+            stacks: points,
+            next_round: 0,
+            button: None,
+            deadlines: Deadlines::default(),
+            tick: TICK,
+            clock: Clock::default(),
+            early: None,
+        }
+    }
+
+    /// Give the players other deadlines, with ticks of some duration, before the first round.
+    /// Every peer of the game has to use the same ones.
+    pub fn with_deadlines(mut self, deadlines: Deadlines, tick: Duration) -> Self {
+        self.deadlines = deadlines;
+        self.tick = tick;
+        self
+    }
+
+    /// Open a channel between every two players, the one in the first seat sending their
+    /// handshake first. Every peer calls it at the same point of the game, between two rounds.
+    /// Errors if a handshake is not signed by the player who sent it.
+    pub fn open_channels(&mut self) -> Result<(), NetError> {
+        let seats = self.transcript.players.len();
+        for first in 0..seats {
+            for second in first + 1..seats {
+                let (mut ours, mut theirs) = (None, None);
+                for (from, to) in [(first, second), (second, first)] {
+                    let received = self.pass(from, to, |peer| {
+                        let (handshake, pending) = channel::handshake(
+                            &peer.player.keypair,
+                            &peer.transcript.players[to],
+                            peer.transcript.game_id,
+                            &mut rand::thread_rng(),
+                        );
+                        ours = Some(pending);
+                        Message::Handshake {
+                            from,
+                            to,
+                            handshake,
+                        }
+                    })?;
+                    match received {
+                        Some(Message::Handshake { handshake, .. }) => theirs = Some(handshake),
+                        Some(_) => return Err(NetError::Malformed),
+                        None => {}
+                    }
+                }
+                if let (Some(ours), Some(theirs)) = (ours, theirs) {
+                    let other = first + second - self.seat;
+                    let channel = ours
+                        .finish(&theirs)
+                        .map_err(|_| NetError::BadSignature(other))?;
+                    self.channels[other] = Some(channel);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Have every player join the game of the header of the transcript in seat order, checking
+    /// the joins of the others like the verifier does
+    fn join_game(&mut self) -> Result<(), NetError> {
+        for seat in 0..self.transcript.players.len() {
+            match seat == self.seat {
+                true => {
+                    let header = self.transcript.header();
+                    self.send(Entry::Join { seat, header })?;
+                }
+                false => self.replay(seat, 1, |replay| replay.join(seat))?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Pass a message from the player in a seat to the player in another, which only they and
+    /// the host relaying it see. The sender makes the message, and the receiver gets it back.
+    fn pass(
+        &mut self,
+        from: usize,
+        to: usize,
+        make: impl FnOnce(&mut Self) -> Message,
+    ) -> Result<Option<Message>, NetError> {
+        if self.seat == from {
+            let message = make(self);
+            self.link.send(&message)?;
+            return Ok(None);
+        }
+        if self.seat != to && self.seat != 0 {
+            return Ok(None);
+        }
+        let message = self.receive(from)?;
+        match message.to() == Some(to) {
+            true => Ok(Some(message).filter(|_| self.seat == to)),
+            false => Err(NetError::Malformed),
+        }
+    }
+
+    /// Send a private message from the player in a seat to the player in another, through the
+    /// channel between them. Every peer calls it at the same point of the game, and only the
+    /// message of the sender is used. Returns the message to its receiver, and None to every other
+    /// peer. Errors if the channels were not opened, or if the message does not open in the
+    /// channel of the receiver.
+    pub fn whisper(
+        &mut self,
+        from: usize,
+        to: usize,
+        message: &[u8],
+    ) -> Result<Option<Vec<u8>>, NetError> {
+        if self.seat == from && self.channels[to].is_none() {
+            return Err(NetError::NoChannel(to));
+        }
+        let received = self.pass(from, to, |peer| Message::Private {
+            from,
+            to,
+            sealed: peer.channels[to]
+                .as_mut()
+                .expect("the channel was checked")
+                .seal(message),
+        })?;
+        match received {
+            Some(Message::Private { sealed, .. }) => {
+                let channel = self.channels[from]
+                    .as_mut()
+                    .ok_or(NetError::NoChannel(from))?;
+                let message = channel
+                    .open(&sealed)
+                    .map_err(|_| NetError::Tampered(from))?;
+                Ok(Some(message))
+            }
+            Some(_) => Err(NetError::Malformed),
+            None => Ok(None),
+        }
+    }
+
+    /// The points of every player, by seat
+    pub fn points(&self) -> &[u32] {
+        self.ledger.stacks()
+    }
+
+    /// The seats of the players who still have points
+    pub fn seats_in(&self) -> Vec<usize> {
+        let points = self.points();
+        (0..points.len()).filter(|seat| points[*seat] > 0).collect()
+    }
+
+    /// The game is over once only one player has points left
+    pub fn is_over(&self) -> bool {
+        self.seats_in().len() < MIN_PLAYERS
+    }
+
+    /// Play a single round with the other peers, returning what happened in it, or the evidence
+    /// against the player caught cheating. Errors if a peer breaks the protocol or disconnects.
+    pub fn play_round(&mut self) -> Result<Result<Round, CheatingEvidence>, NetError> {
+        let number = self.next_round;
+        self.next_round += 1;
+        let seats = self.seats_in();
+        let seating = Seating {
+            stacks: seats.iter().map(|seat| self.points()[*seat]).collect(),
+            button: round::next_button(self.button, &seats),
+            seats,
+        };
+        self.button = Some(seating.button);
+        self.stacks = self.points().to_vec();
+        let (game_id, variant) = (self.transcript.game_id, self.variant);
+        let result = round::play(
+            self,
+            game_id,
+            variant,
+            Dealing::Vrf,
+            Blinds::default(),
+            number,
+            &seating,
+        )?;
+        self.ledger.settle(&seating.seats, &result);
+        self.player.points = self.points()[self.seat];
+
+        // every peer checks that the others signed the same points, but the player who missed a
+        // deadline left the table
+        let points = Entry::Points {
+            round: number,
+            points: self.points().to_vec(),
+        };
+        let missed = result.as_ref().ok().and_then(|round| round.missed);
+        for seat in &seating.seats {
+            if missed.map(|missed| missed.seat) == Some(*seat) {
+                continue;
+            }
+            if *seat == self.seat {
+                let record = Record::signed(points.clone(), &self.player.keypair, game_id);
+                self.link.send(&Message::Result(record))?;
+                continue;
+            }
+            match self.receive(*seat)? {
+                Message::Result(record) if record.entry == points => {
+                    if !record.is_signed_by(&self.transcript.players[*seat], game_id) {
+                        return Err(NetError::BadSignature(*seat));
+                    }
+                }
+                _ => {
+                    return Err(NetError::Disagreement {
+                        round: number,
+                        seat: *seat,
+                    })
+                }
+            }
+        }
+        self.transcript.record(points);
+        match &result {
+            Ok(round) => self.rounds.push(round.clone()),
+            Err(evidence) => self.evidence.push(evidence.clone()),
+        }
+        Ok(result)
+    }
+
+    /// Play rounds until only one player has points left, and return the seat of the winner
+    pub fn play(&mut self) -> Result<usize, NetError> {
+        while !self.is_over() {
+            let _ = self.play_round()?;
+        }
+        Ok(self.seats_in()[0])
+    }
+
+    /// Sign an entry of our player into the transcript, and send it to the other peers
+    fn send(&mut self, entry: Entry) -> Result<(), NetError> {
+        let record = self.sign(entry);
+        let message = Message::signed(record).map_err(|_| NetError::Rejected)?;
+        self.link.send(&message)
+    }
+
+    /// Sign an entry of our player into the transcript, and return its record
+    fn sign(&mut self, entry: Entry) -> Record {
+        self.transcript.sign(entry, &self.player.keypair);
+        self.transcript.records.last().unwrap().clone()
+    }
+
+    /// Receive the next message of the player in another seat, which may have arrived already
+    /// while we waited for it before its deadline
+    fn receive(&mut self, seat: usize) -> Result<Message, NetError> {
+        match self.early.take() {
+            Some(message) => Ok(message),
+            None => self.link.receive(seat),
+        }
+    }
+
+    /// Receive some messages of the player in another seat into the transcript, and read them
+    /// back with the rules of the game, like the verifier does
+    fn replay<T>(
+        &mut self,
+        seat: usize,
+        messages: usize,
+        read: impl FnOnce(&mut Replay) -> Result<T, TranscriptError>,
+    ) -> Result<T, NetError> {
+        let next = self.transcript.records.len();
+        for _ in 0..messages {
+            let records = match self.receive(seat)? {
+                Message::Join(record)
+                | Message::Reveal(record)
+                | Message::VrfReveal(record)
+                | Message::Bet(record)
+                | Message::Fold(record)
+                | Message::Timeout(record) => vec![record],
+                Message::Commit(records) => records,
+                _ => return Err(NetError::Transcript(TranscriptError::UnexpectedEntry(next))),
+            };
+            for record in records {
+                if !record.is_signed_by(&self.transcript.players[seat], self.transcript.game_id) {
+                    return Err(NetError::BadSignature(seat));
+                }
+                self.transcript.records.push(record);
+            }
+        }
+        read(&mut Replay::at(&self.transcript, next)).map_err(NetError::Transcript)
+    }
+
+    /// How long we wait for the player in another seat in a phase. The host waits until the
+    /// deadline of the phase, and a client, who only hears from the host, a few times longer.
+    fn patience(&self, phase: Phase) -> Duration {
+        let ticks = u32::try_from(self.deadlines.of(phase)).unwrap_or(u32::MAX);
+        let patience = match self.link {
+            Link::Host(_) => self.tick.saturating_mul(ticks),
+            Link::Client(_) => self
+                .tick
+                .saturating_mul(ticks)
+                .saturating_mul(CLIENT_PATIENCE),
+        };
+        // a read timeout of zero is refused
+        patience.max(Duration::from_millis(1))
+    }
+}
+
+impl Messages for Peer {
+    type Error = NetError;
+
+    fn public(&self, seat: usize) -> PublicKey {
+        self.transcript.players[seat]
+    }
+
+    fn commitments(
+        &mut self,
+        seat: usize,
+        number: u32,
+        street: u32,
+    ) -> Result<Vec<HashValue>, NetError> {
+        if seat != self.seat {
+            return self.replay(seat, 1, |replay| replay.commitments(seat, number, street));
+        }
+        let game_id = self.transcript.game_id;
+        let posted = self.player.commit(&mut self.board, game_id, number, street);
+        let records = posted
+            .iter()
+            .map(|commitment| {
+                self.sign(Entry::Commitment {
+                    round: number,
+                    seat,
+                    street,
+                    commitment: *commitment,
+                })
+            })
+            .collect();
+        self.link.send(&Message::Commit(records))?;
+        Ok(posted)
+    }
+
+    fn on_time(
+        &mut self,
+        seat: usize,
+        number: u32,
+        phase: Phase,
+        seats: &[usize],
+    ) -> Result<bool, NetError> {
+        if seat == self.seat {
+            // our player answers at once, or never does and leaves the game
+            return match self.player.answer_time(phase) {
+                Some(_) => Ok(true),
+                None => Err(NetError::Left(phase)),
+            };
+        }
+        let patience = self.patience(phase);
+        let on_time = match self.link.wait(seat, patience)? {
+            // the host tells everybody who missed the deadline
+            Some(Message::Missed {
+                round,
+                seat: s,
+                phase: p,
+            }) => match (round, s, p) == (number, seat, phase) {
+                true => false,
+                false => return Err(NetError::Malformed),
+            },
+            Some(message) => {
+                self.early = Some(message);
+                true
+            }
+            None => {
+                let missed = Message::Missed {
+                    round: number,
+                    seat,
+                    phase,
+                };
+                self.link.send(&missed)?;
+                false
+            }
+        };
+        if on_time {
+            return Ok(true);
+        }
+        // every other player of the phase signs that they saw the deadline pass, at the same
+        // tick of the clock of the game, and every peer checks it like the verifier does
+        let tick = match self.clock.wait(None, self.deadlines.of(phase)) {
+            Ok(tick) | Err(tick) => tick,
+        };
+        let next = self.transcript.records.len();
+        for witness in seats.iter().copied().filter(|witness| *witness != seat) {
+            match witness == self.seat {
+                true => self.send(Entry::Timeout {
+                    round: number,
+                    seat,
+                    phase,
+                    tick,
+                    witness,
+                })?,
+                false => self.replay(witness, 1, |_| Ok(()))?,
+            }
+        }
+        Replay::at(&self.transcript, next)
+            .on_time(seat, number, phase, seats)
+            .map_err(NetError::Transcript)
+    }
+
+    fn reveal(&mut self, seat: usize, number: u32, street: u32) -> Result<String, NetError> {
+        if seat != self.seat {
+            return self.replay(seat, 1, |replay| replay.reveal(seat, number, street));
+        }
+        let reveal = self.player.reveal().unwrap_or_default();
+        self.send(Entry::Reveal {
+            round: number,
+            seat,
+            street,
+            reveal: reveal.clone(),
+        })?;
+        Ok(reveal)
+    }
+
+    fn deal(&mut self, seat: usize, common_random: &[u8; 32], cards: usize) {
+        if seat == self.seat {
+            self.player.draw_hand(common_random, cards);
+        }
+    }
+
+    fn shuffle(
+        &mut self,
+        _seat: usize,
+        _number: u32,
+        _deck: &[RistrettoPoint],
+    ) -> Result<Shuffle, NetError> {
+        Err(NetError::Rejected)
+    }
+
+    fn open_shuffle(
+        &mut self,
+        _seat: usize,
+        _number: u32,
+        _challenges: &[bool],
+    ) -> Result<Vec<Opening>, NetError> {
+        Err(NetError::Rejected)
+    }
+
+    fn decrypt(
+        &mut self,
+        _seat: usize,
+        _number: u32,
+        _position: usize,
+        _point: &RistrettoPoint,
+    ) -> Result<(RistrettoPoint, DecryptionProof), NetError> {
+        Err(NetError::Rejected)
+    }
+
+    fn action(
+        &mut self,
+        seat: usize,
+        number: u32,
+        seats: &[usize],
+        betting: &BettingRound,
+        community: &[Card],
+    ) -> Result<Action, NetError> {
+        if seat != self.seat {
+            return self.replay(seat, 1, |replay| {
+                replay.action(seat, number, seats, betting, community)
+            });
+        }
+        // our player bets knowing only their own cards and the community cards
+        let cards = [self.player.hand.as_slice(), community].concat();
+        let opponent_stacks: Vec<u32> = seats
+            .iter()
+            .filter(|other| **other != seat)
+            .map(|other| self.stacks[*other])
+            .collect();
+        let action = self
+            .player
+            .act(&cards, &betting.view(), &opponent_stacks, &self.rounds);
+        // an action that is not allowed counts as a fold
+        let action = match betting.allows(action) {
+            true => action,
+            false => Action::Fold,
+        };
+        self.send(Entry::Action {
+            round: number,
+            seat,
+            action,
+        })?;
+        Ok(action)
+    }
+
+    fn discard(
+        &mut self,
+        _seat: usize,
+        _number: u32,
+        _draw_round: u32,
+        _hand_size: usize,
+        _most: usize,
+    ) -> Result<Vec<usize>, NetError> {
+        Err(NetError::Rejected)
+    }
+
+    fn draws(
+        &mut self,
+        seat: usize,
+        number: u32,
+        common_random: &[u8; 32],
+        recipients: &[usize],
+        cards: &[(usize, u32)],
+    ) -> Result<Vec<Vec<CardDraw>>, NetError> {
+        if seat != self.seat {
+            return self.replay(seat, recipients.len() * cards.len(), |replay| {
+                replay.draws(seat, number, common_random, recipients, cards)
+            });
+        }
+        let draws = self.player.publish_draws(common_random, recipients.len());
+        for (recipient, sent) in recipients.iter().zip(&draws) {
+            for ((slot, draw_round), draw) in cards.iter().zip(sent) {
+                self.send(Entry::Draw {
+                    round: number,
+                    seat,
+                    recipient: *recipient,
+                    slot: *slot,
+                    draw_round: *draw_round,
+                    draw: draw.clone(),
+                })?;
+            }
+        }
+        Ok(draws)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poker::cheating::Cheat;
+    use crate::poker::deadline::MissedDeadline;
+    use crate::poker::transcript::verify_transcript;
+    use rand_core::OsRng;
+    use schnorrkel::Keypair;
+    use std::thread;
+
+    fn new_player(seed: u64) -> Player {
+        Player::from_keypair(Keypair::generate_with(OsRng), 100, seed)
+    }
+
+    /// Host a game on a loopback port and have some clients join it from their own thread, each
+    /// with a player set up by a function of their number, 0 for the host. The clients sit in the
+    /// order they connect, whatever their number. Returns every peer once they played the game to
+    /// its end, by seat.
+    fn play_over_loopback(
+        variant: Variant,
+        clients: usize,
+        setup: impl Fn(usize, &mut Player) + Send + Sync + Copy + 'static,
+    ) -> Vec<Peer> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let joined: Vec<_> = (1..=clients)
+            .map(|client| {
+                thread::spawn(move || {
+                    let mut player = new_player(client as u64);
+                    setup(client, &mut player);
+                    let mut peer = Peer::join(address, player, 7, variant, 100).unwrap();
+                    peer.play().unwrap();
+                    peer
+                })
+            })
+            .collect();
+        let mut player = new_player(0);
+        setup(0, &mut player);
+        let mut host = Peer::host(&listener, player, 7, variant, 100, clients).unwrap();
+        host.play().unwrap();
+        let mut peers = vec![host];
+        peers.extend(joined.into_iter().map(|peer| peer.join().unwrap()));
+        // the clients sit in the order they connected
+        peers.sort_by_key(|peer| peer.seat);
+        peers
+    }
+
+    #[test]
+    fn messages_survive_the_wire() {
+        let keypair = Keypair::generate_with(OsRng);
+        let mut transcript = Transcript::new(7, &[]);
+        transcript.sign(
+            Entry::Action {
+                round: 2,
+                seat: 1,
+                action: Action::Raise(30),
+            },
+            &keypair,
+        );
+        for commitment in [[1u8; 16], [2u8; 16]] {
+            let entry = Entry::Commitment {
+                round: 2,
+                seat: 1,
+                street: 0,
+                commitment,
+            };
+            transcript.sign(entry, &keypair);
+        }
+        let points = Entry::Points {
+            round: 2,
+            points: vec![70, 130],
+        };
+        let timeout = Entry::Timeout {
+            round: 2,
+            seat: 1,
+            phase: Phase::Showdown,
+            tick: 30,
+            witness: 0,
+        };
+        let messages = [
+            Message::Hello {
+                game_id: 7,
+                variant: Variant::Holdem,
+                players: vec![(keypair.public, 100)],
+            },
+            Message::signed(transcript.records[0].clone()).unwrap(),
+            Message::Commit(transcript.records[1..].to_vec()),
+            Message::Result(Record::signed(points.clone(), &keypair, 7)),
+            Message::Handshake {
+                from: 0,
+                to: 1,
+                handshake: channel::handshake(&keypair, &keypair.public, 7, &mut OsRng).0,
+            },
+            Message::Private {
+                from: 1,
+                to: 0,
+                sealed: vec![0, 1, 255],
+            },
+            Message::Missed {
+                round: 2,
+                seat: 1,
+                phase: Phase::Showdown,
+            },
+            Message::signed(Record::signed(timeout, &keypair, 7)).unwrap(),
+        ];
+        for message in messages {
+            let mut wire = Vec::new();
+            write_message(&mut wire, &message).unwrap();
+            assert_eq!(read_message(&mut wire.as_slice()), Ok(message));
+        }
+        assert!(matches!(
+            Message::signed(transcript.records[0].clone()),
+            Ok(Message::Bet(_))
+        ));
+        // the points of a round are only sent signed
+        let mut frame = vec![PROTOCOL_VERSION, 6];
+        frame.extend_from_slice(points.to_line().as_bytes());
+        assert_eq!(Message::decode(&frame), Err(NetError::Malformed));
+    }
+
+    #[test]
+    fn other_versions_are_refused() {
+        let keypair = Keypair::generate_with(OsRng);
+        let points = Entry::Points {
+            round: 0,
+            points: vec![100, 100],
+        };
+        let message = Message::Result(Record::signed(points, &keypair, 7));
+        let mut frame = message.encode();
+        frame[4] = PROTOCOL_VERSION + 1;
+        assert_eq!(
+            read_message(&mut frame.as_slice()),
+            Err(NetError::Version(PROTOCOL_VERSION + 1))
+        );
+        // a frame whose kind does not match its body is malformed
+        let mut frame = message.encode();
+        frame[5] = 1;
+        assert_eq!(
+            read_message(&mut frame.as_slice()),
+            Err(NetError::Malformed)
+        );
+        // and so is one that claims to be too long
+        let mut frame = message.encode();
+        frame[..4].copy_from_slice(&(MAX_FRAME + 1).to_be_bytes());
+        assert_eq!(
+            read_message(&mut frame.as_slice()),
+            Err(NetError::Malformed)
+        );
+    }
+
+    #[test]
+    fn private_messages_only_open_for_their_receiver() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let joined: Vec<_> = (1..=2)
+            .map(|client| {
+                thread::spawn(move || {
+                    let player = new_player(client);
+                    let mut peer = Peer::join(address, player, 7, Variant::FiveCard, 100).unwrap();
+                    // nobody talks privately before asking for the channels
+                    if peer.seat == 1 {
+                        let whispered = peer.whisper(1, 2, b"fold");
+                        assert_eq!(whispered, Err(NetError::NoChannel(2)));
+                    }
+                    peer.open_channels().unwrap();
+                    let first = peer.whisper(1, 2, b"check to the river").unwrap();
+                    let second = peer.whisper(2, 1, b"deal").unwrap();
+                    (peer.seat, first, second)
+                })
+            })
+            .collect();
+        let mut host = Peer::host(&listener, new_player(0), 7, Variant::FiveCard, 100, 2).unwrap();
+        host.open_channels().unwrap();
+        // the host relays the messages between the two clients, but can't read them
+        assert_eq!(host.whisper(1, 2, b"").unwrap(), None);
+        assert_eq!(host.whisper(2, 1, b"").unwrap(), None);
+        for client in joined {
+            let (seat, first, second) = client.join().unwrap();
+            match seat {
+                1 => assert_eq!((first, second), (None, Some(b"deal".to_vec()))),
+                _ => assert_eq!(
+                    (first, second),
+                    (Some(b"check to the river".to_vec()), None)
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn peers_play_a_game_over_loopback() {
+        let peers = play_over_loopback(Variant::FiveCard, 2, |_, _| {});
+        let transcript = &peers[0].transcript;
+        let verified = verify_transcript(transcript).unwrap();
+        assert_eq!(verified.points.iter().sum::<u32>(), 300);
+        for (seat, peer) in peers.iter().enumerate() {
+            assert_eq!(peer.seat, seat);
+            // every peer keeps the same transcript, and agrees on the winner
+            assert_eq!(peer.transcript, *transcript);
+            assert_eq!(peer.points(), verified.points.as_slice());
+            assert_eq!(peer.player.points, verified.points[seat]);
+        }
+    }
+
+    #[test]
+    fn holdem_is_played_over_loopback() {
+        let peers = play_over_loopback(Variant::Holdem, 1, |_, _| {});
+        assert_eq!(peers[0].transcript, peers[1].transcript);
+        assert!(verify_transcript(&peers[1].transcript).is_ok());
+        assert!(peers[0]
+            .rounds
+            .iter()
+            .any(|round| !round.community.is_empty()));
+    }
+
+    #[test]
+    fn every_peer_catches_a_cheater() {
+        for cheat in [
+            Cheat::BadReveal,
+            Cheat::DoubleCommitment,
+            Cheat::Equivocation,
+        ] {
+            let peers = play_over_loopback(Variant::FiveCard, 2, move |client, player| {
+                if client == 2 {
+                    player.cheat = Some(cheat);
+                }
+            });
+            let cheater = peers.iter().position(|peer| peer.player.cheat.is_some());
+            for peer in &peers {
+                assert_eq!(peer.evidence.len(), 1);
+                assert_eq!(Some(peer.evidence[0].seat()), cheater);
+                assert_eq!(peer.points()[cheater.unwrap()], 0);
+                assert_eq!(peer.transcript, peers[0].transcript);
+            }
+            let verified = verify_transcript(&peers[0].transcript).unwrap();
+            assert_eq!(verified.evidence, peers[1].evidence);
+        }
+    }
+
+    #[test]
+    fn staller_forfeits_over_loopback() {
+        // short ticks, so the deadline passes quickly
+        let tick = Duration::from_millis(20);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let joined: Vec<_> = (1..=2)
+            .map(|client| {
+                thread::spawn(move || {
+                    let mut player = new_player(client);
+                    if client == 2 {
+                        player.cheat = Some(Cheat::Stall(Phase::Reveal));
+                    }
+                    let mut peer = Peer::join(address, player, 7, Variant::FiveCard, 100)
+                        .unwrap()
+                        .with_deadlines(Deadlines::default(), tick);
+                    // the staller stops answering, but keeps its connection open
+                    let result = peer.play();
+                    (peer, result)
+                })
+            })
+            .collect();
+        let mut host = Peer::host(&listener, new_player(0), 7, Variant::FiveCard, 100, 2)
+            .unwrap()
+            .with_deadlines(Deadlines::default(), tick);
+        host.play().unwrap();
+        let clients: Vec<(Peer, Result<usize, NetError>)> = joined
+            .into_iter()
+            .map(|client| client.join().unwrap())
+            .collect();
+        let (staller, left) = clients
+            .iter()
+            .find(|(peer, _)| peer.player.cheat.is_some())
+            .unwrap();
+        let (other, played) = clients
+            .iter()
+            .find(|(peer, _)| peer.player.cheat.is_none())
+            .unwrap();
+        assert_eq!(*left, Err(NetError::Left(Phase::Reveal)));
+        assert!(played.is_ok());
+        // the others agree that the staller missed the deadline and forfeited everything
+        assert_eq!(other.transcript, host.transcript);
+        let verified = verify_transcript(&host.transcript).unwrap();
+        let missed = MissedDeadline {
+            seat: staller.seat,
+            phase: Phase::Reveal,
+        };
+        assert_eq!(verified.missed, vec![missed]);
+        assert_eq!(host.points()[staller.seat], 0);
+        assert_eq!(host.points(), verified.points.as_slice());
+        assert_eq!(host.rounds[0].missed, Some(missed));
+    }
+
+    #[test]
+    fn host_refuses_another_game() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client =
+            thread::spawn(move || Peer::join(address, new_player(1), 8, Variant::FiveCard, 100));
+        let host = Peer::host(&listener, new_player(0), 7, Variant::FiveCard, 100, 1);
+        assert!(matches!(host, Err(NetError::Rejected)));
+        assert!(client.join().unwrap().is_err());
+        // or to start with more points than the table
+        let client = thread::spawn(move || {
+            let mut player = new_player(1);
+            player.points = 1000;
+            Peer::join(address, player, 7, Variant::FiveCard, 1000)
+        });
+        let host = Peer::host(&listener, new_player(0), 7, Variant::FiveCard, 100, 1);
+        assert!(matches!(host, Err(NetError::Rejected)));
+        assert!(client.join().unwrap().is_err());
+        // and nobody can play five-card draw over the network
+        let draw = Peer::host(&listener, new_player(0), 7, Variant::Draw, 100, 1);
+        assert!(matches!(draw, Err(NetError::Rejected)));
+    }
+}
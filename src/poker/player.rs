@@ -0,0 +1,444 @@
+use curve25519_dalek::ristretto::RistrettoPoint;
+use rand::{rngs::StdRng, CryptoRng, Rng, RngCore, SeedableRng};
+use schnorrkel::{signing_context, Keypair, PublicKey};
+
+use super::betting::{Action, BettingView};
+use super::card::Card;
+use super::cheating::Cheat;
+use super::commit::{self, CommittedValue, RandomValue};
+use super::deadline::Phase;
+use super::deck::{self, DeckKey, DecryptionProof};
+use super::keys::{self, Identity};
+use super::round::Round;
+use super::shuffle::{Opening, Shuffle, ShuffleSecret};
+use super::strategy::{BettingStrategy, LinearStrategy};
+use super::vrf::{self, CardDraw};
+use crate::p1_hashing::{HashValue, PublicMessageBoard};
+
+/// Generate the schnorrkel keypair corresponding to a bip39 phrase, with the same public key (and
+/// SS58 address) as `Sr25519Pair::from_phrase`. Panics if the phrase is invalid.
+pub fn generate_vrf_keypair(bip39_str: &str) -> Keypair {
+    Identity::from_mnemonic(bip39_str).unwrap().keypair
+}
+
+/// The signing context of the transcripts our secrets are drawn from
+const SECRETS_CONTEXT: &[u8] = b"poker player secrets";
+
+/// A player sitting at the table.
+///
+/// The master keypair is created once when the player sits down, from their identity. When the
+/// player enters a game they derive the child keypair of that game from it, and reuse it for every
+/// round.
+pub struct Player {
+    /// The keypair used to sign our messages and evaluate the VRF on the common random value: the
+    /// child keypair of the game we are in, or the master keypair before we enter a game
+    pub keypair: Keypair,
+    /// The master keypair of our identity, which never signs anything
+    master: Keypair,
+    /// The points the player has left. The player is out once this reaches zero.
+    pub points: u32,
+    /// The points the player put aside when they sat down, which they forfeit if they miss a
+    /// deadline
+    pub deposit: u32,
+    /// The number of ticks of the game clock the player takes to answer
+    pub latency: u64,
+    /// The cards drawn in the current round, by slot
+    pub hand: Vec<Card>,
+    /// The random number contributed to the current round, if any
+    pub random_number: Option<RandomValue>,
+    /// The reveal of our last commitment, kept secret until the reveal phase
+    reveal: Option<String>,
+    /// The card draws of the current round by slot, each with the draw round it was drawn in,
+    /// kept secret until they are published
+    draws: Vec<Vec<(u32, CardDraw)>>,
+    /// The key we encrypted the finite deck of the current round with, if we shuffled it
+    deck_key: Option<DeckKey>,
+    /// What we keep to prove our shuffle of the finite deck of the current round
+    shuffle_secret: Option<ShuffleSecret>,
+    /// How this player cheats, if they do. Honest players have None.
+    pub cheat: Option<Cheat>,
+    /// How this player chooses their bets
+    pub strategy: Box<dyn BettingStrategy>,
+    /// A seeded RNG mixed into every secret of the player. The secrets are keyed with our secret
+    /// key too (see `secret_rng`), so small seeds only make games reproducible, and anyone who
+    /// knows them still can't predict our secrets.
+    rng: StdRng,
+}
+
+impl Player {
+    /// Create a new player from a bip39 phrase, with some starting points and a seed for the RNG
+    /// mixed into their secrets.
+    pub fn new(bip39_str: &str, points: u32, rng_seed: u64) -> Self {
+        Self::from_keypair(generate_vrf_keypair(bip39_str), points, rng_seed)
+    }
+
+    /// Create a new player from their identity, with some starting points and a seed for the RNG
+    /// mixed into their secrets.
+    pub fn from_identity(identity: Identity, points: u32, rng_seed: u64) -> Self {
+        Self::from_keypair(identity.keypair, points, rng_seed)
+    }
+
+    /// Create a new player from an existing keypair, with some starting points and a seed for the
+    /// RNG mixed into their secrets. The player bets with the linear strategy until
+    /// given another one with `with_strategy`.
+    pub fn from_keypair(keypair: Keypair, points: u32, rng_seed: u64) -> Self {
+        Player {
+            master: keypair.clone(),
+            keypair,
+            points,
+            deposit: 0,
+            latency: 1,
+            hand: Vec::new(),
+            random_number: None,
+            reveal: None,
+            draws: Vec::new(),
+            deck_key: None,
+            shuffle_secret: None,
+            cheat: None,
+            strategy: Box::new(LinearStrategy),
+            rng: StdRng::seed_from_u64(rng_seed),
+        }
+    }
+
+    /// Generate a fresh random value to contribute to the common randomness of a street of a
+    /// round, and commit to it on the message board with a random salt. Returns the commitments
+    /// posted, which is a single one unless we cheat.
+    pub fn commit(
+        &mut self,
+        board: &mut PublicMessageBoard,
+        game_id: u64,
+        round: u32,
+        street: u32,
+    ) -> Vec<HashValue> {
+        let context = [
+            &game_id.to_be_bytes()[..],
+            &round.to_be_bytes(),
+            &street.to_be_bytes(),
+        ]
+        .concat();
+        let mut rng = self.secret_rng(b"commitment", &context);
+        let committed = CommittedValue {
+            public: self.public().to_bytes(),
+            game_id,
+            round,
+            street,
+            value: rng.gen(),
+            salt: rng.gen(),
+        };
+        let (reveal, commitment) = commit::commit(board, &committed);
+        self.random_number = Some(committed.value);
+        self.reveal = Some(reveal);
+
+        let mut commitments = vec![commitment];
+        if self.cheat == Some(Cheat::DoubleCommitment) {
+            let other = CommittedValue {
+                value: rng.gen(),
+                ..committed
+            };
+            commitments.push(commit::commit(board, &other).1);
+        }
+        commitments
+    }
+
+    /// A CSPRNG for one of our secrets, keyed with our secret key and with what the secret is for,
+    /// with our seeded RNG as extra randomness
+    fn secret_rng(&mut self, label: &'static [u8], context: &[u8]) -> impl RngCore + CryptoRng {
+        let mut transcript = signing_context(SECRETS_CONTEXT).bytes(label);
+        transcript.append_message(b"context", context);
+        // only the scalar of the key: the nonce half of a derived key is random
+        transcript
+            .build_rng()
+            .rekey_with_witness_bytes(b"secret", &self.keypair.secret.to_bytes()[..32])
+            .finalize(&mut self.rng)
+    }
+
+    /// The reveal of our last commitment, if we have committed
+    pub fn reveal(&self) -> Option<String> {
+        let reveal = self.reveal.clone()?;
+        if self.cheat != Some(Cheat::BadReveal) {
+            return Some(reveal);
+        }
+        // keep the randomness added by the board, but change the value
+        let mut committed = CommittedValue::from_reveal(&reveal).ok()?;
+        committed.value[0] ^= 1;
+        let board_randomness = &reveal[committed.to_message().len()..];
+        Some(committed.to_message() + board_randomness)
+    }
+
+    /// The number of ticks we take to answer in a phase, or None if we never answer
+    pub fn answer_time(&self, phase: Phase) -> Option<u64> {
+        match self.cheat {
+            Some(Cheat::Stall(stalled)) if stalled == phase => None,
+            _ => Some(self.latency),
+        }
+    }
+
+    /// The public key other players use to verify our messages and card draws
+    pub fn public(&self) -> PublicKey {
+        self.keypair.public
+    }
+
+    /// The SS58 address of our master key, which identifies us across games
+    pub fn address(&self) -> String {
+        keys::ss58_address(&self.master.public)
+    }
+
+    /// The public key of our master key, from which anyone can check the public key we use in a
+    /// game
+    pub fn master_public(&self) -> PublicKey {
+        self.master.public
+    }
+
+    /// Derive the child keypair of a game from our master key, and use it from now on
+    pub fn enter_game(&mut self, game_id: u64) {
+        self.keypair = keys::derive_game_keypair(&self.master, game_id);
+    }
+
+    /// Evaluate our VRF on the common random value once for every slot of a hand of some size,
+    /// returning the cards.
+    ///
+    /// Only we know the cards until the draws are published.
+    pub fn draw_hand(&mut self, common_random: &[u8], size: usize) -> Vec<Card> {
+        let (hand, draws) = (0..size)
+            .map(|slot| {
+                let (in_out, draw) =
+                    vrf::draw(&self.keypair, &vrf::draw_input(common_random, 0, slot));
+                (vrf::card_from_output(&in_out), vec![(0, draw)])
+            })
+            .unzip();
+        self.hand = hand;
+        self.draws = draws;
+        self.hand.clone()
+    }
+
+    /// Choose the slots of our hand to discard in a draw with our strategy
+    pub fn discard(&mut self) -> Vec<usize> {
+        self.strategy.discard(&self.hand)
+    }
+
+    /// Replace the cards in some slots of our hand with cards drawn by our VRF on the common
+    /// random value of the draw round, the draw round and the slot, returning the new hand. Only
+    /// we know the replacement cards until the draws are published.
+    pub fn redraw(&mut self, draw_random: &[u8], draw_round: u32, slots: &[usize]) -> Vec<Card> {
+        for slot in slots {
+            let input = vrf::draw_input(draw_random, draw_round, *slot);
+            let (in_out, draw) = vrf::draw(&self.keypair, &input);
+            self.hand[*slot] = vrf::card_from_output(&in_out);
+            self.draws[*slot].push((draw_round, draw));
+        }
+        self.hand.clone()
+    }
+
+    /// Encrypt every card of the finite deck of a round with a fresh key, and shuffle it, with the
+    /// shadow shuffles that prove it. Our hand is dealt from the deck afterwards. The key and the
+    /// permutations are drawn from our secret RNG, so knowing our seed does not reveal the order.
+    pub fn shuffle(&mut self, deck: &[RistrettoPoint]) -> Shuffle {
+        let context: Vec<u8> = deck.iter().flat_map(|p| p.compress().to_bytes()).collect();
+        let mut rng = self.secret_rng(b"shuffle", &context);
+        let key = DeckKey::generate(&mut rng);
+        let (mut shuffle, secret) = key.shuffle(deck, &mut rng);
+        if self.cheat == Some(Cheat::BadShuffle) {
+            shuffle.deck[0] = shuffle.deck[1];
+        }
+        self.deck_key = Some(key);
+        self.shuffle_secret = Some(secret);
+        self.hand.clear();
+        shuffle
+    }
+
+    /// Answer the challenges of our last shuffle
+    pub fn open_shuffle(&self, challenges: &[bool]) -> Vec<Opening> {
+        let secret = self.shuffle_secret.as_ref().expect("we shuffled the deck");
+        secret.open(challenges)
+    }
+
+    /// Remove our layer of encryption from a card of the deck we shuffled in public, with the proof
+    /// that we removed the layer of the key we shuffled with
+    pub fn decrypt(&mut self, point: &RistrettoPoint) -> (RistrettoPoint, DecryptionProof) {
+        let key = self.deck_key.as_ref().expect("we shuffled the deck");
+        let (decrypted, proof) = key.decrypt_proven(point, &mut self.rng);
+        match self.cheat {
+            // the proof of the real decryption does not hold for another card
+            Some(Cheat::BadDecryption) => (decrypted + point, proof),
+            _ => (decrypted, proof),
+        }
+    }
+
+    /// Receive a card of the deck for a slot of our hand, with only our layer of encryption left
+    /// on it, and learn it. Returns the card, or None if the point is not a card.
+    pub fn receive(&mut self, slot: usize, point: &RistrettoPoint) -> Option<Card> {
+        let key = self.deck_key.as_ref().expect("we shuffled the deck");
+        let card = deck::card_from_point(&key.decrypt(point))?;
+        match slot < self.hand.len() {
+            true => self.hand[slot] = card,
+            false => self.hand.push(card),
+        }
+        Some(card)
+    }
+
+    /// Publish our card draws of the current round to each of the other players, returning the
+    /// draws sent to each of them: slot by slot, every draw of the slot in the order it was
+    /// drawn. An honest player sends the same draws to everyone.
+    pub fn publish_draws(&self, common_random: &[u8], recipients: usize) -> Vec<Vec<CardDraw>> {
+        let draws: Vec<CardDraw> = self
+            .draws
+            .iter()
+            .flatten()
+            .map(|(_, d)| d.clone())
+            .collect();
+        let honest_recipients = match self.cheat {
+            Some(Cheat::InvalidVrfProof) => 0,
+            Some(Cheat::Equivocation) => 1,
+            _ => return vec![draws; recipients],
+        };
+        // draws on other inputs, which do not verify on the common random value
+        let mut other_input = common_random.to_vec();
+        other_input.push(0);
+        let other_draws: Vec<CardDraw> = (0..self.draws.len())
+            .flat_map(|slot| {
+                self.draws[slot]
+                    .iter()
+                    .map(move |(round, _)| (slot, *round))
+            })
+            .map(|(slot, round)| {
+                vrf::draw(&self.keypair, &vrf::draw_input(&other_input, round, slot)).1
+            })
+            .collect();
+        (0..recipients)
+            .map(|i| match i < honest_recipients {
+                true => draws.clone(),
+                false => other_draws.clone(),
+            })
+            .collect()
+    }
+
+    /// Give the player a betting strategy
+    pub fn with_strategy(mut self, strategy: Box<dyn BettingStrategy>) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Choose an action for our hand with our strategy when it is our turn to bet, knowing the
+    /// stacks of the other players in the round and the history of the game.
+    pub fn act(
+        &mut self,
+        hand: &[Card],
+        view: &BettingView,
+        opponent_stacks: &[u32],
+        history: &[Round],
+    ) -> Action {
+        self.strategy
+            .act(hand, view, self.points, opponent_stacks, history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: &str =
+        "lunar antique thank school space garden soda cigar glove despair master pumpkin";
+
+    fn view(current_bet: u32) -> BettingView {
+        BettingView {
+            contributed: 0,
+            to_call: current_bet,
+            current_bet,
+            min_raise_to: current_bet * 2,
+            min_bet: 1,
+            all_in: 100,
+            pot: current_bet,
+        }
+    }
+
+    fn hand(indices: [u8; 5]) -> Vec<Card> {
+        indices
+            .iter()
+            .map(|index| Card::from_index(*index).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn linear_player_bets_more_on_better_hands() {
+        let mut player = Player::new(SEED, 100, 0);
+        // 8 high, and four aces
+        let weak = hand([0, 14, 28, 42, 6]);
+        let strong = hand([12, 25, 38, 51, 11]);
+        assert_eq!(player.act(&weak, &view(0), &[100], &[]), Action::Bet(20));
+        assert_eq!(player.act(&strong, &view(0), &[100], &[]), Action::Bet(99));
+        assert_eq!(player.act(&weak, &view(30), &[100], &[]), Action::Fold);
+        assert_eq!(
+            player.act(&strong, &view(10), &[100], &[]),
+            Action::Raise(99)
+        );
+    }
+
+    #[test]
+    fn player_acts_with_their_strategy() {
+        use crate::poker::strategy::ConstantStrategy;
+
+        let cards = hand([0, 14, 28, 42, 6]);
+        let mut player = Player::new(SEED, 100, 0).with_strategy(Box::new(ConstantStrategy(10)));
+        assert_eq!(player.act(&cards, &view(0), &[100], &[]), Action::Bet(10));
+        assert_eq!(player.act(&cards, &view(10), &[100], &[]), Action::Call);
+        assert_eq!(player.act(&cards, &view(11), &[100], &[]), Action::Fold);
+    }
+
+    #[test]
+    fn draw_hand_is_deterministic() {
+        let mut player = Player::new(SEED, 100, 0);
+        let hand_1 = player.draw_hand(b"common random", 5);
+        let hand_2 = player.draw_hand(b"common random", 5);
+        assert_eq!(hand_1.len(), 5);
+        assert_eq!(hand_1, hand_2);
+        assert_eq!(player.hand, hand_1);
+        // every slot is drawn from its own VRF evaluation
+        let draws = &player.publish_draws(b"common random", 1)[0];
+        assert_eq!(draws.len(), 5);
+        assert_ne!(draws[0], draws[1]);
+    }
+
+    #[test]
+    fn redraw_replaces_the_discarded_slots() {
+        let mut player = Player::new(SEED, 100, 0);
+        let dealt = player.draw_hand(b"common random", 5);
+        let drawn = player.redraw(b"draw random", 1, &[1, 3]);
+        assert_eq!(drawn[0], dealt[0]);
+        assert_eq!(drawn[2], dealt[2]);
+        assert_eq!(drawn[4], dealt[4]);
+        // the original draws are published too, right before their replacement
+        let draws = &player.publish_draws(b"common random", 1)[0];
+        assert_eq!(draws.len(), 7);
+        let input = vrf::draw_input(b"draw random", 1, 3);
+        let in_out = vrf::verify(&player.public(), &input, &draws[5]).unwrap();
+        assert_eq!(vrf::card_from_output(&in_out), drawn[3]);
+    }
+
+    #[test]
+    fn commitments_are_keyed_with_the_secret_key() {
+        const OTHER_SEED: &str =
+            "parade method erupt shock bacon wait follow limb onion laugh exhibit whip";
+        let mut board = PublicMessageBoard::new(0);
+        let mut value = |phrase: &str, rng_seed: u64| {
+            let mut player = Player::new(phrase, 100, rng_seed);
+            player.commit(&mut board, 0, 0, 0);
+            player.random_number.unwrap()
+        };
+        // the same seed gives the same values only to the same key
+        assert_eq!(value(SEED, 0), value(SEED, 0));
+        assert_ne!(value(SEED, 0), value(SEED, 1));
+        assert_ne!(value(SEED, 0), value(OTHER_SEED, 0));
+    }
+
+    #[test]
+    fn vrf_keypair_matches_sr25519_address() {
+        use sp_core::crypto::Ss58Codec;
+        use sp_core::sr25519::Pair as Sr25519Pair;
+        use sp_core::Pair;
+
+        let player = Player::new(SEED, 100, 0);
+        let sr25519 = Sr25519Pair::from_phrase(SEED, None).unwrap().0;
+        assert_eq!(player.public().to_bytes(), sr25519.public().0);
+        assert_eq!(player.address(), sr25519.public().to_ss58check());
+    }
+}
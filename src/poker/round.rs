@@ -0,0 +1,1113 @@
+//! The flow of a single round, shared by the live game and the transcript verifier.
+//!
+//! A round is driven by a source of `Messages`: the live game asks its players and signs what they
+//! send into the transcript, while the verifier reads the same messages back from a transcript.
+//! Both run the very same `play`, so every variant of the game is written once, and a transcript
+//! verifies exactly when the game it records followed the rules.
+//!
+//! Every round starts with a commit-reveal between the players, whose common random value is the
+//! input of the VRFs that draw their private cards. In hold'em, the flop, the turn and the river
+//! each need a fresh commit-reveal between the players still in, and the community cards are read
+//! from its common random value, so no single player can choose them or know them in advance.
+//!
+//! In five-card draw, the players still in after the first betting round announce publicly which
+//! slots of their hand they discard. Only once every discard is signed do they run a fresh
+//! commit-reveal, and draw the replacements with their VRF on its common random value, the draw
+//! round and the slot. Nobody knows that value when they choose their discards, so nobody can pick
+//! the slots whose replacements they like. At the showdown they publish the draws of the original
+//! cards as well as the replacements, so everyone can check when each card was drawn.
+//!
+//! In the finite-deck mode, the cards are dealt from a single deck of 52 cards that every player
+//! encrypted and shuffled instead (see `deck`), so no card appears twice in a round. The private
+//! cards, the community cards and the replacements are dealt from the top of the deck in the
+//! order the round needs them. The players still commit to a random value before shuffling, but
+//! only reveal it once everybody shuffled: it gives the challenges of the shuffle proofs (see
+//! `shuffle`), which no shuffler can know in advance, and nobody is dealt a card before every
+//! shuffle is proven.
+
+use curve25519_dalek::ristretto::RistrettoPoint;
+use schnorrkel::PublicKey;
+
+use super::betting::{Action, BettingRound, Blinds};
+use super::card::Card;
+use super::cheating::CheatingEvidence;
+use super::commit;
+use super::deadline::{MissedDeadline, Phase};
+use super::deck::{self, DecryptionProof};
+use super::game::MIN_BET;
+use super::hand::{self, HAND_SIZE};
+use super::ledger::{self, Pot};
+use super::shuffle::{self, Opening, Shuffle};
+use super::vrf::{self, CardDraw};
+use crate::p1_hashing::HashValue;
+
+/// The variants of poker the engine can play
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Variant {
+    /// Every player draws a private hand of five cards, with a single betting round
+    #[default]
+    FiveCard,
+    /// Texas hold'em: every player draws two private hole cards, then five community cards are
+    /// dealt on the flop, the turn and the river, with a betting round on each street
+    Holdem,
+    /// Five-card draw: every player draws a private hand of five cards, bets, discards some of
+    /// them and draws replacements, then bets again
+    Draw,
+}
+
+impl Variant {
+    /// The number of private cards every player draws with their VRF
+    pub fn hole_cards(self) -> usize {
+        match self {
+            Variant::FiveCard | Variant::Draw => HAND_SIZE,
+            Variant::Holdem => 2,
+        }
+    }
+
+    /// The number of times the players can discard and draw replacement cards
+    pub fn draws(self) -> u32 {
+        match self {
+            Variant::Draw => 1,
+            Variant::FiveCard | Variant::Holdem => 0,
+        }
+    }
+
+    /// The number of community cards dealt on each street after the first
+    pub fn community_cards(self) -> &'static [usize] {
+        match self {
+            Variant::FiveCard | Variant::Draw => &[],
+            Variant::Holdem => &[3, 1, 1],
+        }
+    }
+
+    /// The name of the variant, as written in transcripts
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::FiveCard => "five-card",
+            Variant::Holdem => "holdem",
+            Variant::Draw => "draw",
+        }
+    }
+
+    /// The variant with a name. Errors if there is none.
+    pub fn from_name(name: &str) -> Result<Self, ()> {
+        [Variant::FiveCard, Variant::Holdem, Variant::Draw]
+            .into_iter()
+            .find(|variant| variant.name() == name)
+            .ok_or(())
+    }
+}
+
+/// How the cards of a round are dealt
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Dealing {
+    /// Every card is drawn from its own infinite deck, with the VRF of the player it is dealt to
+    /// or from the common random value of a street
+    #[default]
+    Vrf,
+    /// Every card is dealt from a single deck of 52 cards, encrypted and shuffled by every player
+    Deck,
+}
+
+impl Dealing {
+    /// The name of the dealing mode, as written in transcripts
+    pub fn name(self) -> &'static str {
+        match self {
+            Dealing::Vrf => "vrf",
+            Dealing::Deck => "deck",
+        }
+    }
+
+    /// The dealing mode with a name. Errors if there is none.
+    pub fn from_name(name: &str) -> Result<Self, ()> {
+        [Dealing::Vrf, Dealing::Deck]
+            .into_iter()
+            .find(|dealing| dealing.name() == name)
+            .ok_or(())
+    }
+}
+
+/// Everything that happened in a single round of the game
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Round {
+    /// The number of this round, starting from 0
+    pub number: u32,
+    /// The seats of the players who played the round, in order. The contributions and hands are
+    /// in the same order.
+    pub seats: Vec<usize>,
+    /// The seat of the button: the first player to act is the next one after it
+    pub button: usize,
+    /// The forced bets of the round, which are part of the contributions
+    pub blinds: Blinds,
+    /// Every commitment posted to a random value, street by street, in seat order
+    pub commitments: Vec<HashValue>,
+    /// The common random value of the first street, computed from every player's reveal: the VRF
+    /// input of the private cards, or the source of the challenges of the shuffle proofs in the
+    /// finite-deck mode
+    pub common_random: [u8; 32],
+    /// The common random value of every draw round, from the commit-reveal that follows the
+    /// discards: the VRF input of the replacements. Empty in the finite-deck mode.
+    pub draw_randoms: Vec<[u8; 32]>,
+    /// The community cards dealt on the later streets, if the variant has any
+    pub community: Vec<Card>,
+    /// The slots each player discarded in the draws, if the variant has any, in turn order with
+    /// their seat and the draw round
+    pub discards: Vec<(usize, u32, Vec<usize>)>,
+    /// Every betting action, in turn order, with the seat of the player who took it
+    pub actions: Vec<(usize, Action)>,
+    /// How much each player put in the pot
+    pub contributions: Vec<u32>,
+    /// The private cards each player showed at showdown. Players who folded never show their
+    /// cards, and nobody shows their cards if everybody else folded.
+    pub hands: Vec<Option<Vec<Card>>>,
+    /// The draw round each shown card was drawn in, 0 for the cards dealt at the start of the
+    /// round, in the same order as the hands
+    pub drawn_in: Vec<Option<Vec<u32>>>,
+    /// The main pot and the side pots of the round, with the players who won each of them
+    pub pots: Vec<Pot>,
+    /// The player who missed a deadline and stopped the round, if any
+    pub missed: Option<MissedDeadline>,
+}
+
+impl Round {
+    /// The pot of the round, with everything the players put in
+    pub fn pot(&self) -> u32 {
+        self.contributions.iter().sum()
+    }
+
+    /// The seats of the players who won a share of any pot, in seat order
+    pub fn winners(&self) -> Vec<usize> {
+        self.seats
+            .iter()
+            .copied()
+            .filter(|seat| self.pots.iter().any(|pot| pot.winners.contains(seat)))
+            .collect()
+    }
+
+    /// Stop the round when a player missed a deadline: they forfeit what they put in, and every pot
+    /// is split among the other players who did not fold and can win it, without a showdown
+    fn forfeit(mut self, missed: MissedDeadline) -> Self {
+        let folded: Vec<usize> = self
+            .actions
+            .iter()
+            .filter(|(_, action)| *action == Action::Fold)
+            .map(|(seat, _)| *seat)
+            .collect();
+        let remaining: Vec<usize> = self
+            .seats
+            .iter()
+            .copied()
+            .filter(|seat| *seat != missed.seat && !folded.contains(seat))
+            .collect();
+        self.pots = ledger::pots(&self.seats, &self.contributions, &remaining);
+        for pot in self.pots.iter_mut() {
+            pot.winners = pot.eligible.clone();
+        }
+        self.missed = Some(missed);
+        self
+    }
+}
+
+/// The players of a round around the table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Seating {
+    /// The seats of the players, in order
+    pub seats: Vec<usize>,
+    /// The stacks of the players when the round starts, in the same order
+    pub stacks: Vec<u32>,
+    /// The seat of the button
+    pub button: usize,
+}
+
+/// Where the messages of the players come from while a round is played
+pub trait Messages {
+    /// Why the messages can't be read, for instance because a transcript is malformed
+    type Error;
+
+    /// The public key the player in a seat signs and draws cards with
+    fn public(&self, seat: usize) -> PublicKey;
+
+    /// The commitments the player in a seat posted to their random value for a street, which is
+    /// a single one unless they cheat
+    fn commitments(
+        &mut self,
+        seat: usize,
+        number: u32,
+        street: u32,
+    ) -> Result<Vec<HashValue>, Self::Error>;
+
+    /// Whether the player in a seat answers in a phase of a round before its deadline. A player who
+    /// doesn't is never asked for the message, and every other player of the phase, in some seats,
+    /// witnesses the timeout.
+    fn on_time(
+        &mut self,
+        seat: usize,
+        number: u32,
+        phase: Phase,
+        seats: &[usize],
+    ) -> Result<bool, Self::Error>;
+
+    /// The reveal of the commitment of the player in a seat for a street
+    fn reveal(&mut self, seat: usize, number: u32, street: u32) -> Result<String, Self::Error>;
+
+    /// Let the player in a seat draw their private cards on the common random value of the round,
+    /// which only they learn
+    fn deal(&mut self, _seat: usize, _common_random: &[u8; 32], _cards: usize) {}
+
+    /// The deck after the player in a seat encrypted every card of it with their key for the round
+    /// and shuffled it, with the shadow shuffles of its proof, in the finite-deck mode
+    fn shuffle(
+        &mut self,
+        seat: usize,
+        number: u32,
+        deck: &[RistrettoPoint],
+    ) -> Result<Shuffle, Self::Error>;
+
+    /// The answers of the player in a seat to the challenges of their shuffle
+    fn open_shuffle(
+        &mut self,
+        seat: usize,
+        number: u32,
+        challenges: &[bool],
+    ) -> Result<Vec<Opening>, Self::Error>;
+
+    /// The card at a position of the deck after the player in a seat removed their layer of
+    /// encryption from it, with the proof that they removed the layer of the key they shuffled
+    /// with, in the finite-deck mode
+    fn decrypt(
+        &mut self,
+        seat: usize,
+        number: u32,
+        position: usize,
+        point: &RistrettoPoint,
+    ) -> Result<(RistrettoPoint, DecryptionProof), Self::Error>;
+
+    /// Give the player in a seat a private card for a slot of their hand, with only their own
+    /// layer of encryption left on it, so only they learn it
+    fn receive(&mut self, _seat: usize, _slot: usize, _point: &RistrettoPoint) {}
+
+    /// Let the player in a seat draw the replacements of the slots they discarded with their VRF on
+    /// the common random value of the draw round, which only they learn
+    fn redraw(
+        &mut self,
+        _seat: usize,
+        _draw_random: &[u8; 32],
+        _draw_round: u32,
+        _slots: &[usize],
+    ) {
+    }
+
+    /// The action of the player in a seat when it is their turn to bet, which must be allowed by
+    /// the betting round. The seats are the ones of every player of the round.
+    fn action(
+        &mut self,
+        seat: usize,
+        number: u32,
+        seats: &[usize],
+        betting: &BettingRound,
+        community: &[Card],
+    ) -> Result<Action, Self::Error>;
+
+    /// The slots of their hand the player in a seat discards in a draw round, which must be
+    /// distinct slots of the hand in increasing order, and at most some number of them
+    fn discard(
+        &mut self,
+        seat: usize,
+        number: u32,
+        draw_round: u32,
+        hand_size: usize,
+        most: usize,
+    ) -> Result<Vec<usize>, Self::Error>;
+
+    /// The card draws the player in a seat sent to each recipient at the showdown, one for each
+    /// of the given slots and draw rounds
+    fn draws(
+        &mut self,
+        seat: usize,
+        number: u32,
+        common_random: &[u8; 32],
+        recipients: &[usize],
+        cards: &[(usize, u32)],
+    ) -> Result<Vec<Vec<CardDraw>>, Self::Error>;
+}
+
+/// Play a round of a variant between the players around the table, dealing the cards in some mode
+/// and reading their messages from a source. Every player posts the ante, and the first two after
+/// the button the blinds. Nobody can put in more than their stack over the whole round.
+///
+/// Returns what happened in the round, or the evidence against the first player caught cheating,
+/// which stops the round. A player who misses a deadline stops the round too, and the pot goes to
+/// the other players still in. Errors only if the messages can't be read.
+pub fn play<M: Messages>(
+    messages: &mut M,
+    game_id: u64,
+    variant: Variant,
+    dealing: Dealing,
+    blinds: Blinds,
+    number: u32,
+    seating: &Seating,
+) -> Result<Result<Round, CheatingEvidence>, M::Error> {
+    let seats = &seating.seats;
+    let mut round = Round {
+        number,
+        seats: seats.to_vec(),
+        button: seating.button,
+        blinds,
+        commitments: Vec::new(),
+        common_random: [0u8; 32],
+        draw_randoms: Vec::new(),
+        community: Vec::new(),
+        discards: Vec::new(),
+        actions: Vec::new(),
+        // the antes are dead money, which counts towards no bet
+        contributions: seating
+            .stacks
+            .iter()
+            .map(|stack| blinds.ante.min(*stack))
+            .collect(),
+        hands: vec![None; seats.len()],
+        drawn_in: vec![None; seats.len()],
+        pots: Vec::new(),
+        missed: None,
+    };
+    match run(
+        messages,
+        game_id,
+        variant,
+        dealing,
+        &seating.stacks,
+        &mut round,
+    )? {
+        Ok(()) => Ok(Ok(round)),
+        Err(Stop::Cheated(evidence)) => Ok(Err(evidence)),
+        Err(Stop::Missed(missed)) => Ok(Ok(round.forfeit(missed))),
+    }
+}
+
+/// Why a round stopped before its end
+enum Stop {
+    /// A player was caught cheating
+    Cheated(CheatingEvidence),
+    /// A player missed a deadline
+    Missed(MissedDeadline),
+}
+
+impl From<CheatingEvidence> for Stop {
+    fn from(evidence: CheatingEvidence) -> Self {
+        Stop::Cheated(evidence)
+    }
+}
+
+/// Run a round from the deal to the showdown, until a player is caught cheating or misses a
+/// deadline. The stacks are the ones of every player of the round.
+fn run<M: Messages>(
+    messages: &mut M,
+    game_id: u64,
+    variant: Variant,
+    dealing: Dealing,
+    stacks: &[u32],
+    round: &mut Round,
+) -> Result<Result<(), Stop>, M::Error> {
+    let number = round.number;
+    let seats = &round.seats.clone();
+    let hole_cards = variant.hole_cards();
+    let mut dealer = Dealer::new(seats.len());
+
+    match dealing {
+        // the first street gives the VRF input of the private cards, which each player draws alone
+        Dealing::Vrf => {
+            round.common_random = match street_random(messages, game_id, round, 0, seats)? {
+                Ok(random) => random,
+                Err(stop) => return Ok(Err(stop)),
+            };
+            for seat in seats {
+                messages.deal(*seat, &round.common_random, hole_cards);
+            }
+        }
+        // every player shuffles the deck in seat order between the commit and the reveal of the
+        // first street, and proves their shuffle on its common random value. Only then are the
+        // private cards dealt from the deck.
+        Dealing::Deck => {
+            let commitments = match commit_street(messages, round, 0, seats)? {
+                Ok(commitments) => commitments,
+                Err(stop) => return Ok(Err(stop)),
+            };
+            let mut shuffles = Vec::new();
+            for seat in seats {
+                let shuffle = messages.shuffle(*seat, number, &dealer.deck)?;
+                let received = std::mem::replace(&mut dealer.deck, shuffle.deck.clone());
+                dealer.keys.push(shuffle.key);
+                shuffles.push((received, shuffle));
+            }
+            round.common_random =
+                match reveal_street(messages, game_id, number, 0, seats, &commitments)? {
+                    Ok(random) => random,
+                    Err(stop) => return Ok(Err(stop)),
+                };
+            for (seat, (received, shuffle)) in seats.iter().zip(&shuffles) {
+                let challenges = shuffle::challenges(&round.common_random, *seat);
+                let openings = messages.open_shuffle(*seat, number, &challenges)?;
+                if let Err(challenge) = shuffle::verify(received, shuffle, &challenges, &openings) {
+                    return Ok(Err(CheatingEvidence::BadShuffle {
+                        seat: *seat,
+                        round: number,
+                        challenge,
+                    }
+                    .into()));
+                }
+            }
+            for i in 0..seats.len() {
+                for slot in 0..hole_cards {
+                    if let Err(evidence) = dealer.deal_private(messages, number, seats, i, slot)? {
+                        return Ok(Err(evidence.into()));
+                    }
+                }
+            }
+        }
+    }
+
+    // each street is bet on before the community cards of the next one are dealt, from a fresh
+    // commit-reveal between the players still in, or from the deck
+    let mut remaining = bet(messages, round, seats, stacks, true)?;
+    for (street, cards) in (1..).zip(variant.community_cards()) {
+        if remaining.len() < 2 {
+            break;
+        }
+        let dealt = round.community.len();
+        match dealing {
+            Dealing::Vrf => {
+                let random = match street_random(messages, game_id, round, street, &remaining)? {
+                    Ok(random) => random,
+                    Err(stop) => return Ok(Err(stop)),
+                };
+                round
+                    .community
+                    .extend((dealt..dealt + cards).map(|slot| community_card(&random, slot)));
+            }
+            Dealing::Deck => {
+                for _ in 0..*cards {
+                    match dealer.deal_public(messages, number, seats)? {
+                        Ok(card) => round.community.push(card),
+                        Err(evidence) => return Ok(Err(evidence.into())),
+                    }
+                }
+            }
+        }
+        remaining = bet(messages, round, &remaining, stacks, false)?;
+    }
+
+    // in a draw, the players still in announce the slots they discard in turn order, and bet
+    // again on their new hands. Every slot remembers the draw rounds it was drawn in.
+    let mut drawn = vec![vec![vec![0]; hole_cards]; seats.len()];
+    for draw_round in 1..=variant.draws() {
+        if remaining.len() < 2 {
+            break;
+        }
+        let discarding = round.discards.len();
+        for seat in turn_order(round.button, &remaining) {
+            // the replacements of a finite deck can't outnumber the cards left in it
+            let most = match dealing {
+                Dealing::Vrf => hole_cards,
+                Dealing::Deck => hole_cards.min(dealer.left()),
+            };
+            let slots = messages.discard(seat, number, draw_round, hole_cards, most)?;
+            let i = seats.iter().position(|s| *s == seat).unwrap();
+            if dealing == Dealing::Deck {
+                for slot in &slots {
+                    if let Err(evidence) = dealer.deal_private(messages, number, seats, i, *slot)? {
+                        return Ok(Err(evidence.into()));
+                    }
+                }
+            }
+            for slot in &slots {
+                drawn[i][*slot].push(draw_round);
+            }
+            round.discards.push((seat, draw_round, slots));
+        }
+        // the VRF replacements are drawn on a street of their own, after the community streets,
+        // whose commit-reveal only starts once every discard is known
+        if dealing == Dealing::Vrf {
+            let street = variant.community_cards().len() as u32 + draw_round;
+            let random = match street_random(messages, game_id, round, street, &remaining)? {
+                Ok(random) => random,
+                Err(stop) => return Ok(Err(stop)),
+            };
+            for (seat, _, slots) in &round.discards[discarding..] {
+                messages.redraw(*seat, &random, draw_round, slots);
+            }
+            round.draw_randoms.push(random);
+        }
+        remaining = bet(messages, round, &remaining, stacks, false)?;
+    }
+
+    // the players left now show their cards: they exchange the VRF outputs and proofs of every
+    // card they drew and verify them before reading the cards, or remove the last layer of
+    // encryption from their cards of the deck
+    if remaining.len() > 1 {
+        for (i, seat) in seats.iter().enumerate() {
+            if !remaining.contains(seat) {
+                continue;
+            }
+            if let Err(missed) = deadline(messages, *seat, number, Phase::Showdown, &remaining)? {
+                return Ok(Err(missed));
+            }
+            let hand = match dealing {
+                Dealing::Vrf => {
+                    let cards: Vec<(usize, u32)> = drawn[i]
+                        .iter()
+                        .enumerate()
+                        .flat_map(|(slot, rounds)| rounds.iter().map(move |r| (slot, *r)))
+                        .collect();
+                    let recipients: Vec<usize> =
+                        seats.iter().copied().filter(|s| s != seat).collect();
+                    let draws =
+                        messages.draws(*seat, number, &round.common_random, &recipients, &cards)?;
+                    let public = messages.public(*seat);
+                    let randoms = [&[round.common_random][..], &round.draw_randoms].concat();
+                    let shown = match check_draws(*seat, number, &public, &randoms, &cards, &draws)
+                    {
+                        Ok(shown) => shown,
+                        Err(evidence) => return Ok(Err(evidence.into())),
+                    };
+                    // the hand holds the last card drawn in every slot
+                    let mut hand = Vec::new();
+                    for (card, (slot, _)) in shown.into_iter().zip(cards) {
+                        match slot < hand.len() {
+                            true => hand[slot] = card,
+                            false => hand.push(card),
+                        }
+                    }
+                    hand
+                }
+                Dealing::Deck => {
+                    let mut hand = Vec::new();
+                    for slot in 0..hole_cards {
+                        match dealer.show(messages, number, seats, i, slot)? {
+                            Ok(card) => hand.push(card),
+                            Err(evidence) => return Ok(Err(evidence.into())),
+                        }
+                    }
+                    hand
+                }
+            };
+            round.hands[i] = Some(hand);
+            round.drawn_in[i] = Some(drawn[i].iter().map(|r| *r.last().unwrap()).collect());
+        }
+    }
+
+    // every pot goes to the best hand among the players who can win it, or to the last player if
+    // everybody else folded, without a showdown
+    round.pots = ledger::pots(seats, &round.contributions, &remaining);
+    for pot in round.pots.iter_mut() {
+        pot.winners = winners(seats, &pot.eligible, &round.hands, &round.community);
+    }
+    Ok(Ok(()))
+}
+
+/// The finite deck of a round, and the cards dealt from it so far
+struct Dealer {
+    /// The deck, encrypted and shuffled by every player once they have all shuffled it
+    deck: Vec<RistrettoPoint>,
+    /// The public keys every player of the round shuffled the deck with, in seat order
+    keys: Vec<RistrettoPoint>,
+    /// How many cards were dealt from the top of the deck
+    dealt: usize,
+    /// The private cards of every player of the round by slot: their position in the deck, and
+    /// the point with only the layer of the player left on it
+    held: Vec<Vec<(usize, RistrettoPoint)>>,
+    /// The cards shown so far, which can't show up again
+    shown: Vec<Card>,
+}
+
+impl Dealer {
+    /// A dealer with the deck in order, for a round between some number of players
+    fn new(players: usize) -> Self {
+        Dealer {
+            deck: deck::new_deck(),
+            keys: Vec::new(),
+            dealt: 0,
+            held: vec![Vec::new(); players],
+            shown: Vec::new(),
+        }
+    }
+
+    /// The number of cards left in the deck
+    fn left(&self) -> usize {
+        self.deck.len() - self.dealt
+    }
+
+    /// Deal the card on top of the deck to a slot of the hand of the i-th player of the round:
+    /// every other player removes their layer in seat order, and the player receives the card
+    /// with only their own layer left. Errors with evidence against a player whose decryption
+    /// proof does not hold.
+    fn deal_private<M: Messages>(
+        &mut self,
+        messages: &mut M,
+        number: u32,
+        seats: &[usize],
+        i: usize,
+        slot: usize,
+    ) -> Result<Result<(), CheatingEvidence>, M::Error> {
+        let position = self.dealt;
+        self.dealt += 1;
+        let mut point = self.deck[position];
+        for j in (0..seats.len()).filter(|j| *j != i) {
+            point = match self.decrypt(messages, number, seats, j, position, &point)? {
+                Ok(point) => point,
+                Err(evidence) => return Ok(Err(evidence)),
+            };
+        }
+        messages.receive(seats[i], slot, &point);
+        let held = &mut self.held[i];
+        match slot < held.len() {
+            true => held[slot] = (position, point),
+            false => held.push((position, point)),
+        }
+        Ok(Ok(()))
+    }
+
+    /// Deal the card on top of the deck face up: every player of the round removes their layer in
+    /// seat order
+    fn deal_public<M: Messages>(
+        &mut self,
+        messages: &mut M,
+        number: u32,
+        seats: &[usize],
+    ) -> Result<Result<Card, CheatingEvidence>, M::Error> {
+        let position = self.dealt;
+        self.dealt += 1;
+        let mut point = self.deck[position];
+        for j in 0..seats.len() {
+            point = match self.decrypt(messages, number, seats, j, position, &point)? {
+                Ok(point) => point,
+                Err(evidence) => return Ok(Err(evidence)),
+            };
+        }
+        Ok(self.read(*seats.last().unwrap(), number, position, &point))
+    }
+
+    /// Show the private card in a slot of the hand of the i-th player of the round, who removes
+    /// their own layer in public
+    fn show<M: Messages>(
+        &mut self,
+        messages: &mut M,
+        number: u32,
+        seats: &[usize],
+        i: usize,
+        slot: usize,
+    ) -> Result<Result<Card, CheatingEvidence>, M::Error> {
+        let (position, point) = self.held[i][slot];
+        let point = match self.decrypt(messages, number, seats, i, position, &point)? {
+            Ok(point) => point,
+            Err(evidence) => return Ok(Err(evidence)),
+        };
+        Ok(self.read(seats[i], number, position, &point))
+    }
+
+    /// Have the j-th player of the round remove their layer from the card at a position of the
+    /// deck, and check their proof against the public key they shuffled with. Errors with
+    /// evidence against them if it does not hold.
+    fn decrypt<M: Messages>(
+        &self,
+        messages: &mut M,
+        number: u32,
+        seats: &[usize],
+        j: usize,
+        position: usize,
+        point: &RistrettoPoint,
+    ) -> Result<Result<RistrettoPoint, CheatingEvidence>, M::Error> {
+        let (decrypted, proof) = messages.decrypt(seats[j], number, position, point)?;
+        match deck::verify_decryption(&self.keys[j], point, &decrypted, &proof) {
+            Ok(()) => Ok(Ok(decrypted)),
+            Err(()) => Ok(Err(CheatingEvidence::BadDecryption {
+                seat: seats[j],
+                round: number,
+                position,
+                point: Box::new(decrypted),
+            })),
+        }
+    }
+
+    /// The card of a point with every layer removed, which must be a card not shown before in the
+    /// round. Errors with evidence against the player who removed the last layer otherwise, which
+    /// only happens if a shuffle or a decryption got through its proof.
+    fn read(
+        &mut self,
+        seat: usize,
+        number: u32,
+        position: usize,
+        point: &RistrettoPoint,
+    ) -> Result<Card, CheatingEvidence> {
+        match deck::card_from_point(point) {
+            Some(card) if !self.shown.contains(&card) => {
+                self.shown.push(card);
+                Ok(card)
+            }
+            _ => Err(CheatingEvidence::BadDecryption {
+                seat,
+                round: number,
+                position,
+                point: Box::new(*point),
+            }),
+        }
+    }
+}
+
+/// Run the commit-reveal of a street between the players in some seats, returning the common
+/// random value of the street, or the evidence against a player who double committed or whose
+/// reveal does not open their commitment, or the player who missed a deadline
+fn street_random<M: Messages>(
+    messages: &mut M,
+    game_id: u64,
+    round: &mut Round,
+    street: u32,
+    seats: &[usize],
+) -> Result<Result<[u8; 32], Stop>, M::Error> {
+    let commitments = match commit_street(messages, round, street, seats)? {
+        Ok(commitments) => commitments,
+        Err(stop) => return Ok(Err(stop)),
+    };
+    reveal_street(messages, game_id, round.number, street, seats, &commitments)
+}
+
+/// Collect the commitments of the players in some seats to their random value for a street,
+/// or the evidence against a player who double committed, or the player who missed the deadline
+fn commit_street<M: Messages>(
+    messages: &mut M,
+    round: &mut Round,
+    street: u32,
+    seats: &[usize],
+) -> Result<Result<Vec<HashValue>, Stop>, M::Error> {
+    let number = round.number;
+    let mut commitments = Vec::new();
+    for seat in seats {
+        if let Err(missed) = deadline(messages, *seat, number, Phase::Commit, seats)? {
+            return Ok(Err(missed));
+        }
+        let posted = messages.commitments(*seat, number, street)?;
+        if posted.len() > 1 {
+            return Ok(Err(CheatingEvidence::DoubleCommitment {
+                seat: *seat,
+                round: number,
+                first: posted[0],
+                second: posted[1],
+            }
+            .into()));
+        }
+        commitments.push(posted[0]);
+    }
+    round.commitments.extend(&commitments);
+    Ok(Ok(commitments))
+}
+
+/// Collect the reveals of the commitments of the players in some seats for a street, returning
+/// the common random value of the street, or the evidence against a player whose reveal does not
+/// open their commitment, or the player who missed the deadline
+fn reveal_street<M: Messages>(
+    messages: &mut M,
+    game_id: u64,
+    number: u32,
+    street: u32,
+    seats: &[usize],
+    commitments: &[HashValue],
+) -> Result<Result<[u8; 32], Stop>, M::Error> {
+    let mut revealed = Vec::new();
+    for (seat, commitment) in seats.iter().zip(commitments) {
+        if let Err(missed) = deadline(messages, *seat, number, Phase::Reveal, seats)? {
+            return Ok(Err(missed));
+        }
+        let reveal = messages.reveal(*seat, number, street)?;
+        let public = messages.public(*seat);
+        match commit::check_reveal(*commitment, &reveal, &public, game_id, number, street) {
+            Ok(value) => revealed.push(value),
+            Err(_) => {
+                return Ok(Err(CheatingEvidence::BadReveal {
+                    seat: *seat,
+                    commitment: *commitment,
+                    reveal,
+                }
+                .into()))
+            }
+        }
+    }
+    Ok(Ok(commit::combine_randomness(
+        &revealed, game_id, number, street,
+    )))
+}
+
+/// Check that the player in a seat answers in a phase between the players in some seats before its
+/// deadline, or stop the round
+fn deadline<M: Messages>(
+    messages: &mut M,
+    seat: usize,
+    number: u32,
+    phase: Phase,
+    seats: &[usize],
+) -> Result<Result<(), Stop>, M::Error> {
+    match messages.on_time(seat, number, phase, seats)? {
+        true => Ok(Ok(())),
+        false => Ok(Err(Stop::Missed(MissedDeadline { seat, phase }))),
+    }
+}
+
+/// Run a betting round between the players in some seats, who can put in whatever is left of their
+/// stack after the previous streets. The stacks are the ones of every player of the round, and the
+/// blinds open the first betting round. Returns the seats of the players who did not fold.
+fn bet<M: Messages>(
+    messages: &mut M,
+    round: &mut Round,
+    seats: &[usize],
+    stacks: &[u32],
+    first: bool,
+) -> Result<Vec<usize>, M::Error> {
+    let position = |seat: usize| round.seats.iter().position(|s| *s == seat).unwrap();
+    let order = turn_order(round.button, seats);
+    let left = order
+        .iter()
+        .map(|seat| stacks[position(*seat)] - round.contributions[position(*seat)])
+        .collect();
+
+    let mut betting = BettingRound::new(order, MIN_BET.max(round.blinds.big), left);
+    if first && round.blinds.big > 0 {
+        betting.post_blinds(&round.blinds);
+    }
+    while let Some(seat) = betting.next_to_act() {
+        let action =
+            messages.action(seat, round.number, &round.seats, &betting, &round.community)?;
+        betting
+            .act(action)
+            .expect("the messages only hold allowed actions");
+    }
+    for seat in seats {
+        round.contributions[position(*seat)] += betting.contribution(*seat);
+    }
+    round.actions.extend(betting.actions.iter().copied());
+    let remaining = betting.remaining();
+    Ok(seats
+        .iter()
+        .copied()
+        .filter(|seat| remaining.contains(seat))
+        .collect())
+}
+
+/// The community card dealt in some slot of the board, from the common random value of the
+/// street it is dealt on
+pub fn community_card(street_random: &[u8; 32], slot: usize) -> Card {
+    Card::from_random_bytes(&vrf::slot_input(street_random, slot))
+}
+
+/// The seats of a round in turn order, starting from the next seat after the button
+pub fn turn_order(button: usize, seats: &[usize]) -> Vec<usize> {
+    let first = seats.iter().position(|seat| *seat > button).unwrap_or(0);
+    [&seats[first..], &seats[..first]].concat()
+}
+
+/// The seat of the button in a round between the players in some seats: the next seat after the
+/// button of the previous round, or the last seat in the first round, so the first seat acts first
+pub fn next_button(previous: Option<usize>, seats: &[usize]) -> usize {
+    match previous {
+        Some(previous) => turn_order(previous, seats)[0],
+        None => *seats.last().unwrap(),
+    }
+}
+
+/// The winners of a pot: the players at the showdown who can win it with the best hand made of
+/// their cards and the community cards, or the last player left if everybody else folded. The
+/// hands are in the same order as the seats.
+pub fn winners(
+    seats: &[usize],
+    showdown: &[usize],
+    hands: &[Option<Vec<Card>>],
+    community: &[Card],
+) -> Vec<usize> {
+    let values: Vec<_> = seats
+        .iter()
+        .zip(hands)
+        .map(|(seat, shown)| match showdown.contains(seat) {
+            true => shown
+                .as_ref()
+                .map(|cards| hand::best_hand(&[cards.as_slice(), community].concat())),
+            false => None,
+        })
+        .collect();
+    let best = values.iter().flatten().max().cloned();
+    seats
+        .iter()
+        .zip(values)
+        .filter(|(seat, value)| showdown.contains(seat) && *value == best)
+        .map(|(seat, _)| *seat)
+        .collect()
+}
+
+/// Check the card draws a player sent to each of the other players of a round, one for each of
+/// the given slots and draw rounds, returning the cards. The random values are the common random
+/// value of the round followed by the one of every draw round. Errors with evidence if they sent
+/// different draws to different players, or if a VRF proof does not verify on the random value of
+/// its draw round followed by the slot and the draw round.
+pub fn check_draws(
+    seat: usize,
+    number: u32,
+    public: &PublicKey,
+    randoms: &[[u8; 32]],
+    cards: &[(usize, u32)],
+    draws: &[Vec<CardDraw>],
+) -> Result<Vec<Card>, CheatingEvidence> {
+    let sent = &draws[0];
+    if let Some(other) = draws.iter().find(|other| *other != sent) {
+        let slot = (0..sent.len())
+            .find(|slot| other.get(*slot) != sent.get(*slot))
+            .unwrap_or(0);
+        return Err(CheatingEvidence::Equivocation {
+            seat,
+            round: number,
+            first: Box::new(sent[slot].clone()),
+            second: Box::new(other[slot].clone()),
+        });
+    }
+    let mut shown = Vec::new();
+    for ((slot, draw_round), draw) in cards.iter().zip(sent) {
+        let input = vrf::draw_input(&randoms[*draw_round as usize], *draw_round, *slot);
+        match vrf::verify(public, &input, draw) {
+            Ok(in_out) => shown.push(vrf::card_from_output(&in_out)),
+            Err(_) => {
+                return Err(CheatingEvidence::InvalidVrfProof {
+                    seat,
+                    input,
+                    draw: Box::new(draw.clone()),
+                })
+            }
+        }
+    }
+    Ok(shown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::OsRng;
+    use schnorrkel::Keypair;
+
+    fn cards(indices: &[u8]) -> Vec<Card> {
+        indices
+            .iter()
+            .map(|index| Card::from_index(*index).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn variants_have_names() {
+        for variant in [Variant::FiveCard, Variant::Holdem, Variant::Draw] {
+            assert_eq!(Variant::from_name(variant.name()), Ok(variant));
+        }
+        assert!(Variant::from_name("omaha").is_err());
+        // hold'em deals the flop, the turn and the river, for seven cards per player
+        let holdem = Variant::Holdem;
+        let community: usize = holdem.community_cards().iter().sum();
+        assert_eq!(holdem.hole_cards() + community, 7);
+    }
+
+    #[test]
+    fn dealing_modes_have_names() {
+        for dealing in [Dealing::Vrf, Dealing::Deck] {
+            assert_eq!(Dealing::from_name(dealing.name()), Ok(dealing));
+        }
+        assert!(Dealing::from_name("shoe").is_err());
+    }
+
+    #[test]
+    fn button_moves_around_the_table() {
+        assert_eq!(turn_order(4, &[0, 2, 5]), vec![5, 0, 2]);
+        assert_eq!(turn_order(5, &[0, 2, 5]), vec![0, 2, 5]);
+        assert_eq!(next_button(None, &[0, 2, 5]), 5);
+        assert_eq!(next_button(Some(5), &[0, 2, 5]), 0);
+        // the button skips the seats of the players who are out
+        assert_eq!(next_button(Some(0), &[0, 2, 5]), 2);
+        assert_eq!(next_button(Some(3), &[0, 2, 5]), 5);
+    }
+
+    #[test]
+    fn community_cards_count_at_the_showdown() {
+        // a suited 2 and 3 against a suited ace and king, with no community cards
+        let hands = vec![Some(cards(&[0, 1])), Some(cards(&[12, 11]))];
+        assert_eq!(winners(&[0, 1], &[0, 1], &hands, &[]), vec![1]);
+        // with the 4, 5 and 6 of the same suit on the board, the 2 and 3 make a straight flush and
+        // the ace and king only a flush
+        let board = cards(&[2, 3, 4, 20, 30]);
+        assert_eq!(winners(&[0, 1], &[0, 1], &hands, &board), vec![0]);
+        // a player who folded never wins
+        assert_eq!(
+            winners(&[0, 1], &[1], &[None, hands[1].clone()], &board),
+            vec![1]
+        );
+        // and a side pot goes to the best hand among the players who can win it
+        assert_eq!(winners(&[0, 1], &[1], &hands, &board), vec![1]);
+    }
+
+    #[test]
+    fn tied_hands_share_the_pot() {
+        // a royal flush on the board plays for both players, whatever their cards, and the pot
+        // is theirs together instead of going to the last seat
+        let hands = vec![Some(cards(&[13, 14])), Some(cards(&[26, 27]))];
+        let board = cards(&[8, 9, 10, 11, 12]);
+        assert_eq!(winners(&[0, 1], &[0, 1], &hands, &board), vec![0, 1]);
+    }
+
+    #[test]
+    fn replacements_are_checked_on_their_draw_round() {
+        let keypair = Keypair::generate_with(OsRng);
+        let randoms = [[3u8; 32], [4u8; 32]];
+        let (_, dealt) = vrf::draw(&keypair, &vrf::draw_input(&randoms[0], 0, 1));
+        let (in_out, replacement) = vrf::draw(&keypair, &vrf::draw_input(&randoms[1], 1, 1));
+        let cards = [(1, 0), (1, 1)];
+        let sent = vec![dealt.clone(), replacement.clone()];
+        let shown = check_draws(0, 0, &keypair.public, &randoms, &cards, &[sent]).unwrap();
+        assert_eq!(shown[1], vrf::card_from_output(&in_out));
+        // a replacement drawn on the common random value of the round, which everybody knew
+        // before the discards, does not count
+        let (_, early) = vrf::draw(&keypair, &vrf::draw_input(&randoms[0], 1, 1));
+        let precomputed = vec![dealt.clone(), early];
+        assert!(matches!(
+            check_draws(0, 0, &keypair.public, &randoms, &cards, &[precomputed]),
+            Err(CheatingEvidence::InvalidVrfProof { .. })
+        ));
+        // and a replacement can't be passed off as the card dealt in its slot
+        let swapped = vec![replacement, dealt];
+        assert!(matches!(
+            check_draws(0, 0, &keypair.public, &randoms, &cards, &[swapped]),
+            Err(CheatingEvidence::InvalidVrfProof { .. })
+        ));
+    }
+
+    #[test]
+    fn cards_of_the_deck_show_up_once() {
+        let mut dealer = Dealer::new(2);
+        let ace = Card::from_index(12).unwrap();
+        let point = deck::card_point(ace);
+        assert_eq!(dealer.read(0, 0, 0, &point), Ok(ace));
+        // neither a card shown before nor a point that is not a card can be dealt
+        assert!(matches!(
+            dealer.read(1, 0, 1, &point),
+            Err(CheatingEvidence::BadDecryption {
+                seat: 1,
+                position: 1,
+                ..
+            })
+        ));
+        assert!(dealer.read(1, 0, 2, &(point + point)).is_err());
+    }
+
+    #[test]
+    fn community_cards_depend_on_the_street_random() {
+        let board: Vec<Card> = (0..5)
+            .map(|slot| community_card(&[1u8; 32], slot))
+            .collect();
+        let again: Vec<Card> = (0..5)
+            .map(|slot| community_card(&[1u8; 32], slot))
+            .collect();
+        let other: Vec<Card> = (0..5)
+            .map(|slot| community_card(&[2u8; 32], slot))
+            .collect();
+        assert_eq!(board, again);
+        assert_ne!(board, other);
+    }
+}
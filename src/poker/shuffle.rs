@@ -0,0 +1,432 @@
+//! Proofs that a shuffle of the finite deck is honest.
+//!
+//! A player shuffles the deck by multiplying every card by their secret key and permuting the
+//! cards. A cheater could instead swap a card for another one, or duplicate a card, so every
+//! shuffle comes with a cut-and-choose proof that the shuffled deck is a permutation of the deck
+//! they received under a single key:
+//!
+//! 1. Along with the shuffled deck and the public key of their key, the shuffler publishes a number
+//!    of shadow shuffles of the deck they received, each with a fresh key and a fresh permutation,
+//!    and the public key of every fresh key.
+//! 2. Once every player has shuffled, a challenge bit is read for every shadow from a random value
+//!    the shuffler could not know when they published it.
+//! 3. For a 0, the shuffler opens the key and permutation from the deck they received to the
+//!    shadow. For a 1, they open the key and permutation from the shadow to the shuffled deck,
+//!    which are the quotient of the keys and the composition of the permutations. Either way the
+//!    opened key must also take the public key of the source to the public key of the target, so
+//!    the public key of the shuffler is the one of the key they shuffled with, and their
+//!    decryption proofs (see `deck`) are bound to their shuffle.
+//!
+//! Each opening alone reveals nothing about the shuffle, since the shadow is random. A dishonest
+//! shuffle can answer at most one of the two challenges of a shadow, so it is caught unless
+//! every challenge picks the one it can answer, which happens once in 2^128 shuffles. At least one
+//! challenge is always a 1, or the shuffled deck would never be checked at all.
+//!
+//! The cards of an opening are checked all at once, on a combination of the cards with weights
+//! hashed from the whole shuffle and its openings, so anybody verifying the same shuffle reaches
+//! the same verdict. A shuffle with a key of zero or a card at the identity point is rejected
+//! outright, as it would erase the cards.
+
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::{Identity, VartimeMultiscalarMul};
+use rand::{seq::SliceRandom, Rng};
+use sp_core::blake2_256;
+
+use super::deck::{random_scalar, scalar_from_hex, scalar_to_hex};
+
+/// The number of shadow shuffles in every shuffle proof, each of which catches a dishonest
+/// shuffle with probability 1/2
+pub const SHUFFLE_CHALLENGES: usize = 128;
+
+/// The domain of the hash from the common random value to the challenges
+const CHALLENGE_CONTEXT: &[u8] = b"finite deck poker shuffle";
+
+/// The domain of the hash from a shuffle and its openings to the weights the cards are combined
+/// with
+const WEIGHTS_CONTEXT: &[u8] = b"finite deck poker shuffle weights";
+
+/// A shuffled deck as published by the shuffler, with the shadow shuffles of its proof
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Shuffle {
+    /// The public key of the key of the shuffler, which their decryptions are proven against
+    pub key: RistrettoPoint,
+    /// The deck the shuffler received, encrypted with their key and permuted
+    pub deck: Vec<RistrettoPoint>,
+    /// The public keys of the fresh keys of the shadow shuffles
+    pub shadow_keys: Vec<RistrettoPoint>,
+    /// The deck the shuffler received, encrypted with a fresh key and permuted with a fresh
+    /// permutation, once for every challenge
+    pub shadows: Vec<Vec<RistrettoPoint>>,
+}
+
+/// The answer to the challenge of a shadow shuffle: the key and the permutation from the deck the
+/// shuffler received to the shadow for a 0, or from the shadow to the shuffled deck for a 1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Opening {
+    pub key: Scalar,
+    /// The position in the source deck of every card of the target deck
+    pub permutation: Vec<usize>,
+}
+
+/// What the shuffler keeps to answer the challenges of their shuffle
+#[derive(Clone, Debug)]
+pub struct ShuffleSecret {
+    key: Scalar,
+    permutation: Vec<usize>,
+    shadows: Vec<(Scalar, Vec<usize>)>,
+}
+
+/// Encrypt every card of a deck with a key and shuffle it, with the shadow shuffles that prove it
+pub fn shuffle(
+    key: Scalar,
+    deck: &[RistrettoPoint],
+    rng: &mut impl Rng,
+) -> (Shuffle, ShuffleSecret) {
+    let (shuffled, permutation) = permute(key, deck, rng);
+    let mut shadow_keys = Vec::new();
+    let mut shadows = Vec::new();
+    let mut secrets = Vec::new();
+    for _ in 0..SHUFFLE_CHALLENGES {
+        let shadow_key = random_scalar(rng);
+        let (shadow, shadow_permutation) = permute(shadow_key, deck, rng);
+        shadow_keys.push(shadow_key * RISTRETTO_BASEPOINT_POINT);
+        shadows.push(shadow);
+        secrets.push((shadow_key, shadow_permutation));
+    }
+    let shuffle = Shuffle {
+        key: key * RISTRETTO_BASEPOINT_POINT,
+        deck: shuffled,
+        shadow_keys,
+        shadows,
+    };
+    let secret = ShuffleSecret {
+        key,
+        permutation,
+        shadows: secrets,
+    };
+    (shuffle, secret)
+}
+
+impl ShuffleSecret {
+    /// Answer the challenges of our shuffle
+    pub fn open(&self, challenges: &[bool]) -> Vec<Opening> {
+        self.shadows
+            .iter()
+            .zip(challenges)
+            .map(
+                |((shadow_key, shadow_permutation), challenge)| match challenge {
+                    false => Opening {
+                        key: *shadow_key,
+                        permutation: shadow_permutation.clone(),
+                    },
+                    true => {
+                        // the card at position i of the shuffled deck is at this position of the
+                        // shadow
+                        let mut inverse = vec![0; shadow_permutation.len()];
+                        for (position, source) in shadow_permutation.iter().enumerate() {
+                            inverse[*source] = position;
+                        }
+                        Opening {
+                            key: self.key * shadow_key.invert(),
+                            permutation: self.permutation.iter().map(|p| inverse[*p]).collect(),
+                        }
+                    }
+                },
+            )
+            .collect()
+    }
+}
+
+/// The challenges of the shuffle of the player in a seat, read from a random value that nobody
+/// knew when the shuffles were published. The last one is a 1 if none of the others is.
+pub fn challenges(random: &[u8; 32], seat: usize) -> Vec<bool> {
+    let mut data = CHALLENGE_CONTEXT.to_vec();
+    data.extend_from_slice(random);
+    data.extend_from_slice(&(seat as u32).to_le_bytes());
+    let bits = blake2_256(&data);
+    let mut challenges: Vec<bool> = (0..SHUFFLE_CHALLENGES)
+        .map(|i| bits[i / 8] & (1 << (i % 8)) != 0)
+        .collect();
+    if !challenges.contains(&true) {
+        challenges[SHUFFLE_CHALLENGES - 1] = true;
+    }
+    challenges
+}
+
+/// Verify the openings of a shuffle of a deck for some challenges. Errors with the index of the
+/// first challenge whose opening does not hold, or with 0 if the shuffle or the challenges are
+/// malformed, since none of the openings can hold then.
+pub fn verify(
+    deck: &[RistrettoPoint],
+    shuffle: &Shuffle,
+    challenges: &[bool],
+    openings: &[Opening],
+) -> Result<(), usize> {
+    let identity = RistrettoPoint::identity();
+    let well_formed = challenges.len() == SHUFFLE_CHALLENGES
+        && challenges.contains(&true)
+        && shuffle.deck.len() == deck.len()
+        && shuffle.shadows.len() == SHUFFLE_CHALLENGES
+        && shuffle.shadow_keys.len() == SHUFFLE_CHALLENGES
+        && shuffle
+            .shadows
+            .iter()
+            .all(|shadow| shadow.len() == deck.len())
+        && !deck.contains(&identity)
+        && shuffle.key != identity
+        && !shuffle.deck.contains(&identity)
+        && !shuffle.shadow_keys.contains(&identity)
+        && !shuffle
+            .shadows
+            .iter()
+            .any(|shadow| shadow.contains(&identity));
+    if !well_formed {
+        return Err(0);
+    }
+    let weights = weights(deck, shuffle, openings);
+    for (i, challenge) in challenges.iter().enumerate() {
+        let (shadow, shadow_key, opening) = match (
+            shuffle.shadows.get(i),
+            shuffle.shadow_keys.get(i),
+            openings.get(i),
+        ) {
+            (Some(shadow), Some(shadow_key), Some(opening)) => (shadow, shadow_key, opening),
+            _ => return Err(i),
+        };
+        // the opened key takes the public key of the source to the one of the target too
+        let valid = opening.key != Scalar::zero()
+            && match challenge {
+                false => {
+                    opening.key * RISTRETTO_BASEPOINT_POINT == *shadow_key
+                        && holds(deck, shadow, opening, &weights)
+                }
+                true => {
+                    opening.key * shadow_key == shuffle.key
+                        && holds(shadow, &shuffle.deck, opening, &weights)
+                }
+            };
+        if !valid {
+            return Err(i);
+        }
+    }
+    Ok(())
+}
+
+/// The weights the cards of every opening of a shuffle are combined with, hashed from the deck,
+/// the shuffle and the openings so that the shuffler can't know them before publishing all three
+fn weights(deck: &[RistrettoPoint], shuffle: &Shuffle, openings: &[Opening]) -> Vec<Scalar> {
+    let mut data = WEIGHTS_CONTEXT.to_vec();
+    let points = deck
+        .iter()
+        .chain([&shuffle.key])
+        .chain(&shuffle.deck)
+        .chain(&shuffle.shadow_keys)
+        .chain(shuffle.shadows.iter().flatten());
+    for point in points {
+        data.extend_from_slice(point.compress().as_bytes());
+    }
+    for opening in openings {
+        data.extend_from_slice(opening.key.as_bytes());
+        for position in &opening.permutation {
+            data.extend_from_slice(&(*position as u32).to_le_bytes());
+        }
+    }
+    let seed = blake2_256(&data);
+    (0..deck.len() as u32)
+        .map(|i| {
+            let mut data = seed.to_vec();
+            data.extend_from_slice(&i.to_le_bytes());
+            let mut weight = [0u8; 16];
+            weight.copy_from_slice(&blake2_256(&data)[..16]);
+            Scalar::from(u128::from_le_bytes(weight))
+        })
+        .collect()
+}
+
+/// Whether a deck is another deck encrypted with the key of an opening and permuted with its
+/// permutation, checked on the combination of the cards with some weights
+fn holds(
+    source: &[RistrettoPoint],
+    target: &[RistrettoPoint],
+    opening: &Opening,
+    weights: &[Scalar],
+) -> bool {
+    let permutation = &opening.permutation;
+    let mut seen = vec![false; source.len()];
+    if permutation.len() != source.len() || target.len() != source.len() {
+        return false;
+    }
+    for position in permutation {
+        match seen.get_mut(*position) {
+            Some(seen) if !*seen => *seen = true,
+            _ => return false,
+        }
+    }
+    // check every card at once, on their combination
+    let sources = permutation.iter().map(|position| source[*position]);
+    let combined_target = RistrettoPoint::vartime_multiscalar_mul(weights, target);
+    let combined_source = RistrettoPoint::vartime_multiscalar_mul(weights, sources);
+    combined_target == opening.key * combined_source
+}
+
+/// A deck encrypted with a key and permuted with a random permutation, and the permutation
+fn permute(
+    key: Scalar,
+    deck: &[RistrettoPoint],
+    rng: &mut impl Rng,
+) -> (Vec<RistrettoPoint>, Vec<usize>) {
+    let mut permutation: Vec<usize> = (0..deck.len()).collect();
+    permutation.shuffle(rng);
+    (apply(key, &permutation, deck), permutation)
+}
+
+/// A deck encrypted with a key and permuted: the card at position i comes from the position
+/// `permutation[i]` of the deck
+fn apply(key: Scalar, permutation: &[usize], deck: &[RistrettoPoint]) -> Vec<RistrettoPoint> {
+    permutation
+        .iter()
+        .map(|position| key * deck[*position])
+        .collect()
+}
+
+/// The hex encoding of an opening, as written in transcripts: the key, then the permutation
+pub fn opening_to_hex(opening: &Opening) -> String {
+    let permutation: Vec<String> = opening.permutation.iter().map(|p| p.to_string()).collect();
+    format!("{}:{}", scalar_to_hex(&opening.key), permutation.join(","))
+}
+
+/// Parse an opening from its hex encoding. Errors if it is malformed.
+pub fn opening_from_hex(s: &str) -> Result<Opening, ()> {
+    let (key, permutation) = s.split_once(':').ok_or(())?;
+    Ok(Opening {
+        key: scalar_from_hex(key)?,
+        permutation: permutation
+            .split(',')
+            .map(|p| p.parse().map_err(|_| ()))
+            .collect::<Result<_, ()>>()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poker::deck::new_deck;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn key(rng: &mut StdRng) -> Scalar {
+        random_scalar(rng)
+    }
+
+    #[test]
+    fn honest_shuffle_verifies() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let deck = new_deck();
+        let (shuffle, secret) = shuffle(key(&mut rng), &deck, &mut rng);
+        let challenges = challenges(&[7u8; 32], 2);
+        assert!(challenges.contains(&true) && challenges.contains(&false));
+        assert_eq!(challenges, super::challenges(&[7u8; 32], 2));
+        let openings = secret.open(&challenges);
+        assert_eq!(verify(&deck, &shuffle, &challenges, &openings), Ok(()));
+        for opening in &openings {
+            assert_eq!(
+                opening_from_hex(&opening_to_hex(opening)),
+                Ok(opening.clone())
+            );
+        }
+        // the openings only answer their own challenges
+        let flipped: Vec<bool> = challenges.iter().map(|c| !c).collect();
+        assert_eq!(verify(&deck, &shuffle, &flipped, &openings), Err(0));
+    }
+
+    #[test]
+    fn tampered_deck_is_rejected() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let deck = new_deck();
+        let (mut shuffle, secret) = shuffle(key(&mut rng), &deck, &mut rng);
+        // swap a card for a copy of another one
+        shuffle.deck[0] = shuffle.deck[1];
+        let challenges = challenges(&[7u8; 32], 2);
+        let first = challenges.iter().position(|c| *c).unwrap();
+        assert_eq!(
+            verify(&deck, &shuffle, &challenges, &secret.open(&challenges)),
+            Err(first)
+        );
+    }
+
+    #[test]
+    fn public_key_is_bound_to_the_shuffle() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let deck = new_deck();
+        let (mut shuffle, secret) = shuffle(key(&mut rng), &deck, &mut rng);
+        // claim the public key of another key, to decrypt with it later
+        shuffle.key = key(&mut rng) * RISTRETTO_BASEPOINT_POINT;
+        let challenges = challenges(&[7u8; 32], 2);
+        let first = challenges.iter().position(|c| *c).unwrap();
+        assert_eq!(
+            verify(&deck, &shuffle, &challenges, &secret.open(&challenges)),
+            Err(first)
+        );
+    }
+
+    #[test]
+    fn openings_must_be_permutations() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let deck = new_deck();
+        let (shuffle, secret) = shuffle(key(&mut rng), &deck, &mut rng);
+        let mut challenges = vec![false; SHUFFLE_CHALLENGES];
+        challenges[SHUFFLE_CHALLENGES - 1] = true;
+        let mut openings = secret.open(&challenges);
+        openings[3].permutation[0] = openings[3].permutation[1];
+        assert_eq!(verify(&deck, &shuffle, &challenges, &openings), Err(3));
+        openings.truncate(2);
+        assert_eq!(verify(&deck, &shuffle, &challenges, &openings), Err(2));
+    }
+
+    #[test]
+    fn shuffled_deck_is_always_challenged() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let deck = new_deck();
+        let (mut shuffle, secret) = shuffle(key(&mut rng), &deck, &mut rng);
+        // with only 0s, the shuffled deck could be anything
+        shuffle.deck = deck.clone();
+        let challenges = vec![false; SHUFFLE_CHALLENGES];
+        let openings = secret.open(&challenges);
+        assert_eq!(verify(&deck, &shuffle, &challenges, &openings), Err(0));
+        assert_eq!(
+            verify(&deck, &shuffle, &challenges[1..], &openings[1..]),
+            Err(0)
+        );
+    }
+
+    #[test]
+    fn malformed_shuffles_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let deck = new_deck();
+        let challenges = challenges(&[7u8; 32], 2);
+        let (honest, secret) = shuffle(key(&mut rng), &deck, &mut rng);
+        let openings = secret.open(&challenges);
+        let mut short = honest.clone();
+        short.deck.pop();
+        assert_eq!(verify(&deck, &short, &challenges, &openings), Err(0));
+        // a key of zero erases every card
+        let (erased, secret) = shuffle(Scalar::zero(), &deck, &mut rng);
+        assert_eq!(
+            verify(&deck, &erased, &challenges, &secret.open(&challenges)),
+            Err(0)
+        );
+        let mut identity = honest.clone();
+        identity.shadows[0][0] = RistrettoPoint::identity();
+        assert_eq!(verify(&deck, &identity, &challenges, &openings), Err(0));
+        // the weights only depend on what is verified
+        assert_eq!(
+            weights(&deck, &honest, &openings),
+            weights(&deck, &honest, &openings)
+        );
+        assert_ne!(
+            weights(&deck, &honest, &openings),
+            weights(&deck, &honest, &openings[1..])
+        );
+    }
+}
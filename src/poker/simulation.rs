@@ -0,0 +1,524 @@
+//! Monte Carlo simulations of betting strategies.
+//!
+//! A simulation plays many tournaments between the same strategies, a matchup, to tell which one
+//! wins in the long run. Every game is seeded: the keys and the random number generators of the
+//! players come from the seed of the simulation and the number of the game, so a simulation plays
+//! the same games whatever the number of threads it runs on, and again with the same seed. The
+//! strategies move one seat to the left every game, so none of them keeps the best seat.
+//!
+//! The report gives, for every strategy of every matchup, its win rate with a 95% Wilson score
+//! interval, and the mean and the variance of the stack it ends the games with. A game that hits
+//! the cap of hands is won by the player with the most points. The report also gives the average
+//! length of the games, with a 95% confidence interval, and prints as a table or as CSV.
+
+use std::fmt;
+use std::thread;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use schnorrkel::{ExpansionMode, MiniSecretKey};
+
+use super::cheating::Cheat;
+use super::game::{Game, MAX_PLAYERS, MIN_PLAYERS};
+use super::player::Player;
+use super::round::{Dealing, Variant};
+use super::strategy::{
+    BettingStrategy, ConstantStrategy, KellyStrategy, LinearStrategy, RandomBluffStrategy,
+};
+use super::tournament::{Schedule, Tournament};
+
+/// The quantile of the normal distribution for a 95% confidence interval
+const Z_95: f64 = 1.96;
+
+/// The odd constant closest to 2^64 over the golden ratio, which spreads the numbers of the games
+/// over the seeds
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// A betting strategy a simulation gives to a player
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StrategyKind {
+    Linear,
+    /// Always bet this amount
+    Constant(u32),
+    /// Bet this fraction of the Kelly criterion
+    Kelly(f32),
+    /// Bet like the linear strategy, and bluff with this probability
+    Bluff(f64),
+}
+
+impl StrategyKind {
+    /// The strategy of a player, with the seed of its random number generator
+    pub fn build(self, rng_seed: u64) -> Box<dyn BettingStrategy> {
+        match self {
+            StrategyKind::Linear => Box::new(LinearStrategy),
+            StrategyKind::Constant(amount) => Box::new(ConstantStrategy(amount)),
+            StrategyKind::Kelly(fraction) => Box::new(KellyStrategy { fraction }),
+            StrategyKind::Bluff(probability) => {
+                Box::new(RandomBluffStrategy::new(probability, rng_seed))
+            }
+        }
+    }
+
+    /// Parse a strategy from its name, as written by `to_string`: `linear`, `constant:<amount>`,
+    /// `kelly` or `kelly:<fraction>`, and `bluff:<probability>`. Errors if there is none.
+    pub fn from_name(name: &str) -> Result<Self, ()> {
+        let (kind, parameter) = match name.split_once(':') {
+            Some((kind, parameter)) => (kind, Some(parameter)),
+            None => (name, None),
+        };
+        match (kind, parameter) {
+            ("linear", None) => Ok(StrategyKind::Linear),
+            ("kelly", None) => Ok(StrategyKind::Kelly(1.0)),
+            ("constant", Some(amount)) => {
+                amount.parse().map(StrategyKind::Constant).map_err(|_| ())
+            }
+            ("kelly", Some(fraction)) => match fraction.parse() {
+                Ok(fraction) if fraction >= 0.0 => Ok(StrategyKind::Kelly(fraction)),
+                _ => Err(()),
+            },
+            ("bluff", Some(probability)) => match probability.parse() {
+                Ok(probability) if (0.0..=1.0).contains(&probability) => {
+                    Ok(StrategyKind::Bluff(probability))
+                }
+                _ => Err(()),
+            },
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for StrategyKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StrategyKind::Linear => write!(f, "linear"),
+            StrategyKind::Constant(amount) => write!(f, "constant:{}", amount),
+            StrategyKind::Kelly(fraction) => write!(f, "kelly:{}", fraction),
+            StrategyKind::Bluff(probability) => write!(f, "bluff:{}", probability),
+        }
+    }
+}
+
+/// The mean and the variance of some samples
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Summary {
+    pub samples: usize,
+    pub mean: f64,
+    /// The unbiased variance of the samples, 0 for less than two
+    pub variance: f64,
+}
+
+impl Summary {
+    /// Summarize some samples
+    pub fn of(samples: &[f64]) -> Self {
+        let n = samples.len();
+        if n == 0 {
+            return Summary::default();
+        }
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        let variance = match n {
+            1 => 0.0,
+            _ => samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64,
+        };
+        Summary {
+            samples: n,
+            mean,
+            variance,
+        }
+    }
+
+    /// The 95% confidence interval of the mean, from the normal approximation
+    pub fn interval(&self) -> (f64, f64) {
+        let margin = match self.samples {
+            0 => 0.0,
+            n => Z_95 * (self.variance / n as f64).sqrt(),
+        };
+        (self.mean - margin, self.mean + margin)
+    }
+}
+
+/// The 95% Wilson score interval of the rate of some successes in some trials
+pub fn wilson_interval(successes: u32, trials: u32) -> (f64, f64) {
+    if trials == 0 {
+        return (0.0, 1.0);
+    }
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let z2 = Z_95 * Z_95;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let margin = Z_95 / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    ((center - margin).max(0.0), (center + margin).min(1.0))
+}
+
+/// What happened in a single game of a simulation, by strategy of the matchup
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Outcome {
+    winner: usize,
+    hands: u32,
+    stacks: Vec<u32>,
+    cheated: bool,
+}
+
+/// The results of the strategies of a matchup
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchupReport {
+    pub strategies: Vec<StrategyKind>,
+    pub games: u32,
+    /// The games won by every strategy
+    pub wins: Vec<u32>,
+    /// The number of hands of the games
+    pub hands: Summary,
+    /// The stack every strategy ends the games with
+    pub stacks: Vec<Summary>,
+    /// The number of games where a player was caught cheating
+    pub cheated: u32,
+}
+
+impl MatchupReport {
+    fn new(strategies: &[StrategyKind], outcomes: &[Outcome]) -> Self {
+        let mut wins = vec![0; strategies.len()];
+        for outcome in outcomes {
+            wins[outcome.winner] += 1;
+        }
+        let hands: Vec<f64> = outcomes.iter().map(|o| o.hands as f64).collect();
+        let stacks = (0..strategies.len())
+            .map(|i| {
+                let stacks: Vec<f64> = outcomes.iter().map(|o| o.stacks[i] as f64).collect();
+                Summary::of(&stacks)
+            })
+            .collect();
+        MatchupReport {
+            strategies: strategies.to_vec(),
+            games: outcomes.len() as u32,
+            wins,
+            hands: Summary::of(&hands),
+            stacks,
+            cheated: outcomes.iter().filter(|o| o.cheated).count() as u32,
+        }
+    }
+
+    /// The share of the games won by a strategy
+    pub fn win_rate(&self, strategy: usize) -> f64 {
+        match self.games {
+            0 => 0.0,
+            games => self.wins[strategy] as f64 / games as f64,
+        }
+    }
+}
+
+/// The results of every matchup of a simulation
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub matchups: Vec<MatchupReport>,
+}
+
+impl Report {
+    /// The report as CSV, with a header and a row for every strategy of every matchup
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "matchup,strategy,games,wins,win_rate,win_rate_low,win_rate_high,mean_hands,\
+             hands_low,hands_high,mean_stack,stack_variance,cheated\n",
+        );
+        for (number, matchup) in self.matchups.iter().enumerate() {
+            let (hands_low, hands_high) = matchup.hands.interval();
+            for (i, strategy) in matchup.strategies.iter().enumerate() {
+                let (low, high) = wilson_interval(matchup.wins[i], matchup.games);
+                csv.push_str(&format!(
+                    "{},{},{},{},{:.4},{:.4},{:.4},{:.2},{:.2},{:.2},{:.2},{:.2},{}\n",
+                    number,
+                    strategy,
+                    matchup.games,
+                    matchup.wins[i],
+                    matchup.win_rate(i),
+                    low,
+                    high,
+                    matchup.hands.mean,
+                    hands_low,
+                    hands_high,
+                    matchup.stacks[i].mean,
+                    matchup.stacks[i].variance,
+                    matchup.cheated
+                ));
+            }
+        }
+        csv
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (number, matchup) in self.matchups.iter().enumerate() {
+            let (hands_low, hands_high) = matchup.hands.interval();
+            writeln!(
+                f,
+                "MATCHUP {}: {} GAMES, {:.1} HANDS ON AVERAGE (95% CI {:.1}-{:.1}), CHEATING IN {}",
+                number, matchup.games, matchup.hands.mean, hands_low, hands_high, matchup.cheated
+            )?;
+            writeln!(
+                f,
+                "  {:<16} {:>6} {:>8} {:>15} {:>10} {:>14}",
+                "STRATEGY", "WINS", "WIN RATE", "95% CI", "MEAN STACK", "STACK VARIANCE"
+            )?;
+            for (i, strategy) in matchup.strategies.iter().enumerate() {
+                let (low, high) = wilson_interval(matchup.wins[i], matchup.games);
+                writeln!(
+                    f,
+                    "  {:<16} {:>6} {:>7.1}% {:>15} {:>10.1} {:>14.1}",
+                    strategy.to_string(),
+                    matchup.wins[i],
+                    100.0 * matchup.win_rate(i),
+                    format!("{:.1}%-{:.1}%", 100.0 * low, 100.0 * high),
+                    matchup.stacks[i].mean,
+                    matchup.stacks[i].variance
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Many seeded tournaments between the strategies of some matchups
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    /// The strategies of every matchup, one per player
+    pub matchups: Vec<Vec<StrategyKind>>,
+    /// The number of games of every matchup
+    pub games: u32,
+    pub variant: Variant,
+    pub dealing: Dealing,
+    pub schedule: Schedule,
+    /// The points every player starts with
+    pub stack: u32,
+    /// The seed every game is seeded from
+    pub seed: u64,
+    /// The number of threads the games are played on
+    pub threads: usize,
+    /// The most hands a game lasts, if any
+    pub max_hands: Option<u32>,
+    /// The strategies whose players cheat, by their index in the matchup, whatever seat they
+    /// take in a game
+    pub cheats: Vec<(usize, Cheat)>,
+}
+
+impl Simulation {
+    /// A simulation of some games of five-card poker for every matchup, on the standard
+    /// tournament schedule with stacks of 100 points, on as many threads as the machine has.
+    /// Errors if a matchup does not have 2 to 10 strategies.
+    pub fn new(matchups: Vec<Vec<StrategyKind>>, games: u32) -> Result<Self, ()> {
+        let seats = MIN_PLAYERS..=MAX_PLAYERS;
+        if matchups
+            .iter()
+            .any(|matchup| !seats.contains(&matchup.len()))
+        {
+            return Err(());
+        }
+        Ok(Simulation {
+            matchups,
+            games,
+            variant: Variant::default(),
+            dealing: Dealing::default(),
+            schedule: Schedule::standard(),
+            stack: 100,
+            seed: 0,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            max_hands: None,
+            cheats: Vec::new(),
+        })
+    }
+
+    /// Play the variant of poker
+    pub fn with_variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    /// Deal the cards this way
+    pub fn with_dealing(mut self, dealing: Dealing) -> Self {
+        self.dealing = dealing;
+        self
+    }
+
+    /// Start every player with some points
+    pub fn with_stack(mut self, stack: u32) -> Self {
+        self.stack = stack;
+        self
+    }
+
+    /// Seed the games from another seed
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Play the games on some threads, at least one
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Stop every game after some hands
+    pub fn with_max_hands(mut self, max_hands: u32) -> Self {
+        self.max_hands = Some(max_hands);
+        self
+    }
+
+    /// Have the player of a strategy cheat, by the index of the strategy in the matchup
+    pub fn with_cheat(mut self, strategy: usize, cheat: Cheat) -> Self {
+        self.cheats.push((strategy, cheat));
+        self
+    }
+
+    /// Play every game of every matchup, and report the results
+    pub fn run(&self) -> Report {
+        let matchups = self
+            .matchups
+            .iter()
+            .map(|strategies| {
+                let outcomes = self.play_all(strategies);
+                MatchupReport::new(strategies, &outcomes)
+            })
+            .collect();
+        Report { matchups }
+    }
+
+    /// Play every game of a matchup on the threads of the simulation, and return their outcomes
+    /// in the order of the games
+    fn play_all(&self, strategies: &[StrategyKind]) -> Vec<Outcome> {
+        let threads = self.threads.min(self.games.max(1) as usize);
+        let mut outcomes: Vec<(u32, Outcome)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|worker| {
+                    scope.spawn(move || {
+                        (worker as u32..self.games)
+                            .step_by(threads)
+                            .map(|game| (game, self.play(strategies, game)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("a game of the simulation panicked"))
+                .collect()
+        });
+        outcomes.sort_by_key(|(game, _)| *game);
+        outcomes.into_iter().map(|(_, outcome)| outcome).collect()
+    }
+
+    /// Play a single game of a matchup. The strategies move one seat to the left every game.
+    fn play(&self, strategies: &[StrategyKind], game: u32) -> Outcome {
+        let seats = strategies.len();
+        let strategy_of = |seat: usize| (seat + game as usize) % seats;
+        let mut rng = StdRng::seed_from_u64(self.seed ^ (game as u64).wrapping_mul(GOLDEN_GAMMA));
+        let players: Vec<Player> = (0..seats)
+            .map(|seat| {
+                let mini_secret = MiniSecretKey::from_bytes(&rng.gen::<[u8; 32]>())
+                    .expect("any 32 bytes are a mini secret key");
+                let keypair = mini_secret.expand_to_keypair(ExpansionMode::Ed25519);
+                let strategy = strategies[strategy_of(seat)].build(rng.gen());
+                let mut player =
+                    Player::from_keypair(keypair, self.stack, rng.gen()).with_strategy(strategy);
+                // the cheat follows its strategy around the table
+                player.cheat = self
+                    .cheats
+                    .iter()
+                    .find(|(cheater, _)| *cheater == strategy_of(seat))
+                    .map(|(_, cheat)| *cheat);
+                player
+            })
+            .collect();
+        let game = Game::new(game as u64, players)
+            .expect("matchups have 2 to 10 strategies")
+            .with_variant(self.variant)
+            .with_dealing(self.dealing);
+        let mut tournament = Tournament::new(game, self.schedule.clone());
+        let mut hands = 0;
+        while !tournament.is_over() && hands < self.max_hands.unwrap_or(u32::MAX) {
+            let _ = tournament.play_hand();
+            hands += 1;
+        }
+
+        let mut stacks = vec![0; seats];
+        for (seat, player) in tournament.game.players.iter().enumerate() {
+            stacks[strategy_of(seat)] = player.points;
+        }
+        Outcome {
+            winner: strategy_of(tournament.standings()[0].seat),
+            hands,
+            stacks,
+            cheated: !tournament.game.evidence.is_empty(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strategies_have_names() {
+        for strategy in [
+            StrategyKind::Linear,
+            StrategyKind::Constant(10),
+            StrategyKind::Kelly(0.5),
+            StrategyKind::Bluff(0.25),
+        ] {
+            assert_eq!(StrategyKind::from_name(&strategy.to_string()), Ok(strategy));
+        }
+        assert_eq!(
+            StrategyKind::from_name("kelly"),
+            Ok(StrategyKind::Kelly(1.0))
+        );
+        for name in ["constant", "bluff:2", "kelly:-1", "linear:1", "martingale"] {
+            assert!(StrategyKind::from_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn summaries_and_intervals() {
+        let summary = Summary::of(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        assert_eq!(summary.mean, 5.0);
+        assert!((summary.variance - 32.0 / 7.0).abs() < 1e-9);
+        let (low, high) = summary.interval();
+        assert!(low < 5.0 && high > 5.0 && (5.0 - low - (high - 5.0)).abs() < 1e-9);
+        assert_eq!(Summary::of(&[3.0]).interval(), (3.0, 3.0));
+        // the Wilson interval stays within 0 and 1, even with no wins at all
+        let (low, high) = wilson_interval(0, 10);
+        assert_eq!(low, 0.0);
+        assert!(high > 0.2 && high < 0.35);
+        let (low, high) = wilson_interval(50, 100);
+        assert!((low + high - 1.0).abs() < 1e-9 && low > 0.39 && high < 0.61);
+    }
+
+    #[test]
+    fn simulations_are_seeded_whatever_the_threads() {
+        let matchups = vec![vec![StrategyKind::Linear, StrategyKind::Kelly(1.0)]];
+        let simulation = Simulation::new(matchups, 6).unwrap().with_max_hands(4);
+        let report = simulation.clone().with_threads(1).run();
+        assert_eq!(simulation.clone().with_threads(4).run(), report);
+        let matchup = &report.matchups[0];
+        assert_eq!(matchup.games, 6);
+        assert_eq!(matchup.wins.iter().sum::<u32>(), 6);
+        assert!(matchup.hands.mean <= 4.0);
+        // no chip is created or lost, so the mean stacks add up to the points of the table
+        let total: f64 = matchup.stacks.iter().map(|stack| stack.mean).sum();
+        assert!((total - 200.0).abs() < 1e-9);
+        let csv = report.to_csv();
+        assert_eq!(csv.lines().count(), 3);
+        assert!(csv.lines().nth(2).unwrap().starts_with("0,kelly:1,6,"));
+        assert!(Simulation::new(vec![vec![StrategyKind::Linear]], 1).is_err());
+    }
+
+    #[test]
+    fn cheats_follow_their_strategy_around_the_table() {
+        let matchups = vec![vec![StrategyKind::Linear, StrategyKind::Kelly(1.0)]];
+        let report = Simulation::new(matchups, 4)
+            .unwrap()
+            .with_max_hands(2)
+            .with_cheat(1, Cheat::BadReveal)
+            .run();
+        // the cheater is caught in every game, whatever seat their strategy takes
+        let matchup = &report.matchups[0];
+        assert_eq!(matchup.cheated, 4);
+        assert_eq!(matchup.wins, vec![4, 0]);
+        assert_eq!(matchup.stacks[1].mean, 0.0);
+    }
+}
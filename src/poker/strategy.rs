@@ -0,0 +1,321 @@
+//! Betting strategies.
+//!
+//! Every player bets according to their own strategy, which only sees what the player can see:
+//! their own cards, their own stack, the stacks of the other players, the betting so far and the
+//! rounds played before.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::betting::{Action, BettingView};
+use super::card::Card;
+use super::hand::{self, Category, HandValue};
+use super::round::Round;
+
+/// A way of choosing a bet for a hand.
+///
+/// Simple strategies only choose how much they would like to put in the pot with `bet`, and the
+/// default `act` turns that into an action: bet or raise up to that amount, call if the highest
+/// bet is not above it, or if it is and we want to put in our whole stack, and fold otherwise.
+/// Nobody has to fold when nobody bet yet: a player who wants less than the minimum bet checks.
+/// Strategies can also choose their actions directly.
+pub trait BettingStrategy: Send {
+    /// Choose how much to put in the pot for our hand, knowing our stack, the stacks of the other
+    /// players in the round and the history of the game.
+    fn bet(&mut self, hand: &[Card], stack: u32, opponent_stacks: &[u32], history: &[Round])
+        -> u32;
+
+    /// Choose an action when it is our turn in a betting round. An action that is not allowed
+    /// counts as a fold.
+    fn act(
+        &mut self,
+        hand: &[Card],
+        view: &BettingView,
+        stack: u32,
+        opponent_stacks: &[u32],
+        history: &[Round],
+    ) -> Action {
+        let wanted = self
+            .bet(hand, stack, opponent_stacks, history)
+            .min(view.all_in);
+        // a bet below the minimum is only allowed when it puts in the whole stack
+        if view.current_bet == 0 {
+            return match wanted < view.min_bet.min(view.all_in) {
+                true => Action::Check,
+                false => Action::Bet(wanted.max(view.min_bet).min(view.all_in)),
+            };
+        }
+        if view.to_call == 0 {
+            Action::Check
+        } else if wanted > view.current_bet && wanted >= view.min_raise_to {
+            Action::Raise(wanted)
+        } else if wanted >= view.current_bet.min(view.all_in) {
+            Action::Call
+        } else {
+            Action::Fold
+        }
+    }
+
+    /// Choose the slots of our hand to discard in the draw of five-card draw. By default we keep a
+    /// straight or better, and otherwise every card that pairs with another one, or only the
+    /// highest card if none does.
+    fn discard(&mut self, hand: &[Card]) -> Vec<usize> {
+        if hand::best_hand(hand).category >= Category::Straight {
+            return Vec::new();
+        }
+        let paired = |slot: &usize| hand.iter().filter(|c| c.rank == hand[*slot].rank).count() > 1;
+        let highest = match (0..hand.len()).any(|slot| paired(&slot)) {
+            true => None,
+            false => (0..hand.len()).max_by_key(|slot| hand[*slot].rank),
+        };
+        (0..hand.len())
+            .filter(|slot| !paired(slot) && Some(*slot) != highest)
+            .collect()
+    }
+}
+
+/// Bet a share of the stack equal to the strength of the hand, which is at least 1 and almost the
+/// whole stack for the strongest hands.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LinearStrategy;
+
+impl BettingStrategy for LinearStrategy {
+    fn bet(&mut self, hand: &[Card], stack: u32, _: &[u32], _: &[Round]) -> u32 {
+        (hand::best_hand(hand).strength() * stack as f32 - 1.0) as u32 + 1
+    }
+}
+
+/// Always bet the same amount, whatever the hand
+#[derive(Clone, Copy, Debug)]
+pub struct ConstantStrategy(pub u32);
+
+impl BettingStrategy for ConstantStrategy {
+    fn bet(&mut self, _: &[Card], _: u32, _: &[u32], _: &[Round]) -> u32 {
+        self.0
+    }
+}
+
+/// Bet a fraction of the stack given by the Kelly criterion for an even-money bet, `2p - 1`,
+/// where `p` is the probability that our hand beats every other hand of the round, counting a
+/// tie as half a win. The fraction can be scaled down, for instance to 0.5 for half Kelly.
+#[derive(Clone, Copy, Debug)]
+pub struct KellyStrategy {
+    pub fraction: f32,
+}
+
+impl Default for KellyStrategy {
+    fn default() -> Self {
+        KellyStrategy { fraction: 1.0 }
+    }
+}
+
+impl KellyStrategy {
+    /// The probability of beating some opponents with a hand, counting ties as half
+    pub fn win_probability(value: &HandValue, opponents: usize) -> f32 {
+        value.strength().powi(opponents as i32)
+    }
+}
+
+impl BettingStrategy for KellyStrategy {
+    fn bet(&mut self, hand: &[Card], stack: u32, opponent_stacks: &[u32], _: &[Round]) -> u32 {
+        let value = hand::best_hand(hand);
+        let p = Self::win_probability(&value, opponent_stacks.len().max(1));
+        let kelly = (2.0 * p - 1.0).max(0.0) * self.fraction;
+        (kelly * stack as f32) as u32
+    }
+}
+
+/// Bet like the linear strategy, but now and then bluff by betting the whole stack whatever the
+/// hand.
+#[derive(Clone, Debug)]
+pub struct RandomBluffStrategy {
+    /// The probability of bluffing on any round
+    pub bluff_probability: f64,
+    rng: StdRng,
+}
+
+impl RandomBluffStrategy {
+    /// Create a bluffing strategy with some probability of bluffing and a seed for its RNG
+    pub fn new(bluff_probability: f64, rng_seed: u64) -> Self {
+        RandomBluffStrategy {
+            bluff_probability,
+            rng: StdRng::seed_from_u64(rng_seed),
+        }
+    }
+}
+
+impl BettingStrategy for RandomBluffStrategy {
+    fn bet(
+        &mut self,
+        hand: &[Card],
+        stack: u32,
+        opponent_stacks: &[u32],
+        history: &[Round],
+    ) -> u32 {
+        if self.rng.gen_bool(self.bluff_probability) {
+            return stack;
+        }
+        LinearStrategy.bet(hand, stack, opponent_stacks, history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poker::card::{Rank, Suit};
+
+    /// A hand of the given ranks, in every suit so it is never a flush
+    fn hand(ranks: [Rank; 5]) -> Vec<Card> {
+        let suits = [
+            Suit::Spades,
+            Suit::Hearts,
+            Suit::Clubs,
+            Suit::Diamonds,
+            Suit::Spades,
+        ];
+        ranks
+            .iter()
+            .zip(suits)
+            .map(|(rank, suit)| Card { rank: *rank, suit })
+            .collect()
+    }
+
+    fn seven_high() -> Vec<Card> {
+        hand([Rank::Seven, Rank::Five, Rank::Four, Rank::Three, Rank::Two])
+    }
+
+    fn four_aces() -> Vec<Card> {
+        hand([Rank::Ace, Rank::Ace, Rank::Ace, Rank::Ace, Rank::King])
+    }
+
+    fn pair_of_aces() -> Vec<Card> {
+        hand([Rank::Ace, Rank::Ace, Rank::King, Rank::Queen, Rank::Two])
+    }
+
+    #[test]
+    fn linear_strategy_follows_the_strength_of_the_hand() {
+        assert_eq!(LinearStrategy.bet(&seven_high(), 100, &[100], &[]), 17);
+        assert_eq!(LinearStrategy.bet(&four_aces(), 100, &[100], &[]), 99);
+        assert_eq!(LinearStrategy.bet(&pair_of_aces(), 100, &[100], &[]), 85);
+    }
+
+    #[test]
+    fn default_discard_keeps_the_made_hand() {
+        let mut strategy = LinearStrategy;
+        assert_eq!(strategy.discard(&pair_of_aces()), vec![2, 3, 4]);
+        let two_pair = hand([Rank::Ace, Rank::Ace, Rank::Two, Rank::King, Rank::Two]);
+        assert_eq!(strategy.discard(&two_pair), vec![3]);
+        // with nothing made, only the highest card is kept
+        assert_eq!(strategy.discard(&seven_high()), vec![1, 2, 3, 4]);
+        // a straight or better is kept whole
+        let straight = hand([Rank::Six, Rank::Five, Rank::Four, Rank::Three, Rank::Two]);
+        assert!(strategy.discard(&straight).is_empty());
+        assert!(strategy.discard(&four_aces()).is_empty());
+    }
+
+    #[test]
+    fn constant_strategy_ignores_hand() {
+        let mut strategy = ConstantStrategy(7);
+        assert_eq!(strategy.bet(&seven_high(), 100, &[100], &[]), 7);
+        assert_eq!(strategy.bet(&four_aces(), 100, &[100], &[]), 7);
+    }
+
+    #[test]
+    fn kelly_strategy_only_bets_with_an_edge() {
+        let mut strategy = KellyStrategy::default();
+        // a high card hand is below the middle of the hands, so there is no edge
+        assert_eq!(strategy.bet(&seven_high(), 100, &[100], &[]), 0);
+        // a pair of aces beats most hands: p = 0.853
+        assert_eq!(strategy.bet(&pair_of_aces(), 100, &[100], &[]), 70);
+        // the edge shrinks with more opponents
+        assert!(strategy.bet(&pair_of_aces(), 100, &[100, 100, 100], &[]) < 70);
+        let mut half = KellyStrategy { fraction: 0.5 };
+        assert_eq!(half.bet(&pair_of_aces(), 100, &[100], &[]), 35);
+    }
+
+    fn view(current_bet: u32, contributed: u32) -> BettingView {
+        BettingView {
+            contributed,
+            to_call: current_bet - contributed,
+            current_bet,
+            min_raise_to: current_bet * 2,
+            min_bet: 1,
+            all_in: 100,
+            pot: current_bet + contributed,
+        }
+    }
+
+    #[test]
+    fn default_actions_follow_the_wanted_bet() {
+        let mut strategy = ConstantStrategy(30);
+        let cards = four_aces();
+        assert_eq!(
+            strategy.act(&cards, &view(0, 0), 100, &[100], &[]),
+            Action::Bet(30)
+        );
+        assert_eq!(
+            strategy.act(&cards, &view(10, 0), 100, &[100], &[]),
+            Action::Raise(30)
+        );
+        assert_eq!(
+            strategy.act(&cards, &view(20, 0), 100, &[100], &[]),
+            Action::Call
+        );
+        assert_eq!(
+            strategy.act(&cards, &view(30, 0), 100, &[100], &[]),
+            Action::Call
+        );
+        assert_eq!(
+            strategy.act(&cards, &view(40, 30), 100, &[100], &[]),
+            Action::Fold
+        );
+        assert_eq!(
+            strategy.act(&cards, &view(30, 30), 100, &[100], &[]),
+            Action::Check
+        );
+        let mut nothing = ConstantStrategy(0);
+        assert_eq!(
+            nothing.act(&cards, &view(0, 0), 100, &[100], &[]),
+            Action::Check
+        );
+        // nobody bets above their stack
+        let mut everything = ConstantStrategy(1000);
+        assert_eq!(
+            everything.act(&cards, &view(0, 0), 100, &[100], &[]),
+            Action::Bet(100)
+        );
+        // but a short stack who wants to put in everything calls all-in
+        let short = BettingView {
+            all_in: 50,
+            ..view(80, 0)
+        };
+        assert_eq!(
+            everything.act(&cards, &short, 50, &[100], &[]),
+            Action::Call
+        );
+        // a bet below the minimum checks instead, unless it is the whole stack
+        let blinds_up = BettingView {
+            min_bet: 40,
+            ..view(0, 0)
+        };
+        assert_eq!(
+            strategy.act(&cards, &blinds_up, 100, &[100], &[]),
+            Action::Check
+        );
+        let all_in = BettingView {
+            all_in: 30,
+            ..blinds_up
+        };
+        assert_eq!(
+            strategy.act(&cards, &all_in, 30, &[100], &[]),
+            Action::Bet(30)
+        );
+    }
+
+    #[test]
+    fn random_bluff_strategy_sometimes_bets_everything() {
+        let mut always = RandomBluffStrategy::new(1.0, 0);
+        assert_eq!(always.bet(&seven_high(), 100, &[100], &[]), 100);
+        let mut never = RandomBluffStrategy::new(0.0, 0);
+        assert_eq!(never.bet(&seven_high(), 100, &[100], &[]), 17);
+    }
+}
@@ -0,0 +1,331 @@
+//! Tournaments with a blinds schedule.
+//!
+//! A tournament is a game whose forced bets go up every few hands, following a schedule of levels,
+//! so that the stacks get shorter compared to the blinds until only one player is left. The button
+//! moves to the next player still in after every hand, and the players who run out of points are
+//! out of the tournament.
+//!
+//! Every hand is a round of the game engine, so the whole tournament is recorded in the transcript
+//! of the game, with its schedule, and the standings can be recomputed from the transcript alone:
+//! the last player left wins, and the others place by the hand they went out in, the later the
+//! better. Players who went out in the same hand place by the stack they started it with. A player
+//! who misses a deadline leaves the table, and is out in the hand they left in, whatever points
+//! they kept.
+
+use std::fmt;
+
+use super::betting::Blinds;
+use super::cheating::CheatingEvidence;
+use super::game::Game;
+use super::round::Round;
+use super::transcript::{verify_transcript, Transcript, TranscriptError};
+
+/// The blinds of every level of a tournament, which go up every few hands. The default schedule
+/// has no blinds at all.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Schedule {
+    /// How many hands every level lasts
+    pub hands_per_level: u32,
+    /// The blinds of every level, the last one lasting until the end
+    pub levels: Vec<Blinds>,
+}
+
+impl Schedule {
+    /// A schedule for stacks of about 100 points, going up every 10 hands
+    pub fn standard() -> Self {
+        let levels = [
+            (1, 2, 0),
+            (2, 4, 0),
+            (3, 6, 1),
+            (5, 10, 1),
+            (10, 20, 2),
+            (25, 50, 5),
+        ];
+        Schedule {
+            hands_per_level: 10,
+            levels: levels
+                .iter()
+                .map(|(small, big, ante)| Blinds {
+                    small: *small,
+                    big: *big,
+                    ante: *ante,
+                })
+                .collect(),
+        }
+    }
+
+    /// The level of a hand, counting from 0
+    pub fn level(&self, hand: u32) -> usize {
+        match self.hands_per_level {
+            0 => 0,
+            hands => (hand / hands) as usize,
+        }
+    }
+
+    /// The blinds of a hand, counting from 0
+    pub fn blinds(&self, hand: u32) -> Blinds {
+        self.levels
+            .get(self.level(hand))
+            .or(self.levels.last())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Parse a schedule from its text, as written by `to_string`. Errors if it is malformed.
+    pub fn from_text(s: &str) -> Result<Self, ()> {
+        let mut parts = s.split(' ');
+        let hands_per_level = parts.next().ok_or(())?.parse().map_err(|_| ())?;
+        let levels = parts.map(Blinds::from_text).collect::<Result<_, _>>()?;
+        Ok(Schedule {
+            hands_per_level,
+            levels,
+        })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.hands_per_level)?;
+        for blinds in &self.levels {
+            write!(f, " {}", blinds)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where a player finished in a tournament
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Standing {
+    pub seat: usize,
+    /// The points the player has left, which only the winner has once the tournament is over
+    pub points: u32,
+    /// The hand the player lost their last point in, if they are out
+    pub out_in: Option<u32>,
+}
+
+/// The standings of a tournament, from the first place to the last, from the points every player
+/// started with and the points after every hand, by seat, and the hands the players who left the
+/// table left in, with their seat
+pub fn standings(starting: &[u32], hands: &[Vec<u32>], left: &[(u32, usize)]) -> Vec<Standing> {
+    let mut standings: Vec<(Standing, u32)> = (0..starting.len())
+        .map(|seat| {
+            let busted = hands.iter().position(|points| points[seat] == 0);
+            let left_in = left
+                .iter()
+                .find(|(_, s)| *s == seat)
+                .map(|(hand, _)| *hand as usize);
+            let out_in = busted.into_iter().chain(left_in).min();
+            // the stack the player started the hand they went out in with
+            let stack = match out_in {
+                Some(0) | None => starting[seat],
+                Some(hand) => hands[hand - 1][seat],
+            };
+            let standing = Standing {
+                seat,
+                points: hands.last().map_or(starting[seat], |points| points[seat]),
+                out_in: out_in.map(|hand| hand as u32),
+            };
+            (standing, stack)
+        })
+        .collect();
+    // the players still in come first, then the players who went out the latest
+    standings.sort_by_key(|(standing, stack)| {
+        let out_in = standing.out_in.map_or(u32::MAX, |hand| hand);
+        std::cmp::Reverse((out_in, standing.points, *stack))
+    });
+    standings
+        .into_iter()
+        .map(|(standing, _)| standing)
+        .collect()
+}
+
+/// A tournament between the players of a game, played hand by hand until only one is left
+pub struct Tournament {
+    pub game: Game,
+}
+
+impl Tournament {
+    /// Start a tournament between the players of a game, before its first round, with a blinds
+    /// schedule
+    pub fn new(game: Game, schedule: Schedule) -> Self {
+        Tournament {
+            game: game.with_schedule(schedule),
+        }
+    }
+
+    /// The blinds of the next hand
+    pub fn blinds(&self) -> Blinds {
+        self.game.blinds()
+    }
+
+    /// Play the next hand, returning what happened in it, or the evidence against the cheater who
+    /// stopped it and is out of the tournament
+    pub fn play_hand(&mut self) -> Result<Round, CheatingEvidence> {
+        self.game.play_round()
+    }
+
+    /// The tournament is over once only one player is left
+    pub fn is_over(&self) -> bool {
+        self.game.is_over()
+    }
+
+    /// Play hands until only one player is left, and return the final standings
+    pub fn run(&mut self) -> Vec<Standing> {
+        while !self.is_over() {
+            let _ = self.play_hand();
+        }
+        self.standings()
+    }
+
+    /// The standings so far, from the transcript of the game
+    pub fn standings(&self) -> Vec<Standing> {
+        transcript_standings(&self.game.transcript)
+    }
+}
+
+/// Verify the transcript of a tournament from the first hand to the last, and recompute the
+/// standings. Errors if the transcript does not verify.
+pub fn verify_tournament(transcript: &Transcript) -> Result<Vec<Standing>, TranscriptError> {
+    verify_transcript(transcript)?;
+    Ok(transcript_standings(transcript))
+}
+
+/// The standings of the tournament recorded in a transcript, which is not verified
+pub fn transcript_standings(transcript: &Transcript) -> Vec<Standing> {
+    let left: Vec<(u32, usize)> = transcript
+        .timeouts()
+        .iter()
+        .map(|(hand, missed)| (*hand, missed.seat))
+        .collect();
+    standings(
+        &transcript.starting_points,
+        &transcript.points_by_round(),
+        &left,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poker::betting::Action;
+    use crate::poker::card::Card;
+    use crate::poker::player::Player;
+    use crate::poker::round::{Round, Variant};
+    use crate::poker::strategy::{BettingStrategy, ConstantStrategy};
+    use rand_core::OsRng;
+    use schnorrkel::Keypair;
+
+    fn new_tournament(players: usize) -> Tournament {
+        let players = (0..players)
+            .map(|i| Player::from_keypair(Keypair::generate_with(OsRng), 100, i as u64))
+            .collect();
+        let game = Game::new(2, players).unwrap();
+        Tournament::new(game, Schedule::standard())
+    }
+
+    #[test]
+    fn blinds_go_up_every_level() {
+        let schedule = Schedule::standard();
+        assert_eq!(schedule.blinds(0), schedule.levels[0]);
+        assert_eq!(schedule.blinds(9), schedule.levels[0]);
+        assert_eq!(schedule.blinds(10), schedule.levels[1]);
+        // the last level lasts until the end
+        assert_eq!(schedule.blinds(1000), *schedule.levels.last().unwrap());
+        assert_eq!(Schedule::default().blinds(5), Blinds::default());
+        for schedule in [schedule, Schedule::default()] {
+            assert_eq!(Schedule::from_text(&schedule.to_string()), Ok(schedule));
+        }
+        assert!(Schedule::from_text("10 2/1/0").is_err());
+    }
+
+    #[test]
+    fn later_eliminations_place_higher() {
+        let starting = [100, 100, 100, 100];
+        let hands = [
+            vec![150, 50, 100, 100],
+            vec![200, 50, 0, 150],
+            // the two players out in the same hand place by the stack they started it with
+            vec![400, 0, 0, 0],
+        ];
+        let standings = super::standings(&starting, &hands, &[]);
+        let seats: Vec<usize> = standings.iter().map(|standing| standing.seat).collect();
+        assert_eq!(seats, vec![0, 3, 1, 2]);
+        assert_eq!(standings[0].out_in, None);
+        assert_eq!(standings[1].out_in, Some(2));
+        assert_eq!(standings[3].out_in, Some(1));
+        // a player who left the table is out, even with points left
+        let standings = super::standings(&starting, &hands[..2], &[(1, 3)]);
+        let seats: Vec<usize> = standings.iter().map(|standing| standing.seat).collect();
+        assert_eq!(seats, vec![0, 1, 3, 2]);
+        assert_eq!(standings[2].out_in, Some(1));
+    }
+
+    #[test]
+    fn blinds_are_posted_and_the_button_moves() {
+        let mut tournament = new_tournament(3);
+        // nobody ever puts in more than they have to, so the big blind always wins
+        for player in tournament.game.players.iter_mut() {
+            player.strategy = Box::new(ConstantStrategy(0));
+        }
+        let first = tournament.play_hand().unwrap();
+        assert_eq!(first.button, 2);
+        assert_eq!(first.blinds, Schedule::standard().levels[0]);
+        assert_eq!(first.contributions, vec![1, 2, 0]);
+        assert_eq!(first.actions, vec![(2, Action::Fold), (0, Action::Fold)]);
+        let second = tournament.play_hand().unwrap();
+        assert_eq!(second.button, 0);
+        assert_eq!(second.contributions, vec![0, 1, 2]);
+        assert_eq!(second.winners(), vec![2]);
+        let points: Vec<u32> = tournament.game.players.iter().map(|p| p.points).collect();
+        assert_eq!(points, vec![99, 100, 101]);
+    }
+
+    /// Call the big blind before the flop, then only ever want to put in a single point
+    struct SmallBets;
+
+    impl BettingStrategy for SmallBets {
+        fn bet(&mut self, hand: &[Card], _: u32, _: &[u32], _: &[Round]) -> u32 {
+            match hand.len() {
+                2 => 2,
+                _ => 1,
+            }
+        }
+    }
+
+    #[test]
+    fn small_bets_check_below_the_big_blind() {
+        let players = (0..2)
+            .map(|i| {
+                Player::from_keypair(Keypair::generate_with(OsRng), 100, i)
+                    .with_strategy(Box::new(SmallBets))
+            })
+            .collect();
+        let game = Game::new(2, players).unwrap().with_variant(Variant::Holdem);
+        let mut tournament = Tournament::new(game, Schedule::standard());
+        let round = tournament.play_hand().unwrap();
+        // a point is below the big blind, so the streets after the flop are checked through to
+        // the showdown instead of folded
+        assert!(round
+            .actions
+            .iter()
+            .all(|(_, action)| *action != Action::Fold));
+        assert_eq!(round.community.len(), 5);
+        assert_eq!(round.contributions, vec![2, 2]);
+        assert!(round.hands.iter().all(|hand| hand.is_some()));
+    }
+
+    #[test]
+    fn tournament_runs_to_a_verified_winner() {
+        let mut tournament = new_tournament(3);
+        let standings = tournament.run();
+        assert_eq!(standings.len(), 3);
+        assert_eq!(standings[0].points, 300);
+        assert_eq!(Some(standings[0].seat), tournament.game.winner());
+        assert!(standings[1..].iter().all(|standing| standing.points == 0));
+        let mut transcript = tournament.game.transcript.clone();
+        assert_eq!(verify_tournament(&transcript), Ok(standings));
+        // the same hands without the blinds don't add up
+        transcript.schedule = Schedule::default();
+        assert!(verify_tournament(&transcript).is_err());
+    }
+}
@@ -0,0 +1,1431 @@
+//! Signed game transcripts.
+//!
+//! The game records every message the players send: commitments and reveals on every street,
+//! betting actions, discards and the card draw of every slot of their hands, or the shuffles and
+//! decryptions of the finite deck, each signed by the player who sent it with their schnorrkel
+//! key. Before the first round every player signs that they join the game of the header: its
+//! variant, dealing mode, schedule and deposit, and every player with their starting points.
+//! After every round the game also records the points of every player, which nobody signs, and
+//! when a player misses a deadline, every other player of the phase signs that they witnessed
+//! the timeout, since the message that never came can't be signed. The transcript can be saved to a
+//! text file, one entry per line, and checked offline with `verify_transcript`, which replays the
+//! whole game from the signed messages alone, with the same round flow as the game, and
+//! recomputes the points.
+//!
+//! The signatures cover the game id, so a message cannot be replayed into another game, and the
+//! round number, so it cannot be replayed into another round.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use curve25519_dalek::ristretto::RistrettoPoint;
+use schnorrkel::{
+    signing_context,
+    vrf::{VRFPreOut, VRFProof},
+    Keypair, PublicKey, Signature,
+};
+use sp_core::blake2_256;
+
+use super::betting::{Action, BettingRound};
+use super::card::Card;
+use super::card::DECK_SIZE;
+use super::cheating::CheatingEvidence;
+use super::deadline::{MissedDeadline, Phase};
+use super::deck::{point_from_hex, point_to_hex, scalar_from_hex, scalar_to_hex, DecryptionProof};
+use super::game::{MAX_PLAYERS, MIN_PLAYERS};
+use super::ledger::Ledger;
+use super::player::Player;
+use super::round::{self, Dealing, Messages, Seating, Variant};
+use super::shuffle::{opening_from_hex, opening_to_hex, Opening, Shuffle};
+use super::tournament::Schedule;
+use super::vrf::CardDraw;
+use crate::p1_hashing::HashValue;
+
+/// The signing context of every transcript entry
+pub const TRANSCRIPT_CONTEXT: &[u8] = b"infinite deck poker transcript";
+
+/// Something that happened in a game
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Entry {
+    /// A player joined the game from their seat before the first round, agreeing to the header of
+    /// the transcript, of which this is the hash
+    Join { seat: usize, header: [u8; 32] },
+    /// A player posted a commitment to their random value for a street
+    Commitment {
+        round: u32,
+        seat: usize,
+        street: u32,
+        commitment: HashValue,
+    },
+    /// A player revealed their random value for a street
+    Reveal {
+        round: u32,
+        seat: usize,
+        street: u32,
+        reveal: String,
+    },
+    /// A player took a betting action
+    Action {
+        round: u32,
+        seat: usize,
+        action: Action,
+    },
+    /// A player announced the slots of their hand they discard in a draw round
+    Discard {
+        round: u32,
+        seat: usize,
+        draw_round: u32,
+        slots: Vec<usize>,
+    },
+    /// A player sent the card they drew in a slot of their hand in a draw round to another player
+    /// at the showdown
+    Draw {
+        round: u32,
+        seat: usize,
+        recipient: usize,
+        slot: usize,
+        draw_round: u32,
+        draw: CardDraw,
+    },
+    /// A player encrypted every card of the finite deck and shuffled it, with the shadow shuffles
+    /// of its proof
+    Shuffle {
+        round: u32,
+        seat: usize,
+        shuffle: Shuffle,
+    },
+    /// A player answered the challenges of their shuffle
+    Opening {
+        round: u32,
+        seat: usize,
+        openings: Vec<Opening>,
+    },
+    /// A player removed their layer of encryption from the card at a position of the finite deck,
+    /// with the proof that they removed the layer of the key they shuffled with
+    Decrypt {
+        round: u32,
+        seat: usize,
+        position: usize,
+        point: RistrettoPoint,
+        proof: DecryptionProof,
+    },
+    /// The points of every player at the end of a round, recorded by the game
+    Points { round: u32, points: Vec<u32> },
+    /// A witness saw the player in a seat not answer in a phase before its deadline passed at some
+    /// tick. Every other player of the phase signs a timeout of their own, one after the other.
+    Timeout {
+        round: u32,
+        seat: usize,
+        phase: Phase,
+        tick: u64,
+        witness: usize,
+    },
+}
+
+impl Entry {
+    /// The round the entry belongs to, which is the first one for the players joining the game
+    pub fn round(&self) -> u32 {
+        match self {
+            Self::Join { .. } => 0,
+            Self::Commitment { round, .. }
+            | Self::Reveal { round, .. }
+            | Self::Action { round, .. }
+            | Self::Discard { round, .. }
+            | Self::Draw { round, .. }
+            | Self::Shuffle { round, .. }
+            | Self::Opening { round, .. }
+            | Self::Decrypt { round, .. }
+            | Self::Points { round, .. }
+            | Self::Timeout { round, .. } => *round,
+        }
+    }
+
+    /// The seat of the player who sent the entry and has to sign it, or None for the entries the
+    /// game records itself
+    pub fn seat(&self) -> Option<usize> {
+        match self {
+            Self::Join { seat, .. }
+            | Self::Commitment { seat, .. }
+            | Self::Reveal { seat, .. }
+            | Self::Action { seat, .. }
+            | Self::Discard { seat, .. }
+            | Self::Draw { seat, .. }
+            | Self::Shuffle { seat, .. }
+            | Self::Opening { seat, .. }
+            | Self::Decrypt { seat, .. } => Some(*seat),
+            Self::Timeout { witness, .. } => Some(*witness),
+            Self::Points { .. } => None,
+        }
+    }
+
+    /// The entry as a line of text, with the fields separated by spaces
+    pub fn to_line(&self) -> String {
+        match self {
+            Self::Join { seat, header } => format!("join {} {}", seat, hex::encode(header)),
+            Self::Commitment {
+                round,
+                seat,
+                street,
+                commitment,
+            } => format!(
+                "commit {} {} {} {}",
+                round,
+                seat,
+                street,
+                hex::encode(commitment)
+            ),
+            Self::Reveal {
+                round,
+                seat,
+                street,
+                reveal,
+            } => format!(
+                "reveal {} {} {} {}",
+                round,
+                seat,
+                street,
+                hex::encode(reveal)
+            ),
+            Self::Action {
+                round,
+                seat,
+                action,
+            } => format!("action {} {} {}", round, seat, action_to_str(action)),
+            Self::Discard {
+                round,
+                seat,
+                draw_round,
+                slots,
+            } => {
+                let slots: Vec<String> = slots.iter().map(|slot| slot.to_string()).collect();
+                let slots = match slots.is_empty() {
+                    true => "-".to_string(),
+                    false => slots.join(","),
+                };
+                format!("discard {} {} {} {}", round, seat, draw_round, slots)
+            }
+            Self::Draw {
+                round,
+                seat,
+                recipient,
+                slot,
+                draw_round,
+                draw,
+            } => format!(
+                "draw {} {} {} {} {} {} {}",
+                round,
+                seat,
+                recipient,
+                slot,
+                draw_round,
+                hex::encode(draw.output.to_bytes()),
+                hex::encode(draw.proof.to_bytes())
+            ),
+            Self::Shuffle {
+                round,
+                seat,
+                shuffle,
+            } => {
+                let deck_to_hex = |deck: &[RistrettoPoint]| {
+                    let deck: Vec<String> = deck.iter().map(point_to_hex).collect();
+                    deck.join(",")
+                };
+                let shadows: Vec<String> = shuffle.shadows.iter().map(|s| deck_to_hex(s)).collect();
+                format!(
+                    "shuffle {} {} {} {} {} {}",
+                    round,
+                    seat,
+                    point_to_hex(&shuffle.key),
+                    deck_to_hex(&shuffle.deck),
+                    deck_to_hex(&shuffle.shadow_keys),
+                    shadows.join(";")
+                )
+            }
+            Self::Opening {
+                round,
+                seat,
+                openings,
+            } => {
+                let openings: Vec<String> = openings.iter().map(opening_to_hex).collect();
+                format!("opening {} {} {}", round, seat, openings.join(";"))
+            }
+            Self::Decrypt {
+                round,
+                seat,
+                position,
+                point,
+                proof,
+            } => format!(
+                "decrypt {} {} {} {} {} {}",
+                round,
+                seat,
+                position,
+                point_to_hex(point),
+                scalar_to_hex(&proof.challenge),
+                scalar_to_hex(&proof.response)
+            ),
+            Self::Points { round, points } => {
+                let points: Vec<String> = points.iter().map(|p| p.to_string()).collect();
+                format!("points {} {}", round, points.join(","))
+            }
+            Self::Timeout {
+                round,
+                seat,
+                phase,
+                tick,
+                witness,
+            } => format!("timeout {} {} {} {} {}", round, seat, phase, tick, witness),
+        }
+    }
+
+    /// Parse an entry from a line of text. Errors if the line is malformed.
+    pub fn from_line(line: &str) -> Result<Self, ()> {
+        let parts: Vec<&str> = line.split(' ').collect();
+        let round = parts.get(1).ok_or(())?.parse().map_err(|_| ())?;
+        let seat_at =
+            |i: usize| -> Result<usize, ()> { parts.get(i).ok_or(())?.parse().map_err(|_| ()) };
+        let seat = || seat_at(2);
+        match (parts[0], parts.len()) {
+            ("join", 3) => Ok(Self::Join {
+                seat: seat_at(1)?,
+                header: decode_hex(parts[2])?,
+            }),
+            ("commit", 5) => Ok(Self::Commitment {
+                round,
+                seat: seat()?,
+                street: parts[3].parse().map_err(|_| ())?,
+                commitment: decode_hex(parts[4])?,
+            }),
+            ("reveal", 5) => Ok(Self::Reveal {
+                round,
+                seat: seat()?,
+                street: parts[3].parse().map_err(|_| ())?,
+                reveal: String::from_utf8(hex::decode(parts[4]).map_err(|_| ())?)
+                    .map_err(|_| ())?,
+            }),
+            ("action", 4) => Ok(Self::Action {
+                round,
+                seat: seat()?,
+                action: action_from_str(parts[3])?,
+            }),
+            ("discard", 5) => Ok(Self::Discard {
+                round,
+                seat: seat()?,
+                draw_round: parts[3].parse().map_err(|_| ())?,
+                slots: match parts[4] {
+                    "-" => Vec::new(),
+                    slots => slots
+                        .split(',')
+                        .map(|slot| slot.parse().map_err(|_| ()))
+                        .collect::<Result<_, ()>>()?,
+                },
+            }),
+            ("draw", 8) => Ok(Self::Draw {
+                round,
+                seat: seat()?,
+                recipient: parts[3].parse().map_err(|_| ())?,
+                slot: parts[4].parse().map_err(|_| ())?,
+                draw_round: parts[5].parse().map_err(|_| ())?,
+                draw: CardDraw {
+                    output: VRFPreOut::from_bytes(&decode_hex::<32>(parts[6])?).map_err(|_| ())?,
+                    proof: VRFProof::from_bytes(&decode_hex::<64>(parts[7])?).map_err(|_| ())?,
+                },
+            }),
+            ("shuffle", 7) => {
+                let deck_from_hex = |s: &str| -> Result<Vec<RistrettoPoint>, ()> {
+                    s.split(',').map(point_from_hex).collect()
+                };
+                Ok(Self::Shuffle {
+                    round,
+                    seat: seat()?,
+                    shuffle: Shuffle {
+                        key: point_from_hex(parts[3])?,
+                        deck: deck_from_hex(parts[4])?,
+                        shadow_keys: deck_from_hex(parts[5])?,
+                        shadows: parts[6]
+                            .split(';')
+                            .map(deck_from_hex)
+                            .collect::<Result<_, ()>>()?,
+                    },
+                })
+            }
+            ("opening", 4) => Ok(Self::Opening {
+                round,
+                seat: seat()?,
+                openings: parts[3]
+                    .split(';')
+                    .map(opening_from_hex)
+                    .collect::<Result<_, ()>>()?,
+            }),
+            ("decrypt", 7) => Ok(Self::Decrypt {
+                round,
+                seat: seat()?,
+                position: parts[3].parse().map_err(|_| ())?,
+                point: point_from_hex(parts[4])?,
+                proof: DecryptionProof {
+                    challenge: scalar_from_hex(parts[5])?,
+                    response: scalar_from_hex(parts[6])?,
+                },
+            }),
+            ("points", 3) => Ok(Self::Points {
+                round,
+                points: parts[2]
+                    .split(',')
+                    .map(|p| p.parse().map_err(|_| ()))
+                    .collect::<Result<_, ()>>()?,
+            }),
+            ("timeout", 6) => Ok(Self::Timeout {
+                round,
+                seat: seat()?,
+                phase: Phase::from_name(parts[3])?,
+                tick: parts[4].parse().map_err(|_| ())?,
+                witness: seat_at(5)?,
+            }),
+            _ => Err(()),
+        }
+    }
+
+    /// The message a player signs for this entry, bound to the game
+    fn signing_message(&self, game_id: u64) -> Vec<u8> {
+        format!("{}/{}", game_id, self.to_line()).into_bytes()
+    }
+}
+
+fn action_to_str(action: &Action) -> String {
+    match action {
+        Action::Check => "check".to_string(),
+        Action::Bet(amount) => format!("bet:{}", amount),
+        Action::Call => "call".to_string(),
+        Action::Raise(amount) => format!("raise:{}", amount),
+        Action::Fold => "fold".to_string(),
+    }
+}
+
+fn action_from_str(s: &str) -> Result<Action, ()> {
+    let amount = |a: &str| a.parse::<u32>().map_err(|_| ());
+    match s.split_once(':') {
+        None if s == "check" => Ok(Action::Check),
+        None if s == "call" => Ok(Action::Call),
+        None if s == "fold" => Ok(Action::Fold),
+        Some(("bet", a)) => Ok(Action::Bet(amount(a)?)),
+        Some(("raise", a)) => Ok(Action::Raise(amount(a)?)),
+        _ => Err(()),
+    }
+}
+
+fn decode_hex<const N: usize>(s: &str) -> Result<[u8; N], ()> {
+    let mut bytes = [0u8; N];
+    hex::decode_to_slice(s, &mut bytes).map_err(|_| ())?;
+    Ok(bytes)
+}
+
+/// An entry of the transcript, with the signature of the player who sent it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    pub entry: Entry,
+    /// The signature of the player who sent the entry, or None for the entries the game records
+    pub signature: Option<Signature>,
+}
+
+impl Record {
+    /// Sign an entry of a game with the keypair of the player who sent it
+    pub fn signed(entry: Entry, keypair: &Keypair, game_id: u64) -> Self {
+        let context = signing_context(TRANSCRIPT_CONTEXT);
+        let signature = keypair.sign(context.bytes(&entry.signing_message(game_id)));
+        Record {
+            entry,
+            signature: Some(signature),
+        }
+    }
+
+    /// The record as a line of text: the entry, followed by its signature if it has one
+    pub fn to_line(&self) -> String {
+        match &self.signature {
+            Some(signature) => format!(
+                "{} {}",
+                self.entry.to_line(),
+                hex::encode(signature.to_bytes())
+            ),
+            None => self.entry.to_line(),
+        }
+    }
+
+    /// Parse a record from a line of text. Errors if the line is malformed.
+    pub fn from_line(line: &str) -> Result<Self, ()> {
+        match line.starts_with("points ") {
+            true => Ok(Record {
+                entry: Entry::from_line(line)?,
+                signature: None,
+            }),
+            false => {
+                let (entry, signature) = line.rsplit_once(' ').ok_or(())?;
+                let signature =
+                    Signature::from_bytes(&decode_hex::<64>(signature)?).map_err(|_| ())?;
+                Ok(Record {
+                    entry: Entry::from_line(entry)?,
+                    signature: Some(signature),
+                })
+            }
+        }
+    }
+
+    /// Whether the record is signed with a public key, in a game
+    pub fn is_signed_by(&self, public: &PublicKey, game_id: u64) -> bool {
+        let context = signing_context(TRANSCRIPT_CONTEXT);
+        let message = self.entry.signing_message(game_id);
+        match &self.signature {
+            Some(signature) => public.verify(context.bytes(&message), signature).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// Everything that happened in a game, from the players and their starting points to the points
+/// at the end of the last round
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transcript {
+    pub game_id: u64,
+    /// The variant of poker played
+    pub variant: Variant,
+    /// How the cards are dealt
+    pub dealing: Dealing,
+    /// The blinds of every round
+    pub schedule: Schedule,
+    /// The deposit every player put aside when they sat down
+    pub deposit: u32,
+    /// The public keys of the players, by seat
+    pub players: Vec<PublicKey>,
+    /// The points each player started with, by seat
+    pub starting_points: Vec<u32>,
+    pub records: Vec<Record>,
+}
+
+impl Transcript {
+    /// Start the transcript of a game between some players, of five-card poker from the infinite
+    /// deck until the game says otherwise
+    pub fn new(game_id: u64, players: &[Player]) -> Self {
+        Transcript {
+            game_id,
+            variant: Variant::default(),
+            dealing: Dealing::default(),
+            schedule: Schedule::default(),
+            deposit: 0,
+            players: players.iter().map(|player| player.public()).collect(),
+            starting_points: players.iter().map(|player| player.points).collect(),
+            records: Vec::new(),
+        }
+    }
+
+    /// Sign an entry with the keypair of the player who sent it, and add it to the transcript
+    pub fn sign(&mut self, entry: Entry, keypair: &Keypair) {
+        self.records
+            .push(Record::signed(entry, keypair, self.game_id));
+    }
+
+    /// Add an entry that nobody signs, like the points at the end of a round
+    pub fn record(&mut self, entry: Entry) {
+        self.records.push(Record {
+            entry,
+            signature: None,
+        });
+    }
+
+    /// Parse a transcript from its text, as written by `to_string`. Errors if it is malformed.
+    pub fn from_text(text: &str) -> Result<Self, ()> {
+        let mut lines = text.lines();
+        let game_id = lines
+            .next()
+            .and_then(|line| line.strip_prefix("game "))
+            .ok_or(())?
+            .parse()
+            .map_err(|_| ())?;
+
+        let mut transcript = Transcript {
+            game_id,
+            variant: Variant::default(),
+            dealing: Dealing::default(),
+            schedule: Schedule::default(),
+            deposit: 0,
+            players: Vec::new(),
+            starting_points: Vec::new(),
+            records: Vec::new(),
+        };
+        for line in lines {
+            if let Some(name) = line.strip_prefix("variant ") {
+                transcript.variant = Variant::from_name(name)?;
+                continue;
+            }
+            if let Some(name) = line.strip_prefix("dealing ") {
+                transcript.dealing = Dealing::from_name(name)?;
+                continue;
+            }
+            if let Some(schedule) = line.strip_prefix("schedule ") {
+                transcript.schedule = Schedule::from_text(schedule)?;
+                continue;
+            }
+            if let Some(deposit) = line.strip_prefix("deposit ") {
+                transcript.deposit = deposit.parse().map_err(|_| ())?;
+                continue;
+            }
+            if let Some(player) = line.strip_prefix("player ") {
+                let (public, points) = player.split_once(' ').ok_or(())?;
+                let public = PublicKey::from_bytes(&decode_hex::<32>(public)?).map_err(|_| ())?;
+                transcript.players.push(public);
+                transcript
+                    .starting_points
+                    .push(points.parse().map_err(|_| ())?);
+                continue;
+            }
+            transcript.records.push(Record::from_line(line)?);
+        }
+        Ok(transcript)
+    }
+
+    /// The hash of the header of the transcript, which every player signs when they join the
+    /// game: everything before the first entry, as written by `to_string`
+    pub fn header(&self) -> [u8; 32] {
+        blake2_256(self.header_text().as_bytes())
+    }
+
+    /// The lines of the header, before the first entry
+    fn header_text(&self) -> String {
+        let mut lines = vec![
+            format!("game {}", self.game_id),
+            format!("variant {}", self.variant.name()),
+            format!("dealing {}", self.dealing.name()),
+            format!("schedule {}", self.schedule),
+            format!("deposit {}", self.deposit),
+        ];
+        for (public, points) in self.players.iter().zip(&self.starting_points) {
+            lines.push(format!(
+                "player {} {}",
+                hex::encode(public.to_bytes()),
+                points
+            ));
+        }
+        lines.join("\n")
+    }
+
+    /// The points of every player after each round, as recorded by the game
+    pub fn points_by_round(&self) -> Vec<Vec<u32>> {
+        self.records
+            .iter()
+            .filter_map(|record| match &record.entry {
+                Entry::Points { points, .. } => Some(points.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The players who missed a deadline and left the table, with the round they left in, once
+    /// for all the witnesses of their timeout
+    pub fn timeouts(&self) -> Vec<(u32, MissedDeadline)> {
+        let mut timeouts: Vec<(u32, MissedDeadline)> = self
+            .records
+            .iter()
+            .filter_map(|record| match &record.entry {
+                Entry::Timeout {
+                    round, seat, phase, ..
+                } => Some((
+                    *round,
+                    MissedDeadline {
+                        seat: *seat,
+                        phase: *phase,
+                    },
+                )),
+                _ => None,
+            })
+            .collect();
+        timeouts.dedup();
+        timeouts
+    }
+
+    /// Write the transcript to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Read a transcript from a file. Errors if the file can't be read or is malformed.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ()> {
+        Self::from_text(&fs::read_to_string(path).map_err(|_| ())?)
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.header_text())?;
+        for record in &self.records {
+            writeln!(f, "{}", record.to_line())?;
+        }
+        Ok(())
+    }
+}
+
+/// Why a transcript does not verify
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TranscriptError {
+    /// The record at this index is not signed by the player who sent it
+    BadSignature(usize),
+    /// The record at this index is not what the game expected next
+    UnexpectedEntry(usize),
+    /// The points recorded at the end of this round are not the ones the replay computed
+    WrongPoints(u32),
+    /// The transcript ends in the middle of a round
+    Truncated,
+    /// The header does not seat a number of players who can play, or their points and deposits
+    /// add up to more than can be counted
+    BadHeader,
+}
+
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BadSignature(index) => write!(f, "entry {} has a bad signature", index),
+            Self::UnexpectedEntry(index) => write!(f, "entry {} was not expected", index),
+            Self::WrongPoints(round) => write!(f, "the points after round {} are wrong", round),
+            Self::Truncated => write!(f, "the transcript ends in the middle of a round"),
+            Self::BadHeader => write!(f, "the header is not a game that can be played"),
+        }
+    }
+}
+
+/// The result of replaying a transcript
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifiedGame {
+    /// The points of every player at the end of the transcript, by seat
+    pub points: Vec<u32>,
+    /// The evidence against every player caught cheating during the game
+    pub evidence: Vec<CheatingEvidence>,
+    /// The players who missed a deadline and left the table during the game
+    pub missed: Vec<MissedDeadline>,
+}
+
+/// Replay a transcript, checking every signature, commitment, reveal, betting action, VRF proof,
+/// shuffle proof and card decrypted from the finite deck, and recompute the points of every
+/// player after each round. The points are only computed from what the players signed: the
+/// header every player joined, starting points included, and their messages.
+///
+/// Cheating is not an error: a transcript where a player cheated is valid as long as the cheater
+/// forfeited, and the evidence against them is returned with the points. Neither is a missed
+/// deadline, as long as the player forfeited their deposit and the pot and left the table. Errors
+/// if a signature does not verify, if an entry is not what the rules of the game allow, or if the
+/// points recorded after a round are wrong.
+pub fn verify_transcript(transcript: &Transcript) -> Result<VerifiedGame, TranscriptError> {
+    // the header comes from the file like the rest, so it is checked before the ledger adds up
+    // the points of the players
+    let seats = transcript.players.len();
+    if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&seats) || transcript.starting_points.len() != seats {
+        return Err(TranscriptError::BadHeader);
+    }
+    let total = transcript
+        .starting_points
+        .iter()
+        .try_fold(0u32, |total, points| total.checked_add(*points))
+        .and_then(|total| total.checked_add(transcript.deposit.checked_mul(seats as u32)?));
+    if total.is_none() {
+        return Err(TranscriptError::BadHeader);
+    }
+
+    for (index, record) in transcript.records.iter().enumerate() {
+        let seat = match record.entry.seat() {
+            Some(seat) => seat,
+            None => continue,
+        };
+        let public = transcript
+            .players
+            .get(seat)
+            .ok_or(TranscriptError::UnexpectedEntry(index))?;
+        if !record.is_signed_by(public, transcript.game_id) {
+            return Err(TranscriptError::BadSignature(index));
+        }
+    }
+
+    let mut replay = Replay::at(transcript, 0);
+    for seat in 0..transcript.players.len() {
+        replay.join(seat)?;
+    }
+    let deposits = vec![transcript.deposit; transcript.players.len()];
+    let mut ledger = Ledger::new(transcript.starting_points.clone()).with_deposits(deposits);
+    let mut evidence = Vec::new();
+    let mut missed = Vec::new();
+    let mut number = 0;
+    let mut button = None;
+    while replay.next < transcript.records.len() {
+        let points = ledger.stacks();
+        let left = |seat: &usize| missed.iter().any(|m: &MissedDeadline| m.seat == *seat);
+        let seats: Vec<usize> = (0..points.len())
+            .filter(|seat| points[*seat] > 0 && !left(seat))
+            .collect();
+        if seats.len() < MIN_PLAYERS {
+            return Err(TranscriptError::UnexpectedEntry(replay.next));
+        }
+        let seating = Seating {
+            stacks: seats.iter().map(|seat| points[*seat]).collect(),
+            button: round::next_button(button, &seats),
+            seats,
+        };
+        button = Some(seating.button);
+        let result = round::play(
+            &mut replay,
+            transcript.game_id,
+            transcript.variant,
+            transcript.dealing,
+            transcript.schedule.blinds(number),
+            number,
+            &seating,
+        )?;
+        ledger.settle(&seating.seats, &result);
+        match result {
+            Ok(round) => missed.extend(round.missed),
+            Err(caught) => evidence.push(caught),
+        }
+        match replay.take()? {
+            Entry::Points {
+                round,
+                points: recorded,
+            } if *round == number && recorded == ledger.stacks() => {}
+            _ => return Err(TranscriptError::WrongPoints(number)),
+        }
+        number += 1;
+    }
+    Ok(VerifiedGame {
+        points: ledger.stacks().to_vec(),
+        evidence,
+        missed,
+    })
+}
+
+/// The messages of a transcript, read back in order to replay the game
+pub(crate) struct Replay<'a> {
+    transcript: &'a Transcript,
+    /// The index of the next record to replay
+    next: usize,
+}
+
+impl<'a> Replay<'a> {
+    /// Replay the messages of a transcript from the record at some index
+    pub(crate) fn at(transcript: &'a Transcript, next: usize) -> Self {
+        Replay { transcript, next }
+    }
+
+    /// Take the next entry
+    fn take(&mut self) -> Result<&'a Entry, TranscriptError> {
+        let record = self
+            .transcript
+            .records
+            .get(self.next)
+            .ok_or(TranscriptError::Truncated)?;
+        self.next += 1;
+        Ok(&record.entry)
+    }
+
+    /// Take the next entry, which must be from a player in a round
+    fn take_from(&mut self, round: u32, seat: usize) -> Result<&'a Entry, TranscriptError> {
+        let entry = self.take()?;
+        match entry.seat() == Some(seat) && entry.round() == round {
+            true => Ok(entry),
+            false => Err(self.unexpected()),
+        }
+    }
+
+    /// Take the entry of the player in a seat joining the game, which must be for the header of
+    /// the transcript
+    pub(crate) fn join(&mut self, seat: usize) -> Result<(), TranscriptError> {
+        let header = self.transcript.header();
+        match self.take()? {
+            Entry::Join { seat: s, header: h } if *s == seat && *h == header => Ok(()),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// The error for the last entry taken, which is not what the game expected
+    fn unexpected(&self) -> TranscriptError {
+        TranscriptError::UnexpectedEntry(self.next - 1)
+    }
+}
+
+impl Messages for Replay<'_> {
+    type Error = TranscriptError;
+
+    fn public(&self, seat: usize) -> PublicKey {
+        self.transcript.players[seat]
+    }
+
+    fn commitments(
+        &mut self,
+        seat: usize,
+        number: u32,
+        street: u32,
+    ) -> Result<Vec<HashValue>, TranscriptError> {
+        let mut posted = match self.take_from(number, seat)? {
+            Entry::Commitment {
+                street: s,
+                commitment,
+                ..
+            } if *s == street => vec![*commitment],
+            _ => return Err(self.unexpected()),
+        };
+        // a second commitment right after the first is a double commitment
+        if let Some(Entry::Commitment {
+            round,
+            seat: other,
+            street: s,
+            commitment,
+        }) = self.transcript.records.get(self.next).map(|r| &r.entry)
+        {
+            if *round == number && *other == seat && *s == street {
+                self.next += 1;
+                posted.push(*commitment);
+            }
+        }
+        Ok(posted)
+    }
+
+    fn on_time(
+        &mut self,
+        seat: usize,
+        number: u32,
+        phase: Phase,
+        seats: &[usize],
+    ) -> Result<bool, TranscriptError> {
+        // a timeout of the player in this phase takes the place of their message, and only counts
+        // if every other player of the phase witnessed it at the same tick
+        let claimed = match self.transcript.records.get(self.next).map(|r| &r.entry) {
+            Some(Entry::Timeout {
+                round,
+                seat: s,
+                phase: p,
+                tick,
+                ..
+            }) if *round == number && *s == seat && *p == phase => *tick,
+            _ => return Ok(true),
+        };
+        for witness in seats.iter().filter(|s| **s != seat) {
+            match self.take()? {
+                Entry::Timeout {
+                    round,
+                    seat: s,
+                    phase: p,
+                    tick,
+                    witness: w,
+                } if *round == number
+                    && *s == seat
+                    && *p == phase
+                    && *tick == claimed
+                    && w == witness => {}
+                _ => return Err(self.unexpected()),
+            }
+        }
+        Ok(false)
+    }
+
+    fn reveal(&mut self, seat: usize, number: u32, street: u32) -> Result<String, TranscriptError> {
+        match self.take_from(number, seat)? {
+            Entry::Reveal {
+                street: s, reveal, ..
+            } if *s == street => Ok(reveal.clone()),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn shuffle(
+        &mut self,
+        seat: usize,
+        number: u32,
+        _deck: &[RistrettoPoint],
+    ) -> Result<Shuffle, TranscriptError> {
+        match self.take_from(number, seat)? {
+            Entry::Shuffle { shuffle, .. } if shuffle.deck.len() == DECK_SIZE as usize => {
+                Ok(shuffle.clone())
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn open_shuffle(
+        &mut self,
+        seat: usize,
+        number: u32,
+        _challenges: &[bool],
+    ) -> Result<Vec<Opening>, TranscriptError> {
+        match self.take_from(number, seat)? {
+            Entry::Opening { openings, .. } => Ok(openings.clone()),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn decrypt(
+        &mut self,
+        seat: usize,
+        number: u32,
+        position: usize,
+        _point: &RistrettoPoint,
+    ) -> Result<(RistrettoPoint, DecryptionProof), TranscriptError> {
+        match self.take_from(number, seat)? {
+            Entry::Decrypt {
+                position: p,
+                point,
+                proof,
+                ..
+            } if *p == position => Ok((*point, *proof)),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn action(
+        &mut self,
+        seat: usize,
+        number: u32,
+        _seats: &[usize],
+        betting: &BettingRound,
+        _community: &[Card],
+    ) -> Result<Action, TranscriptError> {
+        match self.take_from(number, seat)? {
+            Entry::Action { action, .. } if betting.allows(*action) => Ok(*action),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn discard(
+        &mut self,
+        seat: usize,
+        number: u32,
+        draw_round: u32,
+        hand_size: usize,
+        most: usize,
+    ) -> Result<Vec<usize>, TranscriptError> {
+        match self.take_from(number, seat)? {
+            Entry::Discard {
+                draw_round: d,
+                slots,
+                ..
+            } if *d == draw_round
+                && slots.windows(2).all(|pair| pair[0] < pair[1])
+                && slots.iter().all(|slot| *slot < hand_size)
+                && slots.len() <= most =>
+            {
+                Ok(slots.clone())
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn draws(
+        &mut self,
+        seat: usize,
+        number: u32,
+        _common_random: &[u8; 32],
+        recipients: &[usize],
+        cards: &[(usize, u32)],
+    ) -> Result<Vec<Vec<CardDraw>>, TranscriptError> {
+        let mut draws = Vec::new();
+        for recipient in recipients {
+            let mut sent = Vec::new();
+            for (slot, draw_round) in cards {
+                match self.take_from(number, seat)? {
+                    Entry::Draw {
+                        recipient: to,
+                        slot: s,
+                        draw_round: d,
+                        draw,
+                        ..
+                    } if to == recipient && s == slot && d == draw_round => sent.push(draw.clone()),
+                    _ => return Err(self.unexpected()),
+                }
+            }
+            draws.push(sent);
+        }
+        Ok(draws)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poker::cheating::Cheat;
+    use crate::poker::game::Game;
+    use crate::poker::strategy::ConstantStrategy;
+    use rand_core::OsRng;
+
+    fn play_game(cheat: Option<Cheat>) -> Game {
+        play_variant(Variant::FiveCard, cheat)
+    }
+
+    fn play_variant(variant: Variant, cheat: Option<Cheat>) -> Game {
+        play_dealt(variant, Dealing::Vrf, cheat)
+    }
+
+    fn play_dealt(variant: Variant, dealing: Dealing, cheat: Option<Cheat>) -> Game {
+        let players = (0..3)
+            .map(|i| {
+                Player::from_keypair(Keypair::generate_with(OsRng), 100, i)
+                    .with_strategy(Box::new(ConstantStrategy(10 + i as u32)))
+            })
+            .collect();
+        let mut game = Game::new(5, players)
+            .unwrap()
+            .with_variant(variant)
+            .with_dealing(dealing);
+        game.players[1].cheat = cheat;
+        for _ in 0..5 {
+            let _ = game.play_round();
+        }
+        game
+    }
+
+    fn points(game: &Game) -> Vec<u32> {
+        game.players.iter().map(|player| player.points).collect()
+    }
+
+    #[test]
+    fn honest_transcript_verifies() {
+        let game = play_game(None);
+        let verified = verify_transcript(&game.transcript).unwrap();
+        assert_eq!(verified.points, points(&game));
+        assert!(verified.evidence.is_empty());
+    }
+
+    #[test]
+    fn holdem_transcript_verifies() {
+        for cheat in [None, Some(Cheat::BadReveal), Some(Cheat::Equivocation)] {
+            let game = play_variant(Variant::Holdem, cheat);
+            let text = game.transcript.to_string();
+            let transcript = Transcript::from_text(&text).unwrap();
+            assert_eq!(transcript.variant, Variant::Holdem);
+            let verified = verify_transcript(&transcript).unwrap();
+            assert_eq!(verified.points, points(&game));
+            assert_eq!(verified.evidence, game.evidence);
+        }
+    }
+
+    #[test]
+    fn draw_transcript_verifies() {
+        for cheat in [
+            None,
+            Some(Cheat::InvalidVrfProof),
+            Some(Cheat::Equivocation),
+        ] {
+            let game = play_variant(Variant::Draw, cheat);
+            let transcript = Transcript::from_text(&game.transcript.to_string()).unwrap();
+            assert_eq!(transcript, game.transcript);
+            let verified = verify_transcript(&transcript).unwrap();
+            assert_eq!(verified.points, points(&game));
+            assert_eq!(verified.evidence, game.evidence);
+        }
+    }
+
+    #[test]
+    fn discards_are_signed_before_the_draw_random() {
+        let game = play_variant(Variant::Draw, None);
+        let records = &game.transcript.records;
+        let discard = records
+            .iter()
+            .position(|record| matches!(record.entry, Entry::Discard { .. }))
+            .unwrap();
+        let (round, seat, draw_round, slots) = match records[discard].entry.clone() {
+            Entry::Discard {
+                round,
+                seat,
+                draw_round,
+                slots,
+            } => (round, seat, draw_round, slots),
+            _ => unreachable!(),
+        };
+        // nobody commits to the random value of the draw before every discard of the round
+        let draw_commitment = records
+            .iter()
+            .position(|record| match record.entry {
+                Entry::Commitment {
+                    round: r, street, ..
+                } => r == round && street == 1,
+                _ => false,
+            })
+            .unwrap();
+        assert!(records[draw_commitment..]
+            .iter()
+            .all(|record| !matches!(record.entry, Entry::Discard { round: r, .. } if r == round)));
+        // and a player can't change their discards once it is revealed, even by signing again
+        let other = match slots.is_empty() {
+            true => vec![0],
+            false => slots[1..].to_vec(),
+        };
+        let mut resigned = Transcript {
+            records: Vec::new(),
+            ..game.transcript.clone()
+        };
+        let entry = Entry::Discard {
+            round,
+            seat,
+            draw_round,
+            slots: other,
+        };
+        resigned.sign(entry, &game.players[seat].keypair);
+        let mut forged = game.transcript.clone();
+        forged.records[discard] = resigned.records.pop().unwrap();
+        assert!(verify_transcript(&forged).is_err());
+    }
+
+    #[test]
+    fn finite_deck_transcript_verifies() {
+        // the community cards and the replacements are dealt from the deck too
+        for variant in [Variant::Holdem, Variant::Draw] {
+            let game = play_dealt(variant, Dealing::Deck, None);
+            let transcript = Transcript::from_text(&game.transcript.to_string()).unwrap();
+            assert_eq!(transcript, game.transcript);
+            let verified = verify_transcript(&transcript).unwrap();
+            assert_eq!(verified.points, points(&game));
+        }
+    }
+
+    #[test]
+    fn deck_cheaters_are_caught_by_the_verifier() {
+        for cheat in [Cheat::BadShuffle, Cheat::BadDecryption] {
+            let game = play_dealt(Variant::Holdem, Dealing::Deck, Some(cheat));
+            let verified = verify_transcript(&game.transcript).unwrap();
+            assert_eq!(verified.points, points(&game));
+            assert_eq!(verified.evidence, game.evidence);
+            assert_eq!(verified.evidence.len(), 1);
+            assert_eq!(verified.evidence[0].seat(), 1);
+        }
+    }
+
+    #[test]
+    fn variant_is_part_of_the_transcript() {
+        // a hold'em game replayed as five-card poker has the flop where the showdown should be
+        let mut transcript = play_variant(Variant::Holdem, None).transcript;
+        transcript.variant = Variant::FiveCard;
+        assert!(matches!(
+            verify_transcript(&transcript),
+            Err(TranscriptError::UnexpectedEntry(_))
+        ));
+    }
+
+    #[test]
+    fn header_is_signed_by_every_player() {
+        let game = play_game(None);
+        // moving points from a player to another from the start, and in every round after it,
+        // keeps the points consistent but breaks the joins
+        let mut transcript = game.transcript.clone();
+        transcript.starting_points[0] += 10;
+        transcript.starting_points[1] -= 10;
+        for record in transcript.records.iter_mut() {
+            if let Entry::Points { points, .. } = &mut record.entry {
+                points[0] += 10;
+                points[1] -= 10;
+            }
+        }
+        assert_eq!(
+            verify_transcript(&transcript),
+            Err(TranscriptError::UnexpectedEntry(0))
+        );
+        // and so does a larger deposit
+        let mut transcript = game.transcript.clone();
+        transcript.deposit = 50;
+        assert!(verify_transcript(&transcript).is_err());
+        // nor can a join be left out
+        let mut transcript = game.transcript;
+        transcript.records.remove(1);
+        assert_eq!(
+            verify_transcript(&transcript),
+            Err(TranscriptError::UnexpectedEntry(1))
+        );
+    }
+
+    #[test]
+    fn transcript_survives_the_text_format() {
+        let game = play_game(None);
+        let text = game.transcript.to_string();
+        assert_eq!(Transcript::from_text(&text), Ok(game.transcript));
+        assert!(Transcript::from_text("not a transcript").is_err());
+    }
+
+    #[test]
+    fn transcript_with_cheater_gives_the_evidence() {
+        for cheat in [
+            Cheat::BadReveal,
+            Cheat::InvalidVrfProof,
+            Cheat::DoubleCommitment,
+            Cheat::Equivocation,
+        ] {
+            let game = play_game(Some(cheat));
+            let verified = verify_transcript(&game.transcript).unwrap();
+            assert_eq!(verified.points, points(&game));
+            assert_eq!(verified.evidence, game.evidence);
+        }
+    }
+
+    #[test]
+    fn tampered_entry_is_rejected() {
+        let mut transcript = play_game(None).transcript;
+        let index = transcript
+            .records
+            .iter()
+            .position(|record| matches!(record.entry, Entry::Action { .. }))
+            .unwrap();
+        if let Entry::Action { action, .. } = &mut transcript.records[index].entry {
+            *action = Action::Fold;
+        }
+        assert_eq!(
+            verify_transcript(&transcript),
+            Err(TranscriptError::BadSignature(index))
+        );
+    }
+
+    #[test]
+    fn entries_are_bound_to_the_game() {
+        let mut transcript = play_game(None).transcript;
+        transcript.game_id += 1;
+        assert_eq!(
+            verify_transcript(&transcript),
+            Err(TranscriptError::BadSignature(0))
+        );
+    }
+
+    #[test]
+    fn wrong_points_are_rejected() {
+        let mut transcript = play_game(None).transcript;
+        let last = transcript.records.len() - 1;
+        if let Entry::Points { points, .. } = &mut transcript.records[last].entry {
+            points[0] += 1;
+        }
+        let round = transcript.records[last].entry.round();
+        assert_eq!(
+            verify_transcript(&transcript),
+            Err(TranscriptError::WrongPoints(round))
+        );
+    }
+
+    #[test]
+    fn missed_deadlines_verify_from_the_transcript() {
+        for phase in [Phase::Commit, Phase::Reveal, Phase::Showdown] {
+            let game = play_game(Some(Cheat::Stall(phase)));
+            let verified = verify_transcript(&game.transcript).unwrap();
+            assert_eq!(verified.points, points(&game));
+            let missed: Vec<MissedDeadline> = game
+                .rounds
+                .iter()
+                .filter_map(|round| round.missed)
+                .collect();
+            assert_eq!(verified.missed, missed);
+            assert_eq!(game.transcript.timeouts().len(), missed.len());
+        }
+        // nobody can leave the table without the timeout
+        let mut transcript = play_game(Some(Cheat::Stall(Phase::Commit))).transcript;
+        let index = transcript
+            .records
+            .iter()
+            .position(|record| matches!(record.entry, Entry::Timeout { .. }))
+            .unwrap();
+        let text = transcript.to_string();
+        assert_eq!(Transcript::from_text(&text), Ok(transcript.clone()));
+        transcript.records.remove(index);
+        assert_eq!(
+            verify_transcript(&transcript),
+            Err(TranscriptError::UnexpectedEntry(index))
+        );
+    }
+
+    #[test]
+    fn forged_timeout_is_rejected() {
+        let game = play_game(None);
+        let index = game
+            .transcript
+            .records
+            .iter()
+            .position(|record| matches!(record.entry, Entry::Commitment { seat: 1, .. }))
+            .unwrap();
+        let forge = |witnesses: &[usize], signed: bool| {
+            let mut transcript = game.transcript.clone();
+            for (i, witness) in witnesses.iter().enumerate() {
+                let entry = Entry::Timeout {
+                    round: 0,
+                    seat: 1,
+                    phase: Phase::Commit,
+                    tick: 1,
+                    witness: *witness,
+                };
+                transcript.sign(entry, &game.players[*witness].keypair);
+                let mut record = transcript.records.pop().unwrap();
+                if !signed {
+                    record.signature = None;
+                }
+                transcript.records.insert(index + i, record);
+            }
+            verify_transcript(&transcript)
+        };
+        // one player alone can't claim that another one stalled
+        assert_eq!(
+            forge(&[0], true),
+            Err(TranscriptError::UnexpectedEntry(index + 1))
+        );
+        assert_eq!(
+            forge(&[2], true),
+            Err(TranscriptError::UnexpectedEntry(index))
+        );
+        // nor sign the timeout in the name of the others
+        assert_eq!(
+            forge(&[0, 2], false),
+            Err(TranscriptError::BadSignature(index))
+        );
+        // and the staller can't witness their own timeout
+        assert_eq!(
+            forge(&[0, 1], true),
+            Err(TranscriptError::UnexpectedEntry(index + 1))
+        );
+    }
+
+    #[test]
+    fn unplayable_headers_are_rejected() {
+        let player = |points: u32| {
+            let public = Keypair::generate_with(OsRng).public;
+            format!("player {} {}\n", hex::encode(public.to_bytes()), points)
+        };
+        // points that add up to more than a u32 holds
+        let text = format!("game 5\n{}{}", player(u32::MAX), player(u32::MAX));
+        let transcript = Transcript::from_text(&text).unwrap();
+        assert_eq!(
+            verify_transcript(&transcript),
+            Err(TranscriptError::BadHeader)
+        );
+        // and so do the deposits
+        let text = format!(
+            "game 5\ndeposit {}\n{}{}",
+            u32::MAX / 2,
+            player(1),
+            player(1)
+        );
+        let transcript = Transcript::from_text(&text).unwrap();
+        assert_eq!(
+            verify_transcript(&transcript),
+            Err(TranscriptError::BadHeader)
+        );
+        // a game without players, or with a single one, is no game
+        for text in ["game 5\n".to_string(), format!("game 5\n{}", player(100))] {
+            let transcript = Transcript::from_text(&text).unwrap();
+            assert_eq!(
+                verify_transcript(&transcript),
+                Err(TranscriptError::BadHeader)
+            );
+        }
+        // and neither is one with more players than the table seats
+        let mut transcript = play_game(None).transcript;
+        let public = transcript.players[0];
+        transcript.players.resize(MAX_PLAYERS + 1, public);
+        transcript.starting_points.resize(MAX_PLAYERS + 1, 100);
+        assert_eq!(
+            verify_transcript(&transcript),
+            Err(TranscriptError::BadHeader)
+        );
+    }
+
+    #[test]
+    fn truncated_transcript_is_rejected() {
+        let mut transcript = play_game(None).transcript;
+        transcript.records.pop();
+        assert_eq!(
+            verify_transcript(&transcript),
+            Err(TranscriptError::Truncated)
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Drawing cards with a VRF.
+//!
+//! Each player evaluates the schnorrkel VRF with their own keypair on a signing transcript built
+//! from the common random value of the round. The output is private until the player publishes it
+//! together with the proof, and nobody can read a card before checking the proof against the
+//! player's public key. Unlike an ed25519 signature, the VRF output is unique for a given key and
+//! input, so a player cannot grind for a better card.
+//!
+//! Every card of a hand is drawn from its own VRF evaluation, on the common random value followed
+//! by the slot of the card in the hand, so the cards of a hand are independent.
+
+use schnorrkel::{
+    signing_context,
+    vrf::{VRFInOut, VRFPreOut, VRFProof},
+    Keypair, PublicKey, SignatureResult,
+};
+
+use super::card::Card;
+
+/// The signing context used for every card draw
+pub const VRF_CONTEXT: &[u8] = b"infinite deck poker";
+
+/// The context used to turn a VRF output into random bytes for a card
+const CARD_CONTEXT: &[u8] = b"card";
+
+/// A card drawn by a player, as published to the other players: the VRF output and its proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CardDraw {
+    pub output: VRFPreOut,
+    pub proof: VRFProof,
+}
+
+/// The VRF input of the card in some slot of a hand: the common random value followed by the slot
+pub fn slot_input(common_random: &[u8], slot: usize) -> Vec<u8> {
+    let mut input = common_random.to_vec();
+    input.extend_from_slice(&(slot as u32).to_le_bytes());
+    input
+}
+
+/// The VRF input of the card drawn in some slot of a hand in a draw round: the input of the slot
+/// on the random value of the draw round, followed by the draw round for the replacement cards of
+/// five-card draw. The cards dealt at the start of the round have draw round 0 and the common
+/// random value of the round. The replacements have the common random value of their draw round,
+/// which nobody knows before every discard is announced.
+pub fn draw_input(random: &[u8], draw_round: u32, slot: usize) -> Vec<u8> {
+    let mut input = slot_input(random, slot);
+    if draw_round > 0 {
+        input.extend_from_slice(&draw_round.to_le_bytes());
+    }
+    input
+}
+
+/// Evaluate the VRF of a keypair on some input, returning the output and the draw to publish
+pub fn draw(keypair: &Keypair, input: &[u8]) -> (VRFInOut, CardDraw) {
+    let transcript = signing_context(VRF_CONTEXT).bytes(input);
+    let (in_out, proof, _) = keypair.vrf_sign(transcript);
+    let draw = CardDraw {
+        output: in_out.to_preout(),
+        proof,
+    };
+    (in_out, draw)
+}
+
+/// Verify a draw published by the owner of a public key on some input, returning the VRF output
+/// if the proof is valid.
+pub fn verify(public: &PublicKey, input: &[u8], draw: &CardDraw) -> SignatureResult<VRFInOut> {
+    let transcript = signing_context(VRF_CONTEXT).bytes(input);
+    public
+        .vrf_verify(transcript, &draw.output, &draw.proof)
+        .map(|(in_out, _)| in_out)
+}
+
+/// Convert a VRF output into a card, where every one of the 52 cards is equally likely
+pub fn card_from_output(in_out: &VRFInOut) -> Card {
+    Card::from_random_bytes(&in_out.make_bytes::<[u8; 32]>(CARD_CONTEXT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::OsRng;
+
+    #[test]
+    fn draw_verifies_with_the_same_input() {
+        let keypair = Keypair::generate_with(OsRng);
+        let (in_out, draw) = draw(&keypair, b"common random");
+        let verified = verify(&keypair.public, b"common random", &draw).unwrap();
+        assert_eq!(card_from_output(&in_out), card_from_output(&verified));
+    }
+
+    #[test]
+    fn draw_does_not_verify_with_another_input_or_key() {
+        let keypair = Keypair::generate_with(OsRng);
+        let other = Keypair::generate_with(OsRng);
+        let (_, draw) = draw(&keypair, b"common random");
+        assert!(verify(&keypair.public, b"another random", &draw).is_err());
+        assert!(verify(&other.public, b"common random", &draw).is_err());
+    }
+
+    #[test]
+    fn slots_give_independent_draws() {
+        let keypair = Keypair::generate_with(OsRng);
+        let (_, draw_0) = draw(&keypair, &slot_input(b"common random", 0));
+        let (_, draw_1) = draw(&keypair, &slot_input(b"common random", 1));
+        assert_ne!(draw_0.output, draw_1.output);
+        assert!(verify(&keypair.public, &slot_input(b"common random", 1), &draw_0).is_err());
+    }
+
+    #[test]
+    fn output_is_unique_for_key_and_input() {
+        let keypair = Keypair::generate_with(OsRng);
+        let (_, draw_1) = draw(&keypair, b"common random");
+        let (_, draw_2) = draw(&keypair, b"common random");
+        assert_eq!(draw_1.output, draw_2.output);
+    }
+}
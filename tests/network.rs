@@ -0,0 +1,52 @@
+//! A game played over the network between separate processes of the binary
+
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+const BINARY: &str = env!("CARGO_BIN_EXE_pba-cryptography-group-6-activity-3");
+
+#[test]
+fn host_and_client_processes_play_a_game() {
+    let dir = std::env::temp_dir().join(format!("poker-network-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (hosted, joined) = (dir.join("host.transcript"), dir.join("client.transcript"));
+
+    // the host listens on a free port, and says which one
+    let mut host = Command::new(BINARY)
+        .args(["host", "127.0.0.1:0", "--game-id", "7", "--transcript"])
+        .arg(&hosted)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut lines = BufReader::new(host.stdout.take().unwrap()).lines();
+    let address = lines
+        .by_ref()
+        .map(|line| line.unwrap())
+        .find_map(|line| line.split(" ON ").nth(1).map(String::from))
+        .expect("the host says where it listens");
+    let client = Command::new(BINARY)
+        .args(["join", &address, "--game-id", "7", "--transcript"])
+        .arg(&joined)
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    // read the rest of what the host says, so it never blocks on a full pipe
+    let said: Vec<String> = lines.map(|line| line.unwrap()).collect();
+    let host = host.wait().unwrap();
+
+    assert!(client.success());
+    assert!(host.success());
+    assert!(said.iter().any(|line| line.starts_with("TRANSCRIPT VERIFIED")));
+    // both players saved the very same transcript, which verifies on its own
+    let transcript = fs::read_to_string(&hosted).unwrap();
+    assert_eq!(fs::read_to_string(&joined).unwrap(), transcript);
+    let verified = Command::new(BINARY)
+        .arg("verify")
+        .arg(&hosted)
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(verified.success());
+    fs::remove_dir_all(&dir).unwrap();
+}